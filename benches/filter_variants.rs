@@ -0,0 +1,115 @@
+//! Times every `FilterVariant` over the same measurements, reported per step.
+//!
+//! Before timing anything, the setup checks that the variants agree on this well-conditioned
+//! run: the four forms of the Kalman filter to round-off, and the alpha-beta filter, whose
+//! fixed gains also correct the velocity, in filtering out most of the measurement noise.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use kalman_filter_simulator::dynamics::FilterModel;
+use kalman_filter_simulator::filter::KalmanFilter;
+use kalman_filter_simulator::metrics;
+use kalman_filter_simulator::noise::SensorNoise;
+use kalman_filter_simulator::particle::{ParticleParameters, Resampling};
+use kalman_filter_simulator::sensor::SensorModel;
+use kalman_filter_simulator::sim::{replay, simulate, SimulateConfig};
+use kalman_filter_simulator::trajectory::Trajectory;
+use kalman_filter_simulator::ukf::UnscentedParameters;
+use kalman_filter_simulator::variants::{FilterVariant, VariantFilter};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// The built-in scenario, run for 1000 steps.
+const CONFIG: SimulateConfig = SimulateConfig {
+    total_time: 100.0,
+    dt: 0.1,
+    velocity: 1.0,
+    sensor_noise_stddev: 2.0,
+    r: 4.0,
+    q: 0.01,
+    trajectory: Trajectory::ConstantVelocity,
+    sensor: SensorModel::Additive,
+    noise: SensorNoise::Gaussian,
+    initial_position: 0.0,
+    initial_position_offset: 0.0,
+    initial_velocity_offset: 0.0,
+    measurement_interval: 1,
+    timestamp_jitter: 0.0,
+    arrival_delay: 0.0,
+    reorder_window: 0.0,
+    model: FilterModel::KnownVelocity,
+    adaptive_q: None,
+    unscented: UnscentedParameters {
+        alpha: 1e-3,
+        beta: 2.0,
+        kappa: 0.0,
+    },
+    particle: ParticleParameters {
+        particles: 1000,
+        resampling: Resampling::Systematic,
+    },
+};
+
+fn filter_variants(c: &mut Criterion) {
+    let config = CONFIG;
+    let result = simulate(
+        &config,
+        &mut KalmanFilter::from_config(&config),
+        &mut StdRng::seed_from_u64(1),
+    )
+    .unwrap();
+
+    let standard = replay(
+        &result,
+        &config,
+        VariantFilter::from_config(FilterVariant::Standard, &config),
+    );
+    let measurement_rmse = metrics::measurement_rmse(&result);
+    for variant in FilterVariant::ALL {
+        let positions = replay(
+            &result,
+            &config,
+            VariantFilter::from_config(variant, &config),
+        );
+        if variant == FilterVariant::AlphaBeta {
+            let rmse = metrics::positions_rmse(&result, &positions);
+            assert!(
+                rmse < measurement_rmse / 2.0,
+                "alpha-beta RMSE {} barely improves on the measurements' {}",
+                rmse,
+                measurement_rmse
+            );
+        } else {
+            let difference = positions
+                .iter()
+                .zip(&standard)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            assert!(
+                difference < 1e-9,
+                "{} differs from the standard form by {}",
+                variant.name(),
+                difference
+            );
+        }
+    }
+
+    let mut group = c.benchmark_group("filter variants");
+    group.throughput(Throughput::Elements(result.ticks.len() as u64));
+    for variant in FilterVariant::ALL {
+        group.bench_function(variant.name(), |b| {
+            b.iter(|| {
+                replay(
+                    black_box(&result),
+                    &config,
+                    VariantFilter::from_config(variant, &config),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, filter_variants);
+criterion_main!(benches);
@@ -0,0 +1,33 @@
+//! Times the two halves of turning a frame's SVG into pixels: parsing it into a `usvg::Tree`
+//! and rasterizing that tree with `resvg`.
+//!
+//! `mid_run_frame.svg` is frame 50 of the default run with `--scale-bar --grid-lines 4
+//! --margin 20`, as written by the renderer.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg;
+
+const MID_RUN_FRAME: &str = include_str!("mid_run_frame.svg");
+const SIZE: u32 = 500;
+
+fn svg_raster(c: &mut Criterion) {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+
+    c.bench_function("usvg parse", |b| {
+        b.iter(|| usvg::Tree::from_str(MID_RUN_FRAME, &options).unwrap())
+    });
+
+    let tree = usvg::Tree::from_str(MID_RUN_FRAME, &options).unwrap();
+    let mut pixmap = Pixmap::new(SIZE, SIZE).unwrap();
+    c.bench_function("resvg render", |b| {
+        b.iter(|| {
+            pixmap.fill(tiny_skia::Color::TRANSPARENT);
+            resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+        })
+    });
+}
+
+criterion_group!(benches, svg_raster);
+criterion_main!(benches);
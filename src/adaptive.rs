@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+/// Largest factor the process noise can be raised above the configured `q`, so a single
+/// outlier cannot make the filter ignore its model for good.
+const MAX_Q_SCALE: f64 = 1000.0;
+
+/// Process noise that rises while the innovations are larger than the filter expects, such as
+/// during a maneuver, and decays back to the configured `q` once they settle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveQ {
+    /// Normalized innovation squared above which a measurement counts as a surprise. A
+    /// consistent filter averages 1.
+    pub sensitivity: f64,
+    /// Fraction of the excess over `q` kept after each unsurprising measurement
+    pub decay: f64,
+}
+
+impl AdaptiveQ {
+    /// Process noise to use after a measurement whose innovation and innovation variance are
+    /// `innovation`, given the current `q` and the configured `base_q`.
+    ///
+    /// A surprising measurement scales `q` up by how far its normalized innovation squared
+    /// exceeds `sensitivity`; any other one decays it geometrically toward `base_q`.
+    pub fn next_q(&self, q: f64, base_q: f64, (innovation, variance): (f64, f64)) -> f64 {
+        let nis = innovation * innovation / variance;
+        if nis > self.sensitivity {
+            (q * nis / self.sensitivity).min(base_q * MAX_Q_SCALE)
+        } else {
+            base_q + (q - base_q) * self.decay
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.sensitivity.is_finite() && self.sensitivity > 0.0 && (0.0..1.0).contains(&self.decay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::FilterModel;
+    use crate::sim::tests::{config, run};
+    use crate::sim::SimulateConfig;
+    use crate::trajectory::Trajectory;
+
+    /// Mean absolute estimate error of `config` over the ticks within a few seconds after its
+    /// maneuver ends at 4 s, across several seeds.
+    fn post_maneuver_error(config: &SimulateConfig) -> f64 {
+        let errors: Vec<f64> = (1..=8)
+            .flat_map(|seed| run(config, seed).ticks)
+            .filter(|tick| (4.0..8.0).contains(&tick.true_positions.0))
+            .map(|tick| (tick.estimated_positions.1 - tick.true_positions.1).abs())
+            .collect();
+        errors.iter().sum::<f64>() / errors.len() as f64
+    }
+
+    #[test]
+    fn adaptive_q_recovers_faster_from_a_maneuver() {
+        let fixed = SimulateConfig {
+            total_time: 12.0,
+            trajectory: Trajectory::Maneuver {
+                start_time: 3.0,
+                duration: 1.0,
+                acceleration: 5.0,
+            },
+            model: FilterModel::ConstantVelocity,
+            ..config()
+        };
+        let adaptive = SimulateConfig {
+            adaptive_q: Some(AdaptiveQ {
+                sensitivity: 4.0,
+                decay: 0.9,
+            }),
+            ..fixed
+        };
+        let fixed_error = post_maneuver_error(&fixed);
+        let adaptive_error = post_maneuver_error(&adaptive);
+        assert!(
+            adaptive_error < fixed_error * 0.8,
+            "adaptive {} vs fixed {}",
+            adaptive_error,
+            fixed_error
+        );
+    }
+
+    #[test]
+    fn unsurprising_measurements_decay_q_to_its_base() {
+        let adaptive = AdaptiveQ {
+            sensitivity: 4.0,
+            decay: 0.5,
+        };
+        let raised = adaptive.next_q(0.01, 0.01, (10.0, 1.0));
+        assert_eq!(raised, 0.01 * 100.0 / 4.0);
+        let mut q = raised;
+        for _ in 0..60 {
+            q = adaptive.next_q(q, 0.01, (0.5, 1.0));
+        }
+        assert!((q - 0.01).abs() < 1e-12);
+    }
+}
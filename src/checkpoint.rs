@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use kalman_filter_simulator::filter::KalmanFilter;
+use kalman_filter_simulator::sim::{Checkpoint, SimulateTick};
+
+/// First line of a checkpoint file.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    /// Seed of the `StdRng` all of the run's noise comes from
+    seed: u64,
+}
+
+/// Every later line of a checkpoint file: the filter at a save, and the ticks filtered since
+/// the previous save. Appending these keeps each save as small as `--checkpoint-every` ticks,
+/// however long the run.
+#[derive(Serialize)]
+struct Save<'a> {
+    filter: &'a KalmanFilter,
+    ticks: &'a [SimulateTick],
+}
+
+/// A `Save` read back.
+#[derive(Deserialize)]
+struct SavedLine {
+    filter: KalmanFilter,
+    ticks: Vec<SimulateTick>,
+}
+
+/// Reads the seed off the first line of the checkpoint file at `path`, without the rest.
+pub fn read_seed(path: &Path) -> Result<u64, Box<dyn Error>> {
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    let Header { seed } = serde_json::from_str(&line)?;
+    Ok(seed)
+}
+
+/// Reads the checkpoint file at `path` back into the checkpoint of its latest save, starting
+/// from `filter` if it has none yet. A crash while appending leaves the last line cut short;
+/// it is skipped, so the run carries on from the save before it.
+pub fn read_checkpoint(
+    path: &Path,
+    filter: KalmanFilter,
+) -> Result<Checkpoint<KalmanFilter>, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    let Header { seed } = serde_json::from_str(lines.next().unwrap_or_default())?;
+    let mut checkpoint = Checkpoint::new(seed, filter);
+    let mut lines = lines.peekable();
+    while let Some(line) = lines.next() {
+        match serde_json::from_str::<SavedLine>(line) {
+            Ok(saved) => {
+                checkpoint.filter = saved.filter;
+                checkpoint.result.ticks.extend(saved.ticks);
+            }
+            Err(_) if lines.peek().is_none() => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(checkpoint)
+}
+
+/// Writes `checkpoint` to `path` as a fresh checkpoint file for `append_save` to extend,
+/// replacing any previous file in one go so a crash mid-write can't lose it.
+pub fn write_checkpoint(
+    path: &Path,
+    checkpoint: &Checkpoint<KalmanFilter>,
+) -> Result<(), Box<dyn Error>> {
+    let mut contents = serde_json::to_string(&Header {
+        seed: checkpoint.seed,
+    })?;
+    contents.push('\n');
+    if !checkpoint.result.ticks.is_empty() {
+        contents += &save_line(&checkpoint.filter, &checkpoint.result.ticks)?;
+    }
+    let partial = path.with_extension("partial");
+    fs::write(&partial, contents)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Appends a save of `filter` and the `ticks` filtered since the previous save to the
+/// checkpoint file at `path`.
+pub fn append_save(
+    path: &Path,
+    filter: &KalmanFilter,
+    ticks: &[SimulateTick],
+) -> Result<(), Box<dyn Error>> {
+    let line = save_line(filter, ticks)?;
+    OpenOptions::new()
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+fn save_line(filter: &KalmanFilter, ticks: &[SimulateTick]) -> serde_json::Result<String> {
+    let mut line = serde_json::to_string(&Save { filter, ticks })?;
+    line.push('\n');
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use kalman_filter_simulator::sim::simulate_checkpointed;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir, seeded_run};
+
+    #[test]
+    fn saves_append_and_read_back_to_the_whole_run() {
+        let config = default_config();
+        let path = scratch_dir("checkpoint").join("run.ckpt");
+        let mut checkpoint = Checkpoint::new(1, KalmanFilter::from_config(&config));
+        write_checkpoint(&path, &checkpoint).unwrap();
+        simulate_checkpointed(&config, &mut checkpoint, 30, |checkpoint, ticks| {
+            append_save(&path, &checkpoint.filter, ticks)
+        })
+        .unwrap();
+        let straight = serde_json::to_string(&seeded_run(&config)).unwrap();
+        assert_eq!(serde_json::to_string(&checkpoint.result).unwrap(), straight);
+
+        // The header and one line of 30 ticks per save
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 1 + config.steps() / 30);
+        assert_eq!(read_seed(&path).unwrap(), 1);
+        let read = read_checkpoint(&path, KalmanFilter::from_config(&config)).unwrap();
+        assert_eq!(read.result.ticks.len(), config.steps() / 30 * 30);
+
+        // A save cut short by a crash falls back on the one before it, whose filter carries on
+        // as the uninterrupted run did
+        fs::write(&path, &text[..text.len() - 20]).unwrap();
+        let mut resumed = read_checkpoint(&path, KalmanFilter::from_config(&config)).unwrap();
+        assert_eq!(resumed.result.ticks.len(), (config.steps() / 30 - 1) * 30);
+        simulate_checkpointed(
+            &config,
+            &mut resumed,
+            30,
+            |_, _| Ok::<_, Box<dyn Error>>(()),
+        )
+        .unwrap();
+        assert_eq!(serde_json::to_string(&resumed.result).unwrap(), straight);
+
+        // Anywhere else, a broken line is an error
+        let mut lines: Vec<&str> = text.lines().collect();
+        lines[1] = "{";
+        fs::write(&path, lines.join("\n")).unwrap();
+        assert!(read_checkpoint(&path, KalmanFilter::from_config(&config)).is_err());
+    }
+}
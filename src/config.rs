@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::{NonZeroU16, NonZeroU64};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use kalman_filter_simulator::adaptive::AdaptiveQ;
+use kalman_filter_simulator::dynamics::FilterModel;
+use kalman_filter_simulator::noise::SensorNoise;
+use kalman_filter_simulator::particle::ParticleParameters;
+use kalman_filter_simulator::render::{Easing, LineCap, LineJoin, MarkerShape, PlotKind, Theme};
+use kalman_filter_simulator::sensor::SensorModel;
+use kalman_filter_simulator::sim::SimulateConfig;
+use kalman_filter_simulator::trajectory::Trajectory;
+use kalman_filter_simulator::ukf::UnscentedParameters;
+
+/// Simulation parameters as written in a TOML or YAML scenario file.
+///
+/// Every field is optional and falls back to the built-in default:
+///
+/// ```toml
+/// dt = 0.05
+/// q = 0.001
+/// initial_position_offset = 5.0
+/// trajectory = { kind = "ramp-hold", stop_time = 4.0 }
+///
+/// [render]
+/// theme = "classic"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub total_time: f64,
+    pub dt: f64,
+    pub velocity: f64,
+    pub sensor_noise_stddev: f64,
+    /// Measurement variance assumed by the filter, `sensor_noise_stddev²` when omitted
+    pub r: Option<f64>,
+    pub q: f64,
+    pub trajectory: Trajectory,
+    pub sensor: SensorModel,
+    /// Shape of the sensor noise, e.g. `noise = { kind = "student-t", degrees_of_freedom = 4.0 }`
+    pub noise: SensorNoise,
+    pub initial_position: f64,
+    pub initial_position_offset: f64,
+    pub initial_velocity_offset: f64,
+    pub measurement_interval: usize,
+    pub timestamp_jitter: f64,
+    pub arrival_delay: f64,
+    pub reorder_window: f64,
+    pub model: FilterModel,
+    /// Raise `q` during maneuvers, e.g. `adaptive_q = { sensitivity = 4.0, decay = 0.8 }`
+    pub adaptive_q: Option<AdaptiveQ>,
+    /// Sigma points of the `unscented` model, e.g. `unscented = { alpha = 0.5, kappa = 1.0 }`
+    pub unscented: UnscentedParameters,
+    /// Particles of the `particle` model, e.g. `particle = { particles = 500, resampling = "residual" }`
+    pub particle: ParticleParameters,
+    pub render: RenderSettings,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            total_time: 10.0,
+            dt: 0.1,
+            velocity: 1.0,
+            sensor_noise_stddev: 2.0,
+            r: None,
+            q: 0.01,
+            trajectory: Trajectory::ConstantVelocity,
+            sensor: SensorModel::Additive,
+            noise: SensorNoise::Gaussian,
+            initial_position: 0.0,
+            initial_position_offset: 0.0,
+            initial_velocity_offset: 0.0,
+            measurement_interval: 1,
+            timestamp_jitter: 0.0,
+            arrival_delay: 0.0,
+            reorder_window: 0.0,
+            model: FilterModel::KnownVelocity,
+            adaptive_q: None,
+            unscented: UnscentedParameters::default(),
+            particle: ParticleParameters::default(),
+            render: RenderSettings::default(),
+        }
+    }
+}
+
+/// How a scenario is plotted, as written in its `render` table. Each setting is optional
+/// like the simulation parameters, and its flag overrides it.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub plot: PlotKind,
+    pub theme: Theme,
+    pub marker: MarkerShape,
+    pub easing: Easing,
+    pub line_join: LineJoin,
+    pub line_cap: LineCap,
+    pub grid_lines: usize,
+    pub margin: f64,
+    /// Only make every this many ticks a frame
+    pub frame_stride: NonZeroU64,
+    /// Render at most this many frames, one per tick when omitted
+    pub max_frames: Option<NonZeroU64>,
+    /// Frames per second of the encoded animation
+    pub fps: NonZeroU16,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            plot: PlotKind::Position,
+            theme: Theme::OkabeIto,
+            marker: MarkerShape::Circle,
+            easing: Easing::Linear,
+            line_join: LineJoin::Round,
+            line_cap: LineCap::Round,
+            grid_lines: 0,
+            margin: 0.0,
+            frame_stride: NonZeroU64::MIN,
+            max_frames: None,
+            fps: NonZeroU16::new(10).unwrap(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Checks that the margin leaves some of a pane of `size` pixels for the data.
+    pub fn validate(&self, size: usize) -> Result<(), InvalidMargin> {
+        if (0.0..size as f64 / 2.0).contains(&self.margin) {
+            Ok(())
+        } else {
+            Err(InvalidMargin {
+                margin: self.margin,
+                size,
+            })
+        }
+    }
+}
+
+/// Margin that is negative or leaves no room for the data in a pane of `size` pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidMargin {
+    pub margin: f64,
+    pub size: usize,
+}
+
+impl fmt::Display for InvalidMargin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "margin must be at least 0 and less than {} pixels, got {}",
+            self.size as f64 / 2.0,
+            self.margin
+        )
+    }
+}
+
+impl Error for InvalidMargin {}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    ParseYaml {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                write!(f, "cannot read {}: {}", path.display(), source)
+            }
+            ConfigError::Parse { path, source } => {
+                write!(f, "invalid scenario {}: {}", path.display(), source)
+            }
+            ConfigError::ParseYaml { path, source } => {
+                write!(f, "invalid scenario {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::ParseYaml { source, .. } => Some(source),
+        }
+    }
+}
+
+impl Scenario {
+    /// Reads the scenario at `path`, as YAML for a `.yaml` or `.yml` file and as TOML
+    /// otherwise.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        if is_yaml(path) {
+            serde_yaml::from_str(&text).map_err(|source| ConfigError::ParseYaml {
+                path: path.to_path_buf(),
+                source,
+            })
+        } else {
+            toml::from_str(&text).map_err(|source| ConfigError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    }
+
+    pub fn to_config(&self) -> SimulateConfig {
+        SimulateConfig {
+            total_time: self.total_time,
+            dt: self.dt,
+            velocity: self.velocity,
+            sensor_noise_stddev: self.sensor_noise_stddev,
+            r: self.r.unwrap_or(self.sensor_noise_stddev.powi(2)),
+            q: self.q,
+            trajectory: self.trajectory,
+            sensor: self.sensor,
+            noise: self.noise,
+            initial_position: self.initial_position,
+            initial_position_offset: self.initial_position_offset,
+            initial_velocity_offset: self.initial_velocity_offset,
+            measurement_interval: self.measurement_interval,
+            timestamp_jitter: self.timestamp_jitter,
+            arrival_delay: self.arrival_delay,
+            reorder_window: self.reorder_window,
+            model: self.model,
+            adaptive_q: self.adaptive_q,
+            unscented: self.unscented,
+            particle: self.particle,
+        }
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml")
+}
+
+/// Expands directories in `paths` into the `.toml`, `.yaml` and `.yml` files they contain,
+/// sorted by name.
+pub fn expand_config_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, ConfigError> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            expanded.push(path.clone());
+            continue;
+        }
+        let entries = fs::read_dir(path).map_err(|source| ConfigError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|source| ConfigError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            let file = entry.path();
+            if is_yaml(&file)
+                || file
+                    .extension()
+                    .is_some_and(|extension| extension == "toml")
+            {
+                files.push(file);
+            }
+        }
+        files.sort();
+        expanded.extend(files);
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn margin_must_leave_room_for_the_data() {
+        let with = |margin| {
+            RenderSettings {
+                margin,
+                ..RenderSettings::default()
+            }
+            .validate(500)
+        };
+        assert_eq!(with(0.0), Ok(()));
+        assert_eq!(with(249.0), Ok(()));
+        for margin in [-1.0, 250.0, 300.0, f64::NAN] {
+            assert_eq!(with(margin).err().map(|err| err.size), Some(500));
+        }
+    }
+}
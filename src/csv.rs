@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use kalman_filter_simulator::sim::{RecordedMeasurement, SimulateResult};
+
+/// Writes `SimulateResult::to_csv` to the file at `path`.
+pub fn write_csv(result: &SimulateResult, path: &Path, x_column: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    result.to_csv(&mut writer, x_column)?;
+    writer.flush()
+}
+
+/// Reads logged measurements from the CSV file at `path`, whose header names a `time` and a
+/// `measurement` column, and optionally a `true_position` column, in any order. An empty
+/// `measurement` marks a tick without one, and an empty `true_position` one without a logged
+/// truth. The times must not decrease.
+pub fn read_measurements(path: &Path) -> Result<Vec<RecordedMeasurement>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or("the measurement CSV is empty")?;
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| columns.iter().position(|&column| column == name);
+    let time_column = column("time").ok_or("the measurement CSV has no `time` column")?;
+    let measurement_column =
+        column("measurement").ok_or("the measurement CSV has no `measurement` column")?;
+    let true_position_column = column("true_position");
+
+    let mut measurements: Vec<RecordedMeasurement> = Vec::new();
+    for (index, line) in lines {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |column: usize| -> Result<Option<f64>, String> {
+            match fields.get(column).copied().unwrap_or("") {
+                "" => Ok(None),
+                value => value
+                    .parse()
+                    .map(Some)
+                    .map_err(|err| format!("line {}: {}: {}", index + 1, value, err)),
+            }
+        };
+        let time = field(time_column)?.ok_or(format!("line {}: no time", index + 1))?;
+        if measurements.last().is_some_and(|last| time < last.time) {
+            return Err(format!("line {}: time {} goes backwards", index + 1, time).into());
+        }
+        measurements.push(RecordedMeasurement {
+            time,
+            measurement: field(measurement_column)?,
+            true_position: true_position_column.map(field).transpose()?.flatten(),
+        });
+    }
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use kalman_filter_simulator::sim::SimulateConfig;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir, seeded_run};
+
+    #[test]
+    fn rows_are_as_wide_as_the_header() {
+        let result = seeded_run(&SimulateConfig {
+            measurement_interval: 2,
+            ..default_config()
+        });
+        let path = scratch_dir("csv").join("run.csv");
+        write_csv(&result, &path, "time[s]").unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut lines = contents.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(header[0], "time[s]");
+        assert_eq!(&header[header.len() - 2..], ["covariance", "gain"]);
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), result.ticks.len());
+        for row in rows {
+            assert_eq!(row.split(',').count(), header.len(), "{}", row);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use clap::ValueEnum;
+use nalgebra::{Matrix1, Matrix2, Matrix3, SMatrix, SVector, Vector1};
+use serde::{Deserialize, Serialize};
+
+use crate::filter::Filter;
+use crate::linear::KalmanFilter;
+use crate::sim::SimulateConfig;
+
+/// State a filter estimates, and so which motion it can follow without lagging.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterModel {
+    /// Position only, predicted with the nominal velocity. `q` is added to the variance every
+    /// tick.
+    KnownVelocity,
+    /// Position and velocity. `q` is the spectral density of the white-noise acceleration.
+    ConstantVelocity,
+    /// Position, velocity and acceleration. `q` is the spectral density of the white-noise
+    /// jerk.
+    ConstantAcceleration,
+    /// Position and velocity like `ConstantVelocity`, through an extended Kalman filter that
+    /// linearizes the sensor around the estimate, as the range sensor needs.
+    Extended,
+    /// Position and velocity like `Extended`, through an unscented Kalman filter that passes
+    /// sigma points through the sensor instead of linearizing it.
+    Unscented,
+    /// Position and velocity like `ConstantVelocity`, through a particle filter that weighs a
+    /// cloud of samples by each measurement instead of assuming a Gaussian estimate.
+    Particle,
+}
+
+impl FilterModel {
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterModel::KnownVelocity => "known-velocity",
+            FilterModel::ConstantVelocity => "constant-velocity",
+            FilterModel::ConstantAcceleration => "constant-acceleration",
+            FilterModel::Extended => "extended",
+            FilterModel::Unscented => "unscented",
+            FilterModel::Particle => "particle",
+        }
+    }
+
+    /// Whether the model can use a sensor that is nonlinear in the position.
+    pub fn is_nonlinear(self) -> bool {
+        matches!(
+            self,
+            FilterModel::Extended | FilterModel::Unscented | FilterModel::Particle
+        )
+    }
+}
+
+/// Motion model of a linear Kalman filter over an `N`-dimensional state whose first component
+/// is the position. The sensor always measures that position.
+pub trait Dynamics<const N: usize> {
+    /// State transition matrix over a step of `dt`.
+    fn transition(&self, dt: f64) -> SMatrix<f64, N, N>;
+
+    /// Process noise covariance accumulated over a step of `dt`, for a white-noise driving
+    /// term of spectral density `q`.
+    fn process_noise(&self, q: f64, dt: f64) -> SMatrix<f64, N, N>;
+}
+
+/// Position and velocity, driven by white-noise acceleration.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantVelocity;
+
+impl Dynamics<2> for ConstantVelocity {
+    fn transition(&self, dt: f64) -> Matrix2<f64> {
+        Matrix2::new(1.0, dt, 0.0, 1.0)
+    }
+
+    fn process_noise(&self, q: f64, dt: f64) -> Matrix2<f64> {
+        let (dt2, dt3) = (dt * dt, dt * dt * dt);
+        Matrix2::new(dt3 / 3.0, dt2 / 2.0, dt2 / 2.0, dt) * q
+    }
+}
+
+/// Position, velocity and acceleration, driven by white-noise jerk.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantAcceleration;
+
+impl Dynamics<3> for ConstantAcceleration {
+    fn transition(&self, dt: f64) -> Matrix3<f64> {
+        #[rustfmt::skip]
+        let f = Matrix3::new(
+            1.0, dt,  dt * dt / 2.0,
+            0.0, 1.0, dt,
+            0.0, 0.0, 1.0,
+        );
+        f
+    }
+
+    fn process_noise(&self, q: f64, dt: f64) -> Matrix3<f64> {
+        let (dt2, dt3, dt4, dt5) = (dt.powi(2), dt.powi(3), dt.powi(4), dt.powi(5));
+        #[rustfmt::skip]
+        let noise = Matrix3::new(
+            dt5 / 20.0, dt4 / 8.0, dt3 / 6.0,
+            dt4 / 8.0,  dt3 / 3.0, dt2 / 2.0,
+            dt3 / 6.0,  dt2 / 2.0, dt,
+        );
+        noise * q
+    }
+}
+
+/// Kalman filter over the state of `D`, estimating the velocity (and acceleration) along with
+/// the position instead of taking the velocity as known.
+///
+/// A `linear::KalmanFilter` measuring the first state component does the estimation; this
+/// rebuilds its `F` and `Q` for each step's `dt`.
+pub struct ModelFilter<D, const N: usize> {
+    dynamics: D,
+    filter: KalmanFilter<N, 1>,
+    /// Spectral density of the driving white noise, from which each step's `Q` is built
+    q: f64,
+}
+
+impl<D: Dynamics<N>, const N: usize> ModelFilter<D, N> {
+    /// Starts from the initial guess described by `config`, with the higher derivatives at
+    /// zero and unit variance on every component.
+    pub fn from_config(dynamics: D, config: &SimulateConfig) -> Self {
+        let mut x = SVector::zeros();
+        x[0] = config.initial_position + config.initial_position_offset;
+        if N > 1 {
+            x[1] = config.velocity + config.initial_velocity_offset;
+        }
+        let mut h = SMatrix::zeros();
+        h[0] = 1.0;
+        ModelFilter {
+            dynamics,
+            filter: KalmanFilter::new(
+                x,
+                SMatrix::identity(),
+                SMatrix::identity(),
+                h,
+                SMatrix::zeros(),
+                Matrix1::new(config.r),
+            ),
+            q: config.q,
+        }
+    }
+}
+
+impl<D: Dynamics<N>, const N: usize> Filter for ModelFilter<D, N> {
+    fn predict(&mut self, dt: f64) {
+        self.filter.f = self.dynamics.transition(dt);
+        self.filter.q = self.dynamics.process_noise(self.q, dt);
+        self.filter.predict();
+    }
+
+    fn update(&mut self, measured_position: f64) {
+        self.filter.update(&Vector1::new(measured_position));
+    }
+
+    fn update_with_variance(&mut self, measured_position: f64, r: f64) {
+        self.filter
+            .update_with_covariance(&Vector1::new(measured_position), &Matrix1::new(r));
+    }
+
+    fn position(&self) -> f64 {
+        self.filter.x[0]
+    }
+
+    fn velocity(&self) -> f64 {
+        if N > 1 {
+            self.filter.x[1]
+        } else {
+            0.0
+        }
+    }
+
+    fn uncertainty(&self) -> f64 {
+        self.filter.p[(0, 0)]
+    }
+
+    fn gain(&self) -> f64 {
+        self.filter.gain()[0]
+    }
+
+    fn innovation(&self) -> (f64, f64) {
+        (
+            self.filter.innovation()[0],
+            self.filter.innovation_covariance()[0],
+        )
+    }
+
+    fn process_noise(&self) -> f64 {
+        self.q
+    }
+
+    fn set_process_noise(&mut self, q: f64) {
+        self.q = q;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::{config, run};
+    use crate::trajectory::Trajectory;
+
+    /// Estimate RMSE of `model` over the accelerating stretch of a maneuver from 3 s to 6 s,
+    /// pooled over a few seeds.
+    fn maneuver_rmse(model: FilterModel) -> f64 {
+        let config = SimulateConfig {
+            total_time: 10.0,
+            trajectory: Trajectory::Maneuver {
+                start_time: 3.0,
+                duration: 3.0,
+                acceleration: 2.0,
+            },
+            model,
+            ..config()
+        };
+        let errors: Vec<f64> = (1..=5)
+            .flat_map(|seed| run(&config, seed).ticks)
+            .filter(|tick| (3.0..6.0).contains(&tick.true_positions.0))
+            .map(|tick| tick.estimated_positions.1 - tick.true_positions.1)
+            .collect();
+        (errors.iter().map(|error| error * error).sum::<f64>() / errors.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn constant_acceleration_tracks_a_maneuver_better() {
+        let constant_velocity = maneuver_rmse(FilterModel::ConstantVelocity);
+        let constant_acceleration = maneuver_rmse(FilterModel::ConstantAcceleration);
+        assert!(
+            constant_acceleration < constant_velocity,
+            "{} vs {}",
+            constant_acceleration,
+            constant_velocity
+        );
+    }
+}
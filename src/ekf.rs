@@ -0,0 +1,268 @@
+use nalgebra::{Matrix1, Matrix1x2, SMatrix, SVector, Vector1, Vector2};
+
+use crate::dynamics::{ConstantVelocity, Dynamics};
+use crate::filter::Filter;
+use crate::sensor::SensorModel;
+use crate::sim::SimulateConfig;
+
+/// Nonlinear motion and measurement of an `N`-dimensional state observed through
+/// `M`-dimensional measurements.
+pub trait NonlinearModel<const N: usize, const M: usize> {
+    /// State `x` advanced by a step of `dt`.
+    fn transition(&self, x: &SVector<f64, N>, dt: f64) -> SVector<f64, N>;
+
+    /// Process noise covariance accumulated over a step of `dt`, for a white-noise driving
+    /// term of spectral density `q`.
+    fn process_noise(&self, q: f64, dt: f64) -> SMatrix<f64, N, N>;
+
+    /// Noise-free measurement of the state `x`.
+    fn measurement(&self, x: &SVector<f64, N>) -> SVector<f64, M>;
+}
+
+/// Jacobians of a `NonlinearModel`, which an extended Kalman filter linearizes it with.
+pub trait Jacobians<const N: usize, const M: usize>: NonlinearModel<N, M> {
+    /// Jacobian of `transition` with respect to the state, at `x`.
+    fn transition_jacobian(&self, x: &SVector<f64, N>, dt: f64) -> SMatrix<f64, N, N>;
+
+    /// Jacobian of `measurement` with respect to the state, at `x`.
+    fn measurement_jacobian(&self, x: &SVector<f64, N>) -> SMatrix<f64, M, N>;
+}
+
+/// Extended Kalman filter: runs the nonlinear `model` for the estimate itself, and propagates
+/// the covariance through its Jacobians at the current estimate.
+#[derive(Debug, Clone)]
+pub struct ExtendedKalmanFilter<T, const N: usize, const M: usize> {
+    model: T,
+    /// State estimate
+    pub x: SVector<f64, N>,
+    /// Covariance of the state estimate
+    pub p: SMatrix<f64, N, N>,
+    /// Spectral density of the driving white noise
+    pub q: f64,
+    /// Measurement noise covariance
+    pub r: SMatrix<f64, M, M>,
+    k: SMatrix<f64, N, M>,
+    innovation: SVector<f64, M>,
+    innovation_covariance: SMatrix<f64, M, M>,
+}
+
+impl<T: Jacobians<N, M>, const N: usize, const M: usize> ExtendedKalmanFilter<T, N, M> {
+    /// Starts from the estimate `x` with covariance `p`, before any update.
+    pub fn new(
+        model: T,
+        x: SVector<f64, N>,
+        p: SMatrix<f64, N, N>,
+        q: f64,
+        r: SMatrix<f64, M, M>,
+    ) -> Self {
+        ExtendedKalmanFilter {
+            model,
+            x,
+            p,
+            q,
+            r,
+            k: SMatrix::zeros(),
+            innovation: SVector::zeros(),
+            innovation_covariance: SMatrix::zeros(),
+        }
+    }
+
+    /// Propagates the estimate a step of `dt`: `x ← f(x)`, `P ← F P Fᵀ + Q` with `F` the
+    /// transition Jacobian at the previous estimate.
+    pub fn predict(&mut self, dt: f64) {
+        let f = self.model.transition_jacobian(&self.x, dt);
+        self.x = self.model.transition(&self.x, dt);
+        self.p = f * self.p * f.transpose() + self.model.process_noise(self.q, dt);
+    }
+
+    /// Corrects the estimate with the measurement `z`.
+    pub fn update(&mut self, z: &SVector<f64, M>) {
+        let r = self.r;
+        self.update_with_covariance(z, &r);
+    }
+
+    /// Like `update`, but assumes measurement noise covariance `r` for this measurement only.
+    ///
+    /// As in `linear::KalmanFilter`, an innovation covariance that is not positive definite
+    /// turns the estimate NaN.
+    pub fn update_with_covariance(&mut self, z: &SVector<f64, M>, r: &SMatrix<f64, M, M>) {
+        let h = self.model.measurement_jacobian(&self.x);
+        let innovation = z - self.model.measurement(&self.x);
+        let s = h * self.p * h.transpose() + r;
+        let hp = h * self.p;
+        self.k = match s.cholesky() {
+            Some(cholesky) => cholesky.solve(&hp).transpose(),
+            None => SMatrix::repeat(f64::NAN),
+        };
+        self.x += self.k * innovation;
+        self.p = (SMatrix::<f64, N, N>::identity() - self.k * h) * self.p;
+        self.innovation = innovation;
+        self.innovation_covariance = s;
+    }
+
+    /// Gain `K` applied by the latest update, linearized at the predicted estimate.
+    pub fn gain(&self) -> &SMatrix<f64, N, M> {
+        &self.k
+    }
+
+    /// Innovation `z - h(x)` of the latest update.
+    pub fn innovation(&self) -> &SVector<f64, M> {
+        &self.innovation
+    }
+
+    /// Predicted covariance `H P Hᵀ + R` of the latest innovation.
+    pub fn innovation_covariance(&self) -> &SMatrix<f64, M, M> {
+        &self.innovation_covariance
+    }
+}
+
+/// Position and velocity moving at constant velocity, seen through whatever `sensor` reports
+/// about the position, like the distance to a `SensorModel::Range` station.
+#[derive(Debug, Clone, Copy)]
+pub struct Tracking {
+    pub sensor: SensorModel,
+}
+
+impl NonlinearModel<2, 1> for Tracking {
+    fn transition(&self, x: &Vector2<f64>, dt: f64) -> Vector2<f64> {
+        ConstantVelocity.transition(dt) * x
+    }
+
+    fn process_noise(&self, q: f64, dt: f64) -> SMatrix<f64, 2, 2> {
+        ConstantVelocity.process_noise(q, dt)
+    }
+
+    fn measurement(&self, x: &Vector2<f64>) -> Vector1<f64> {
+        Vector1::new(self.sensor.expected(x[0]))
+    }
+}
+
+impl Jacobians<2, 1> for Tracking {
+    fn transition_jacobian(&self, _x: &Vector2<f64>, dt: f64) -> SMatrix<f64, 2, 2> {
+        ConstantVelocity.transition(dt)
+    }
+
+    fn measurement_jacobian(&self, x: &Vector2<f64>) -> Matrix1x2<f64> {
+        Matrix1x2::new(self.sensor.slope(x[0]), 0.0)
+    }
+}
+
+impl ExtendedKalmanFilter<Tracking, 2, 1> {
+    /// Tracks the sensor of `config` from its initial guess, with unit variance on the
+    /// position and velocity like `ModelFilter`.
+    pub fn from_config(config: &SimulateConfig) -> Self {
+        ExtendedKalmanFilter::new(
+            Tracking {
+                sensor: config.sensor,
+            },
+            Vector2::new(
+                config.initial_position + config.initial_position_offset,
+                config.velocity + config.initial_velocity_offset,
+            ),
+            SMatrix::identity(),
+            config.q,
+            Matrix1::new(config.r),
+        )
+    }
+}
+
+impl<T: Jacobians<N, 1>, const N: usize> Filter for ExtendedKalmanFilter<T, N, 1> {
+    fn predict(&mut self, dt: f64) {
+        ExtendedKalmanFilter::predict(self, dt);
+    }
+
+    fn update(&mut self, measurement: f64) {
+        ExtendedKalmanFilter::update(self, &Vector1::new(measurement));
+    }
+
+    fn update_with_variance(&mut self, measurement: f64, r: f64) {
+        self.update_with_covariance(&Vector1::new(measurement), &Matrix1::new(r));
+    }
+
+    fn position(&self) -> f64 {
+        self.x[0]
+    }
+
+    fn velocity(&self) -> f64 {
+        if N > 1 {
+            self.x[1]
+        } else {
+            0.0
+        }
+    }
+
+    fn uncertainty(&self) -> f64 {
+        self.p[(0, 0)]
+    }
+
+    fn gain(&self) -> f64 {
+        self.k[0]
+    }
+
+    fn innovation(&self) -> (f64, f64) {
+        (self.innovation[0], self.innovation_covariance[0])
+    }
+
+    fn process_noise(&self) -> f64 {
+        self.q
+    }
+
+    fn set_process_noise(&mut self, q: f64) {
+        self.q = q;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::FilterModel;
+    use crate::linear::KalmanFilter;
+    use crate::metrics;
+    use crate::sim::tests::{config, run};
+
+    #[test]
+    fn linear_sensor_matches_the_linear_filter() {
+        let config = SimulateConfig {
+            model: FilterModel::Extended,
+            ..config()
+        };
+        let mut extended = ExtendedKalmanFilter::from_config(&config);
+        let mut linear = KalmanFilter::new(
+            extended.x,
+            extended.p,
+            ConstantVelocity.transition(config.dt),
+            Matrix1x2::new(1.0, 0.0),
+            ConstantVelocity.process_noise(config.q, config.dt),
+            Matrix1::new(config.r),
+        );
+        for tick in run(&config, 1).ticks {
+            let z = Vector1::new(tick.measurement.unwrap());
+            extended.predict(config.dt);
+            extended.update(&z);
+            linear.predict();
+            linear.update(&z);
+            assert!((extended.x - linear.x).abs().max() < 1e-12);
+            assert!((extended.p - linear.p).abs().max() < 1e-12);
+            assert!((extended.gain() - linear.gain()).abs().max() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn range_sensor_estimate_stays_close_to_the_truth() {
+        let config = SimulateConfig {
+            total_time: 30.0,
+            sensor: SensorModel::Range {
+                station: 15.0,
+                offset: 5.0,
+            },
+            model: FilterModel::Extended,
+            q: 0.1,
+            ..config()
+        };
+        for seed in 1..=3 {
+            let result = run(&config, seed);
+            let rmse = metrics::estimate_rmse(&result);
+            assert!(rmse < config.sensor_noise_stddev, "seed {}: {}", seed, rmse);
+        }
+    }
+}
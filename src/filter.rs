@@ -0,0 +1,291 @@
+use nalgebra::{Matrix1, Vector1};
+use serde::{Deserialize, Serialize};
+
+use crate::linear;
+use crate::sim::SimulateConfig;
+
+/// Position and velocity of the target, true or estimated.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct State {
+    pub x: f64,
+    pub v: f64,
+}
+
+/// Common interface of the estimators `simulate` can drive.
+pub trait Filter {
+    /// Propagates the estimate `dt` forward in time.
+    fn predict(&mut self, dt: f64);
+
+    /// Corrects the estimate with a position measurement.
+    fn update(&mut self, measured_position: f64);
+
+    /// Like `update`, but assumes measurement variance `r` for this measurement only.
+    fn update_with_variance(&mut self, measured_position: f64, r: f64);
+
+    /// Current position estimate.
+    fn position(&self) -> f64;
+
+    /// Current velocity estimate.
+    fn velocity(&self) -> f64;
+
+    /// Variance of the position estimate.
+    fn uncertainty(&self) -> f64;
+
+    /// Gain applied to the innovation by the latest `update`.
+    fn gain(&self) -> f64;
+
+    /// Innovation (measurement minus predicted position) of the latest `update`, and its
+    /// predicted variance.
+    fn innovation(&self) -> (f64, f64);
+
+    /// Process noise `q` the next `predict` adds.
+    fn process_noise(&self) -> f64;
+
+    /// Replaces the process noise `q` for the following predictions.
+    fn set_process_noise(&mut self, q: f64);
+
+    /// Feeds one measurement taken `dt` after the previous one and returns the new estimate.
+    ///
+    /// This is the whole online loop: call it whenever a measurement arrives.
+    ///
+    /// ```
+    /// use kalman_filter_simulator::filter::{Filter, KalmanFilter};
+    ///
+    /// let mut filter = KalmanFilter::new(0.0, 1.0, 4.0, 0.01);
+    /// let mut variances = Vec::new();
+    /// for (i, measured) in [0.4, 0.1, 0.5].into_iter().enumerate() {
+    ///     let estimate = filter.step(0.1, measured);
+    ///     println!("after measurement {}: x = {:.3}", i + 1, estimate.x);
+    ///     variances.push(filter.uncertainty());
+    /// }
+    /// // Each measurement makes the filter surer of its estimate
+    /// assert!(variances.windows(2).all(|pair| pair[1] < pair[0]));
+    /// assert!(filter.position() > 0.0 && filter.position() < 0.5);
+    /// ```
+    fn step(&mut self, dt: f64, measured_position: f64) -> State {
+        self.predict(dt);
+        self.update(measured_position);
+        State {
+            x: self.position(),
+            v: self.velocity(),
+        }
+    }
+}
+
+/// Kalman filter on the position of a target whose velocity it takes as known.
+///
+/// `p` is the variance of the position estimate, `r` the measurement variance and `q` the
+/// process noise added by each prediction.
+///
+/// ```
+/// use kalman_filter_simulator::filter::{Filter, KalmanFilter};
+///
+/// let mut filter = KalmanFilter::new(0.0, 1.0, 4.0, 0.01);
+/// filter.step(0.1, 0.3);
+/// assert!(filter.position() > 0.1 && filter.position() < 0.3);
+/// assert_eq!(filter.velocity(), 1.0);
+/// assert!(filter.uncertainty() < 1.0);
+/// assert!(filter.gain() > 0.0);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "KalmanFilterSnapshot", into = "KalmanFilterSnapshot")]
+pub struct KalmanFilter {
+    /// The position estimate, its variance and the noise, as a one-dimensional linear filter
+    /// whose transition and measurement are the identity
+    linear: linear::KalmanFilter<1, 1>,
+    /// The velocity the filter takes as known, which moves the position on every prediction
+    v: f64,
+}
+
+/// The serialized form of `KalmanFilter`, kept flat so snapshots stay readable and don't
+/// depend on how the filter computes.
+#[derive(Serialize, Deserialize)]
+struct KalmanFilterSnapshot {
+    state: State,
+    p: f64,
+    r: f64,
+    q: f64,
+    k: f64,
+    /// Innovation and innovation variance of the latest update
+    innovation: (f64, f64),
+}
+
+impl From<KalmanFilterSnapshot> for KalmanFilter {
+    fn from(snapshot: KalmanFilterSnapshot) -> Self {
+        let KalmanFilterSnapshot {
+            state,
+            p,
+            r,
+            q,
+            k,
+            innovation: (innovation, variance),
+        } = snapshot;
+        let mut filter = KalmanFilter::new(state.x, state.v, r, q);
+        filter.linear.p = Matrix1::new(p);
+        filter.linear.set_latest_update(
+            Matrix1::new(k),
+            Vector1::new(innovation),
+            Matrix1::new(variance),
+        );
+        filter
+    }
+}
+
+impl From<KalmanFilter> for KalmanFilterSnapshot {
+    fn from(filter: KalmanFilter) -> Self {
+        KalmanFilterSnapshot {
+            state: State {
+                x: filter.position(),
+                v: filter.v,
+            },
+            p: filter.uncertainty(),
+            r: filter.linear.r[0],
+            q: filter.process_noise(),
+            k: filter.gain(),
+            innovation: filter.innovation(),
+        }
+    }
+}
+
+impl KalmanFilter {
+    /// Starts from `initial_position` with variance 1, moving at `initial_velocity`.
+    pub fn new(initial_position: f64, initial_velocity: f64, r: f64, q: f64) -> Self {
+        KalmanFilter {
+            linear: linear::KalmanFilter::new(
+                Vector1::new(initial_position),
+                Matrix1::new(1.0),
+                Matrix1::identity(),
+                Matrix1::identity(),
+                Matrix1::new(q),
+                Matrix1::new(r),
+            ),
+            v: initial_velocity,
+        }
+    }
+
+    /// Builds the filter from the initial guess described by `config`.
+    pub fn from_config(config: &SimulateConfig) -> Self {
+        KalmanFilter::new(
+            config.initial_position + config.initial_position_offset,
+            config.velocity + config.initial_velocity_offset,
+            config.r,
+            config.q,
+        )
+    }
+
+    /// Snapshot of the whole internal state as pretty-printed JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Restores a filter from a snapshot written by `to_json`.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Filter for KalmanFilter {
+    fn predict(&mut self, dt: f64) {
+        self.linear.predict();
+        self.linear.x[0] += self.v * dt;
+    }
+
+    fn update(&mut self, measured_position: f64) {
+        self.update_with_variance(measured_position, self.linear.r[0]);
+    }
+
+    fn update_with_variance(&mut self, measured_position: f64, r: f64) {
+        self.linear
+            .update_with_covariance(&Vector1::new(measured_position), &Matrix1::new(r));
+    }
+
+    fn position(&self) -> f64 {
+        self.linear.x[0]
+    }
+
+    fn velocity(&self) -> f64 {
+        self.v
+    }
+
+    fn uncertainty(&self) -> f64 {
+        self.linear.p[0]
+    }
+
+    fn gain(&self) -> f64 {
+        self.linear.gain()[0]
+    }
+
+    fn innovation(&self) -> (f64, f64) {
+        (
+            self.linear.innovation()[0],
+            self.linear.innovation_covariance()[0],
+        )
+    }
+
+    fn process_noise(&self) -> f64 {
+        self.linear.q[0]
+    }
+
+    fn set_process_noise(&mut self, q: f64) {
+        self.linear.q = Matrix1::new(q);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessors_read_the_predicted_and_corrected_estimate() {
+        let mut filter = KalmanFilter::new(0.0, 1.0, 4.0, 0.01);
+        filter.predict(1.0);
+        assert_eq!(filter.position(), 1.0);
+        assert!((filter.uncertainty() - 1.01).abs() < 1e-12);
+
+        filter.update(2.0);
+        let k = 1.01 / 5.01;
+        assert!((filter.gain() - k).abs() < 1e-12);
+        assert!((filter.position() - (1.0 + k)).abs() < 1e-12);
+        assert!((filter.uncertainty() - 1.01 * (1.0 - k)).abs() < 1e-12);
+        assert_eq!(filter.velocity(), 1.0);
+        assert_eq!(filter.innovation().0, 1.0);
+    }
+
+    #[test]
+    fn json_snapshot_restores_a_filter_that_keeps_stepping_alike() {
+        let mut filter = KalmanFilter::new(0.0, 1.0, 4.0, 0.01);
+        for measurement in [0.3, -0.2, 0.5] {
+            filter.step(0.1, measurement);
+        }
+        let json = filter.to_json().unwrap();
+        let mut restored = KalmanFilter::from_json(&json).unwrap();
+        assert_eq!(restored.to_json().unwrap(), json);
+        assert_eq!(restored.gain(), filter.gain());
+        assert_eq!(restored.innovation(), filter.innovation());
+
+        for measurement in [0.9, 0.1, 1.4] {
+            let expected = filter.step(0.1, measurement);
+            let state = restored.step(0.1, measurement);
+            assert_eq!((state.x, state.v), (expected.x, expected.v));
+            assert_eq!(restored.uncertainty(), filter.uncertainty());
+        }
+    }
+
+    #[test]
+    fn json_snapshot_keeps_its_flat_fields() {
+        let mut filter = KalmanFilter::new(0.0, 1.0, 4.0, 0.01);
+        filter.step(0.1, 0.3);
+        let json: serde_json::Value = serde_json::from_str(&filter.to_json().unwrap()).unwrap();
+        let mut keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, ["innovation", "k", "p", "q", "r", "state"]);
+        assert_eq!(json["state"]["v"], 1.0);
+        assert_eq!(json["p"], filter.uncertainty());
+        assert_eq!(json["innovation"][1], filter.innovation().1);
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use kalman_filter_simulator::sim::SimulateResult;
+
+fn plot_script(x_column: &str) -> String {
+    format!(
+        r#"set title "Kalman Filter Simulator"
+set xlabel "{}"
+set ylabel "position"
+set key top left
+plot "true.dat" using 1:2 with lines lw 2 lc rgb "red" title "true", \
+     "estimated.dat" using 1:2 with lines lw 2 lc rgb "green" title "estimated", \
+     "measured.dat" using 1:2 with points pt 7 ps 0.5 lc rgb "blue" title "measured"
+pause mouse close
+"#,
+        x_column
+    )
+}
+
+fn write_series(
+    path: &Path,
+    name: &str,
+    x_column: &str,
+    points: impl Iterator<Item = (f64, f64)>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "# {} position: {} position", name, x_column)?;
+    for (time, position) in points {
+        writeln!(writer, "{} {}", time, position)?;
+    }
+    // gnuplot treats a blank line as the end of a dataset
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Writes `true.dat`, `measured.dat`, `estimated.dat` and a `plot.gp` script into `dir`.
+/// `x_column` names the first column, such as `time[s]`.
+pub fn write_gnuplot(result: &SimulateResult, dir: &Path, x_column: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    write_series(
+        &dir.join("true.dat"),
+        "true",
+        x_column,
+        result.ticks.iter().map(|tick| tick.true_positions),
+    )?;
+    write_series(
+        &dir.join("measured.dat"),
+        "measured",
+        x_column,
+        result
+            .ticks
+            .iter()
+            .filter_map(|tick| tick.measured_positions),
+    )?;
+    write_series(
+        &dir.join("estimated.dat"),
+        "estimated",
+        x_column,
+        result.ticks.iter().map(|tick| tick.estimated_positions),
+    )?;
+
+    fs::write(dir.join("plot.gp"), plot_script(x_column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{default_config, scratch_dir, seeded_run};
+
+    /// Data rows of a `.dat` file, split into columns, without the comment and blank lines.
+    fn rows(path: &Path) -> Vec<Vec<String>> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.split_whitespace().map(String::from).collect())
+            .collect()
+    }
+
+    #[test]
+    fn writes_one_row_per_tick_with_two_columns() {
+        let config = default_config();
+        let result = seeded_run(&config);
+        let dir = scratch_dir("gnuplot");
+        write_gnuplot(&result, &dir, "time[s]").unwrap();
+
+        let measured = result
+            .ticks
+            .iter()
+            .filter(|tick| tick.measured_positions.is_some())
+            .count();
+        for (name, expected) in [
+            ("true.dat", result.ticks.len()),
+            ("estimated.dat", result.ticks.len()),
+            ("measured.dat", measured),
+        ] {
+            let rows = rows(&dir.join(name));
+            assert_eq!(rows.len(), expected, "{}", name);
+            assert!(rows.iter().all(|row| row.len() == 2), "{}", name);
+        }
+        assert!(dir.join("plot.gp").exists());
+    }
+}
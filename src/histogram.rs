@@ -0,0 +1,121 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use kalman_filter_simulator::sim::SimulateResult;
+
+/// Counts of values falling into equally wide bins between their smallest and largest value.
+pub struct Histogram {
+    /// Lower edge of the first bin
+    pub lower: f64,
+    pub bin_width: f64,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Bins the finite `values` into `bins` bins spanning their range. The largest value falls
+    /// into the last bin.
+    pub fn new(values: &[f64], bins: usize) -> Self {
+        let finite = || values.iter().copied().filter(|value| value.is_finite());
+        let (min, max) = finite().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+        let mut counts = vec![0; bins];
+        if bins == 0 || min > max {
+            return Histogram {
+                lower: 0.0,
+                bin_width: 0.0,
+                counts,
+            };
+        }
+        // Give identical values a unit-wide bin instead of dividing by zero
+        let bin_width = if max > min {
+            (max - min) / bins as f64
+        } else {
+            1.0
+        };
+        for value in finite() {
+            let bin = ((value - min) / bin_width) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+        Histogram {
+            lower: min,
+            bin_width,
+            counts,
+        }
+    }
+
+    pub fn bin_centers(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.counts.len()).map(|bin| self.lower + (bin as f64 + 0.5) * self.bin_width)
+    }
+
+    /// Writes one `bin_center,count` row per bin.
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "bin_center,count")?;
+        for (center, count) in self.bin_centers().zip(&self.counts) {
+            writeln!(writer, "{},{}", center, count)?;
+        }
+        writer.flush()
+    }
+}
+
+/// Writes `innovation.csv`, the histogram of the normalized innovations `innovation / √variance`
+/// over every measurement, and `error.csv`, that of the estimate errors `estimated - true` over
+/// every tick, into `dir`.
+///
+/// A filter whose `r` and `q` match the real noise has standard normal normalized innovations.
+pub fn write_histograms(result: &SimulateResult, dir: &Path, bins: usize) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let innovations: Vec<_> = result
+        .ticks
+        .iter()
+        .filter_map(|tick| tick.innovation)
+        .map(|(innovation, variance)| innovation / variance.sqrt())
+        .collect();
+    Histogram::new(&innovations, bins).write_csv(&dir.join("innovation.csv"))?;
+
+    let errors: Vec<_> = result
+        .ticks
+        .iter()
+        .map(|tick| tick.estimated_positions.1 - tick.true_positions.1)
+        .collect();
+    Histogram::new(&errors, bins).write_csv(&dir.join("error.csv"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{default_config, scratch_dir, seeded_run};
+
+    /// Sum of the `count` column of the histogram CSV at `path`.
+    fn csv_total(path: &Path) -> usize {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|row| row.split_once(',').unwrap().1.parse::<usize>().unwrap())
+            .sum()
+    }
+
+    #[test]
+    fn counts_sum_to_the_number_of_samples() {
+        let values = [-3.0, -1.0, 0.0, 0.0, 2.5, 4.0, f64::NAN, 7.0];
+        let histogram = Histogram::new(&values, 4);
+        assert_eq!(histogram.counts.len(), 4);
+        assert_eq!(histogram.counts.iter().sum::<usize>(), 7);
+        assert_eq!(histogram.counts[3], 1);
+
+        let result = seeded_run(&default_config());
+        let dir = scratch_dir("histograms");
+        write_histograms(&result, &dir, 20).unwrap();
+        let measurements = result
+            .ticks
+            .iter()
+            .filter(|tick| tick.innovation.is_some())
+            .count();
+        assert_eq!(csv_total(&dir.join("innovation.csv")), measurements);
+        assert_eq!(csv_total(&dir.join("error.csv")), result.ticks.len());
+    }
+}
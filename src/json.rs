@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use kalman_filter_simulator::sim::{SimulateConfig, SimulateResult};
+
+/// Every tick of a run next to the parameters that produced it, for dashboards and notebooks.
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    config: &'a SimulateConfig,
+    /// Seed every random draw was derived from
+    seed: u64,
+    /// Unit of the times in `config` and the ticks
+    time_unit: &'a str,
+    #[serde(flatten)]
+    result: &'a SimulateResult,
+}
+
+/// Writes `result`, simulated from `config`, as pretty-printed JSON with its times in
+/// `time_unit`.
+pub fn write_json(
+    path: &Path,
+    config: &SimulateConfig,
+    seed: u64,
+    time_unit: &str,
+    result: &SimulateResult,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let export = JsonExport {
+        config,
+        seed,
+        time_unit,
+        result,
+    };
+    serde_json::to_writer_pretty(&mut writer, &export)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir, seeded_run};
+
+    #[test]
+    fn export_records_the_seed_and_time_unit() {
+        let config = default_config();
+        let result = seeded_run(&config);
+        let path = scratch_dir("json").join("run.json");
+        write_json(&path, &config, 7, "ms", &result).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["seed"], 7);
+        assert_eq!(json["time_unit"], "ms");
+        assert_eq!(json["ticks"].as_array().unwrap().len(), result.ticks.len());
+    }
+}
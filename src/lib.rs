@@ -0,0 +1,25 @@
+//! Simulates a Kalman filter tracking a target moving along a line, and renders the run as
+//! SVG plots or animation frames.
+//!
+//! [`sim::simulate`] runs any [`filter::Filter`] over noisy measurements of a simulated
+//! trajectory and records every tick in a [`sim::SimulateResult`]; [`render`] turns the
+//! result into plots.
+
+pub mod adaptive;
+pub mod dynamics;
+pub mod ekf;
+pub mod filter;
+pub mod linear;
+pub mod metrics;
+pub mod noise;
+pub mod online;
+pub mod particle;
+mod raster;
+pub mod render;
+mod reorder;
+pub mod sensor;
+pub mod sim;
+pub mod smoother;
+pub mod trajectory;
+pub mod ukf;
+pub mod variants;
@@ -0,0 +1,162 @@
+use nalgebra::{SMatrix, SVector};
+
+/// Linear Kalman filter over an `N`-dimensional state observed through `M`-dimensional
+/// measurements.
+///
+/// The model is `x ← F x + w` with `w ~ N(0, Q)` between measurements, and `z = H x + v` with
+/// `v ~ N(0, R)` for each measurement. Every matrix is a public field, so a filter whose
+/// model changes over time, like one predicting over irregular steps, can replace `f` and `q`
+/// before each `predict`.
+#[derive(Debug, Clone)]
+pub struct KalmanFilter<const N: usize, const M: usize> {
+    /// State estimate
+    pub x: SVector<f64, N>,
+    /// Covariance of the state estimate
+    pub p: SMatrix<f64, N, N>,
+    /// State transition
+    pub f: SMatrix<f64, N, N>,
+    /// Measurement matrix, mapping the state onto what the sensor reports
+    pub h: SMatrix<f64, M, N>,
+    /// Process noise covariance added by each prediction
+    pub q: SMatrix<f64, N, N>,
+    /// Measurement noise covariance
+    pub r: SMatrix<f64, M, M>,
+    k: SMatrix<f64, N, M>,
+    innovation: SVector<f64, M>,
+    innovation_covariance: SMatrix<f64, M, M>,
+}
+
+impl<const N: usize, const M: usize> KalmanFilter<N, M> {
+    /// Starts from the estimate `x` with covariance `p`, before any update.
+    pub fn new(
+        x: SVector<f64, N>,
+        p: SMatrix<f64, N, N>,
+        f: SMatrix<f64, N, N>,
+        h: SMatrix<f64, M, N>,
+        q: SMatrix<f64, N, N>,
+        r: SMatrix<f64, M, M>,
+    ) -> Self {
+        KalmanFilter {
+            x,
+            p,
+            f,
+            h,
+            q,
+            r,
+            k: SMatrix::zeros(),
+            innovation: SVector::zeros(),
+            innovation_covariance: SMatrix::zeros(),
+        }
+    }
+
+    /// Propagates the estimate one step: `x ← F x`, `P ← F P Fᵀ + Q`.
+    pub fn predict(&mut self) {
+        self.x = self.f * self.x;
+        self.p = self.f * self.p * self.f.transpose() + self.q;
+    }
+
+    /// Corrects the estimate with the measurement `z`.
+    pub fn update(&mut self, z: &SVector<f64, M>) {
+        let r = self.r;
+        self.update_with_covariance(z, &r);
+    }
+
+    /// Like `update`, but assumes measurement noise covariance `r` for this measurement only.
+    ///
+    /// When the innovation covariance `H P Hᵀ + R` is not positive definite there is no
+    /// meaningful gain, and the estimate becomes NaN rather than silently skipping the
+    /// measurement.
+    pub fn update_with_covariance(&mut self, z: &SVector<f64, M>, r: &SMatrix<f64, M, M>) {
+        let innovation = z - self.h * self.x;
+        let s = self.h * self.p * self.h.transpose() + r;
+        // K = P Hᵀ S⁻¹, solved as Kᵀ = S⁻¹ H P since S and P are symmetric
+        let hp = self.h * self.p;
+        self.k = match s.cholesky() {
+            Some(cholesky) => cholesky.solve(&hp).transpose(),
+            None => SMatrix::repeat(f64::NAN),
+        };
+        self.x += self.k * innovation;
+        self.p = (SMatrix::<f64, N, N>::identity() - self.k * self.h) * self.p;
+        self.innovation = innovation;
+        self.innovation_covariance = s;
+    }
+
+    /// Gain `K` applied by the latest update.
+    pub fn gain(&self) -> &SMatrix<f64, N, M> {
+        &self.k
+    }
+
+    /// Innovation `z - H x` of the latest update.
+    pub fn innovation(&self) -> &SVector<f64, M> {
+        &self.innovation
+    }
+
+    /// Predicted covariance `H P Hᵀ + R` of the latest innovation.
+    pub fn innovation_covariance(&self) -> &SMatrix<f64, M, M> {
+        &self.innovation_covariance
+    }
+
+    /// Sets what `gain`, `innovation` and `innovation_covariance` report, as if the latest
+    /// update had computed them, for restoring a filter from a snapshot.
+    pub(crate) fn set_latest_update(
+        &mut self,
+        k: SMatrix<f64, N, M>,
+        innovation: SVector<f64, M>,
+        innovation_covariance: SMatrix<f64, M, M>,
+    ) {
+        self.k = k;
+        self.innovation = innovation;
+        self.innovation_covariance = innovation_covariance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix1, Matrix2, Vector1, Vector2};
+
+    use super::*;
+
+    #[test]
+    fn one_dimensional_filter_follows_the_scalar_recursion() {
+        let (r, q) = (4.0, 0.01);
+        let mut filter = KalmanFilter::new(
+            Vector1::new(0.0),
+            Matrix1::new(1.0),
+            Matrix1::identity(),
+            Matrix1::identity(),
+            Matrix1::new(q),
+            Matrix1::new(r),
+        );
+        let (mut x, mut p) = (0.0, 1.0);
+        for z in [0.4, -0.3, 1.2, 0.8, 0.1] {
+            filter.predict();
+            filter.update(&Vector1::new(z));
+
+            let predicted = p + q;
+            let k = predicted / (predicted + r);
+            x += k * (z - x);
+            p = (1.0 - k) * predicted;
+
+            assert!((filter.gain()[0] - k).abs() < 1e-15);
+            assert!((filter.x[0] - x).abs() < 1e-15);
+            assert!((filter.p[0] - p).abs() < 1e-15);
+            assert_eq!(filter.innovation_covariance()[0], predicted + r);
+        }
+    }
+
+    #[test]
+    fn non_positive_definite_innovation_covariance_gives_a_nan_gain() {
+        let mut filter = KalmanFilter::new(
+            Vector2::new(1.0, 0.0),
+            Matrix2::identity(),
+            Matrix2::identity(),
+            Matrix2::identity(),
+            Matrix2::zeros(),
+            Matrix2::from_diagonal(&Vector2::new(1.0, -3.0)),
+        );
+        filter.update(&Vector2::new(2.0, 0.0));
+        assert!(filter.gain().iter().all(|k| k.is_nan()));
+        assert!(filter.x.iter().all(|x| x.is_nan()));
+        assert_eq!(filter.innovation(), &Vector2::new(1.0, 0.0));
+    }
+}
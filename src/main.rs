@@ -1,227 +1,2024 @@
 extern crate rand;
 extern crate svg;
 
-use std::fs::File;
-use std::io::BufWriter;
-
-use image::{Frame, RgbaImage};
-use rand::Rng;
-use resvg::tiny_skia::Pixmap;
-use resvg::usvg;
-use svg::node::element::{Circle, Line};
-use svg::Document;
-
-#[derive(Debug, Clone, Copy)]
-struct State {
-    x: f64,
-    v: f64,
-}
-
-struct KalmanFilter {
-    state: State,
-    p: f64,
-    r: f64,
-    q: f64,
-    k: f64,
-}
-
-impl KalmanFilter {
-    fn new(initial_position: f64, initial_velocity: f64, r: f64, q: f64) -> Self {
-        KalmanFilter {
-            state: State {
-                x: initial_position,
-                v: initial_velocity,
+mod checkpoint;
+mod config;
+mod csv;
+mod gnuplot;
+mod histogram;
+mod json;
+mod manifest;
+mod montecarlo;
+mod output;
+mod profile;
+mod quantize;
+mod stream;
+mod study;
+mod summary;
+mod sweep;
+#[cfg(test)]
+mod testing;
+mod tradeoff;
+mod tui;
+mod tune;
+mod video;
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::num::{NonZeroU16, NonZeroU64};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use clap::{Parser, ValueEnum};
+use config::{RenderSettings, Scenario};
+use image::ImageFormat;
+use kalman_filter_simulator::adaptive::AdaptiveQ;
+use kalman_filter_simulator::dynamics::FilterModel;
+use kalman_filter_simulator::filter::KalmanFilter;
+use kalman_filter_simulator::metrics;
+use kalman_filter_simulator::noise::SensorNoise;
+use kalman_filter_simulator::particle::{self, Resampling};
+use kalman_filter_simulator::render::{
+    animate, composite_frames, frame_size, frame_ticks, rasterize, render_final, AnimateOptions,
+    Easing, LineCap, LineJoin, MarkerShape, NoiseBands, Overlay, PlotKind, RenderOptions, Theme,
+    XAxis, AXES_MARGIN,
+};
+use kalman_filter_simulator::sensor::SensorModel;
+use kalman_filter_simulator::sim::{
+    filter_recorded, filter_recorded_model, refilter, simulate, simulate_checkpointed,
+    simulate_model, Checkpoint, SimulateConfig, SimulateResult,
+};
+use kalman_filter_simulator::smoother;
+use kalman_filter_simulator::trajectory::{Trajectory, VelocitySchedule, Waypoints};
+use kalman_filter_simulator::variants::{self, VariantRun};
+use output::OutputFormat;
+use profile::PhaseTimings;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::ThreadPoolBuilder;
+use study::StudyParameter;
+use summary::{ChiSquare, Summary};
+use tune::TuneObjective;
+
+/// Side length in pixels of each pane of the plots.
+const SIZE: usize = 500;
+
+/// Parses a `START:END` range whose start comes before its end.
+fn parse_range(value: &str) -> Result<(f64, f64), String> {
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got {}", value))?;
+    let start: f64 = start.parse().map_err(|err| format!("{}", err))?;
+    let end: f64 = end.parse().map_err(|err| format!("{}", err))?;
+    if start < end {
+        Ok((start, end))
+    } else {
+        Err(format!("the start must come before the end, got {}", value))
+    }
+}
+
+/// Parses a probability strictly between 0 and 1.
+fn parse_probability(value: &str) -> Result<f64, String> {
+    let p: f64 = value.parse().map_err(|err| format!("{}", err))?;
+    if p > 0.0 && p < 1.0 {
+        Ok(p)
+    } else {
+        Err(format!("must be between 0 and 1 exclusive, got {}", p))
+    }
+}
+
+/// Parses a finite number above 0.
+fn parse_positive(value: &str) -> Result<f64, String> {
+    let x: f64 = value.parse().map_err(|err| format!("{}", err))?;
+    if x.is_finite() && x > 0.0 {
+        Ok(x)
+    } else {
+        Err(format!("must be a positive number, got {}", x))
+    }
+}
+
+/// Parses a margin that leaves some of a pane for the data.
+fn parse_margin(value: &str) -> Result<f64, String> {
+    let margin: f64 = value.parse().map_err(|err| format!("{}", err))?;
+    let render = RenderSettings {
+        margin,
+        ..RenderSettings::default()
+    };
+    render.validate(SIZE).map_err(|err| err.to_string())?;
+    Ok(margin)
+}
+
+/// Like `eprintln!`, but silenced by `--quiet`.
+macro_rules! status {
+    ($quiet:expr, $($arg:tt)*) => {
+        if !$quiet {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SensorPreset {
+    /// Noise of standard deviation `sensor_noise_stddev`
+    Additive,
+    /// Noise proportional to the true position, see `--relative-stddev`
+    Multiplicative,
+    /// Distance to a station beside the track instead of the position, see `--station` and
+    /// `--station-offset`. Needs `--filter-model extended`, `unscented` or
+    /// `particle`.
+    Range,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum NoisePreset {
+    /// Normal noise
+    Gaussian,
+    /// Flat noise with no tails
+    Uniform,
+    /// Double-exponential noise, with heavier tails than normal noise
+    Laplace,
+    /// Student's t noise with `--degrees-of-freedom`, for heavy-tailed outliers
+    StudentT,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TrajectoryPreset {
+    /// Constant velocity for the whole run
+    ConstantVelocity,
+    /// Constant velocity until `--stop-time`, then hold position
+    RampHold,
+    /// Constant velocity with a burst of `--maneuver-acceleration` over the middle fifth of
+    /// the run
+    Maneuver,
+    /// Constant velocity until the first segment of `--velocity-schedule`, then the velocity
+    /// of each segment in turn
+    Piecewise,
+    /// Constant velocity plus `--acceleration` times the elapsed time
+    ConstantAcceleration,
+    /// Constant velocity plus a swing of `--amplitude` every `--period`
+    Sinusoidal,
+    /// Straight lines through each of `--waypoints` in turn, then hold position
+    Waypoints,
+    /// Constant velocity plus a random walk of white-noise acceleration of density
+    /// `--walk-density`
+    RandomWalk,
+}
+
+#[derive(Parser)]
+#[command(about = "Simulates a Kalman filter and renders the result as an animated GIF")]
+struct Args {
+    /// Write the animation to this file, in `--format` [default: output.gif, or the
+    /// extension of `--format`, unless only `--png` is given]. Batch runs name the file after
+    /// each config instead.
+    #[arg(long, value_name = "PATH", visible_alias = "output")]
+    gif: Option<PathBuf>,
+
+    /// Format of the animation. APNG and WebP keep the full colors and alpha of the frames;
+    /// MP4 and WebM are far smaller than GIF for long runs, and need ffmpeg on the PATH.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Gif)]
+    format: OutputFormat,
+
+    /// Write the final frame to this PNG file. Batch runs name the file after each config
+    /// instead.
+    #[arg(long, value_name = "PNG")]
+    png: Option<PathBuf>,
+
+    /// Render only the final chart of the whole run, straight to this SVG or PNG file by its
+    /// extension, without animating or encoding anything. Batch runs name the file after each
+    /// config instead.
+    #[arg(long = "static", value_name = "PATH", conflicts_with_all = ["gif", "png", "format", "compare_noise"])]
+    static_plot: Option<PathBuf>,
+
+    /// Also write the per-tick results, including covariance and gain, to this CSV file.
+    /// Batch runs name the file after each config instead.
+    #[arg(long, value_name = "CSV", visible_alias = "export-csv")]
+    csv: Option<PathBuf>,
+
+    /// Also write every tick, with the configuration and seed of the run, to this JSON file.
+    /// Batch runs name the file after each config instead.
+    #[arg(long, value_name = "JSON", visible_alias = "export-json")]
+    json: Option<PathBuf>,
+
+    /// Export only every Nth tick to the CSV, JSON and gnuplot files. The filter still runs
+    /// every tick.
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    every: u64,
+
+    /// Also write gnuplot data files and a `plot.gp` script into this directory
+    #[arg(long, value_name = "DIR")]
+    gnuplot: Option<PathBuf>,
+
+    /// Also write histograms of the normalized innovations and of the estimate errors, as
+    /// `innovation.csv` and `error.csv`, into this directory
+    #[arg(long, value_name = "DIR")]
+    histograms: Option<PathBuf>,
+
+    /// Number of bins of each `--histograms` histogram
+    #[arg(long, value_name = "N", default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    histogram_bins: u64,
+
+    /// Also write a JSON manifest of the configuration and every written file to this path
+    #[arg(long, value_name = "PATH")]
+    manifest: Option<PathBuf>,
+
+    /// Also write how long simulating, exporting, rendering and encoding took, with the
+    /// configuration, to this JSON file
+    #[arg(long, value_name = "PATH")]
+    profile: Option<PathBuf>,
+
+    /// Write the known-velocity filter's final internal state to this JSON file. Batch runs
+    /// name the file after each config instead.
+    #[arg(long, value_name = "PATH")]
+    filter_snapshot: Option<PathBuf>,
+
+    /// Start the known-velocity filter from a state written by `--filter-snapshot` instead of
+    /// the scenario's initial guess
+    #[arg(long, value_name = "PATH")]
+    initial_filter: Option<PathBuf>,
+
+    /// Save the progress of the known-velocity filter through the run to this JSON Lines file
+    /// every `--checkpoint-every` ticks, so `--resume` can carry on after a crash
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["measurements", "initial_filter"])]
+    checkpoint: Option<PathBuf>,
+
+    /// Ticks between the saves of `--checkpoint`
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 1000)]
+    checkpoint_every: u64,
+
+    /// Carry on from a file written by `--checkpoint` instead of starting the run over. The
+    /// other parameters must be the ones the checkpointed run had.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["measurements", "initial_filter"])]
+    resume: Option<PathBuf>,
+
+    /// Filter the logged `time,measurement[,true_position]` rows of this CSV file instead of
+    /// simulated measurements. The scenario still sets the sensor and filter parameters.
+    #[arg(long, value_name = "CSV", conflicts_with_all = ["monte_carlo", "convergence_study", "tradeoff", "sweep", "compare_noise"])]
+    measurements: Option<PathBuf>,
+
+    /// Scenario file to load parameters from. Pass several files, or a directory of `.toml`
+    /// files, to run each and name the outputs after them.
+    #[arg(long, value_name = "PATH")]
+    config: Vec<PathBuf>,
+
+    /// Length of the simulated run [default: 10]
+    #[arg(long)]
+    total_time: Option<f64>,
+
+    /// Time between ticks [default: 0.1]
+    #[arg(long)]
+    dt: Option<f64>,
+
+    /// Nominal speed of the true motion [default: 1]
+    #[arg(long, allow_hyphen_values = true)]
+    velocity: Option<f64>,
+
+    /// Standard deviation of the sensor noise [default: 2]
+    #[arg(long)]
+    sensor_noise_stddev: Option<f64>,
+
+    /// Measurement variance the filter assumes [default: sensor-noise-stddev²]
+    #[arg(long)]
+    r: Option<f64>,
+
+    /// Process noise the filter adds every prediction [default: 0.01]
+    #[arg(long)]
+    q: Option<f64>,
+
+    /// Position the true trajectory starts from [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    initial_position: Option<f64>,
+
+    /// Shape of the true motion [default: constant-velocity]
+    #[arg(long, value_enum)]
+    trajectory: Option<TrajectoryPreset>,
+
+    /// Time at which the `ramp-hold` trajectory stops (defaults to half the run)
+    #[arg(long)]
+    stop_time: Option<f64>,
+
+    /// Acceleration of the `maneuver` trajectory's burst
+    #[arg(long, default_value_t = 1.0, allow_hyphen_values = true)]
+    maneuver_acceleration: f64,
+
+    /// Segments of the `piecewise` trajectory as `START_TIME:VELOCITY` pairs, such as
+    /// `3:2.5,7:0` (defaults to 2.5 from 30% of the run and 0 from 70%)
+    #[arg(long, value_name = "SCHEDULE", allow_hyphen_values = true)]
+    velocity_schedule: Option<VelocitySchedule>,
+
+    /// Acceleration of the `constant-acceleration` trajectory
+    #[arg(long, default_value_t = 0.2, allow_hyphen_values = true)]
+    acceleration: f64,
+
+    /// Amplitude of the `sinusoidal` trajectory's swing
+    #[arg(long, default_value_t = 2.0, allow_hyphen_values = true)]
+    amplitude: f64,
+
+    /// Period of the `sinusoidal` trajectory's swing (defaults to a quarter of the run)
+    #[arg(long, value_parser = parse_positive)]
+    period: Option<f64>,
+
+    /// Waypoints of the `waypoints` trajectory as `TIME:POSITION` pairs, such as `4:10,8:-5`
+    /// (defaults to 5 at 30% of the run, holding until 60%, and 12 at the end)
+    #[arg(long, value_name = "PATH", allow_hyphen_values = true)]
+    waypoints: Option<Waypoints>,
+
+    /// Spectral density of the `random-walk` trajectory's white-noise acceleration
+    #[arg(long, default_value_t = 0.1)]
+    walk_density: f64,
+
+    /// State the filter estimates [default: known-velocity]
+    #[arg(long, value_enum)]
+    filter_model: Option<FilterModel>,
+
+    /// Spread of the `unscented` model's sigma points around the estimate [default: 0.001]
+    #[arg(long)]
+    ukf_alpha: Option<f64>,
+
+    /// Weight of the `unscented` model's central sigma point in the covariance, 2 for
+    /// Gaussian noise [default: 2]
+    #[arg(long, allow_hyphen_values = true)]
+    ukf_beta: Option<f64>,
+
+    /// Secondary spread parameter of the `unscented` model's sigma points [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    ukf_kappa: Option<f64>,
+
+    /// Number of particles of the `particle` model [default: 1000]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    particles: Option<u64>,
+
+    /// How the `particle` model resamples its particles [default: systematic]
+    #[arg(long, value_enum)]
+    resampling: Option<Resampling>,
+
+    /// Draw the particles of a particle filter at the latest tick of each frame. Unless
+    /// `--filter-model particle` is given, the particle filter runs over the same
+    /// measurements alongside the selected model.
+    #[arg(long)]
+    show_particles: bool,
+
+    /// Also run every filter model over the same measurements, print the RMSE of each and
+    /// overlay the tracks of the models other than `--filter-model`
+    #[arg(long)]
+    compare_models: bool,
+
+    /// Also run the standard, Joseph, square-root, information and alpha-beta forms of the
+    /// known-velocity filter over the same measurements, print their RMSEs from best to worst
+    /// and overlay their tracks
+    #[arg(long)]
+    compare_filters: bool,
+
+    /// Raise `q` while the innovations exceed what the filter expects and let it decay back
+    /// afterwards
+    #[arg(long)]
+    adaptive_q: bool,
+
+    /// Normalized innovation squared above which `--adaptive-q` raises `q`
+    #[arg(long, default_value_t = 4.0)]
+    adaptive_q_sensitivity: f64,
+
+    /// Fraction of the raised `q` above its configured value that `--adaptive-q` keeps after
+    /// each unsurprising measurement
+    #[arg(long, default_value_t = 0.8, value_parser = parse_probability)]
+    adaptive_q_decay: f64,
+
+    /// How the sensor corrupts the true position [default: additive]
+    #[arg(long, value_enum)]
+    sensor: Option<SensorPreset>,
+
+    /// Shape of the sensor noise, always scaled to its standard deviation [default: gaussian]
+    #[arg(long, value_enum)]
+    noise: Option<NoisePreset>,
+
+    /// Degrees of freedom of the `student-t` noise, above 2
+    #[arg(long, default_value_t = 4.0)]
+    degrees_of_freedom: f64,
+
+    /// Standard deviation of the `multiplicative` sensor noise relative to the position
+    #[arg(long, default_value_t = 0.1)]
+    relative_stddev: f64,
+
+    /// Position along the track of the `range` sensor's station
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    station: f64,
+
+    /// Distance of the `range` sensor's station from the track
+    #[arg(long, default_value_t = 5.0)]
+    station_offset: f64,
+
+    /// Offset applied to the filter's initial position guess [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    initial_position_offset: Option<f64>,
+
+    /// Offset applied to the filter's initial velocity guess [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    initial_velocity_offset: Option<f64>,
+
+    /// Number of ticks between sensor measurements [default: 1]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    measurement_interval: Option<u64>,
+
+    /// Largest offset of each tick's timestamp from its nominal time, drawn uniformly. Must be
+    /// below `dt / 2` [default: 0]
+    #[arg(long)]
+    timestamp_jitter: Option<f64>,
+
+    /// Longest delay, drawn uniformly, before each measurement reaches the filter, which lets
+    /// measurements overtake each other [default: 0]
+    #[arg(long)]
+    arrival_delay: Option<f64>,
+
+    /// How long measurements are held to be put back in timestamp order before the filter
+    /// sees them. Measurements delayed by more are dropped [default: 0]
+    #[arg(long)]
+    reorder_window: Option<f64>,
+
+    /// Run the measurement-rate vs noise tradeoff experiment and write `rate,r,rmse` rows to this CSV
+    #[arg(long, value_name = "CSV")]
+    tradeoff: Option<PathBuf>,
+
+    /// Measurement rates (per unit time) compared by `--tradeoff`
+    #[arg(long, value_delimiter = ',', value_parser = parse_positive, default_values_t = [10.0, 5.0, 2.0, 1.0])]
+    tradeoff_rates: Vec<f64>,
+
+    /// Simulate the scenario with the filter's `q` and `r` set to every pair of `--sweep-q` and
+    /// `--sweep-r`, and write the estimate RMSE of each as a heatmap to this SVG or PNG file,
+    /// or as `q,r,rmse` rows to this CSV file, by its extension
+    #[arg(long, value_name = "PATH")]
+    sweep: Option<PathBuf>,
+
+    /// Process noise values `--sweep` tries, the columns of the heatmap
+    #[arg(long, value_delimiter = ',', value_parser = parse_positive, default_values_t = [0.0001, 0.001, 0.01, 0.1, 1.0])]
+    sweep_q: Vec<f64>,
+
+    /// Measurement variances `--sweep` tries, the rows of the heatmap
+    #[arg(long, value_delimiter = ',', value_parser = parse_positive, default_values_t = [0.25, 1.0, 4.0, 16.0, 64.0])]
+    sweep_r: Vec<f64>,
+
+    /// Search for the filter's `q` and `r` that optimize `--tune-objective` on the scenario,
+    /// or on `--measurements`, and print them
+    #[arg(long)]
+    auto_tune: bool,
+
+    /// What `--auto-tune` optimizes
+    #[arg(long, value_enum, default_value_t = TuneObjective::Rmse)]
+    tune_objective: TuneObjective,
+
+    /// Sweep `--study-parameter` over `--study-values` and write how long the gain takes to
+    /// converge for each value as `<parameter>,convergence_time` rows to this CSV
+    #[arg(long, value_name = "CSV")]
+    convergence_study: Option<PathBuf>,
+
+    /// Parameter `--convergence-study` varies
+    #[arg(long, value_enum, default_value_t = StudyParameter::Q)]
+    study_parameter: StudyParameter,
+
+    /// Values `--convergence-study` sets the parameter to
+    #[arg(long, value_delimiter = ',', default_values_t = [0.001, 0.01, 0.1, 1.0])]
+    study_values: Vec<f64>,
+
+    /// Simulate the scenario this many times without rendering and print the mean and
+    /// standard deviation of the RMSEs over the runs
+    #[arg(long, value_name = "RUNS", value_parser = clap::value_parser!(u64).range(1..))]
+    monte_carlo: Option<u64>,
+
+    /// Write the statistics of `--monte-carlo`, and the RMSEs of every run with its seed, to
+    /// this JSON file
+    #[arg(long, value_name = "PATH", requires = "monte_carlo")]
+    monte_carlo_json: Option<PathBuf>,
+
+    /// Seed of every random draw, so that a run with the same seed and parameters repeats
+    /// exactly. `--monte-carlo` derives every run's seed from it [default: drawn from the
+    /// system's entropy, 0 with `--monte-carlo`]
+    #[arg(long, visible_alias = "seed-from")]
+    seed: Option<u64>,
+
+    /// Number of threads `--monte-carlo` runs and the frames are rasterized on, 0 for one per
+    /// core
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    threads: usize,
+
+    /// Unit of `dt` and `total_time`, used in axis labels and export headers
+    #[arg(long, default_value = "s")]
+    time_unit: String,
+
+    /// Overlay a scale bar showing how many time and position units span a given length
+    #[arg(long)]
+    scale_bar: bool,
+
+    /// Replay the run as a live chart in the terminal instead of writing any files
+    #[arg(long)]
+    tui: bool,
+
+    /// Filter `time,measurement` lines from stdin as they arrive and write each estimate to
+    /// stdout as a `time,estimated_position,estimated_velocity,covariance,gain` line, instead
+    /// of simulating anything
+    #[arg(long, conflicts_with_all = ["measurements", "tui"])]
+    stream: bool,
+
+    /// Print a summary of the run's metrics at the end
+    #[arg(long)]
+    summary: bool,
+
+    /// Also report the estimate RMSE without the first T time units of the run, once the
+    /// filter has converged from its initial guess
+    #[arg(long, value_name = "T", default_value_t = 0.0)]
+    warmup: f64,
+
+    /// Confidence level of the chi-square NEES and NIS consistency tests of the summary and
+    /// `--monte-carlo`
+    #[arg(long, value_name = "P", default_value_t = 0.95, value_parser = parse_probability, visible_alias = "nis-confidence")]
+    consistency_confidence: f64,
+
+    /// Validate the parameters and print the derived quantities without simulating or rendering
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print the available trajectory, sensor, noise and filter-model presets and exit
+    #[arg(long)]
+    list_presets: bool,
+
+    /// Print the scenario-file keys the preset `NAME` expands to with the other parameters, and
+    /// exit
+    #[arg(long, value_name = "NAME")]
+    describe_preset: Option<String>,
+
+    /// Only print errors
+    #[arg(long)]
+    quiet: bool,
+
+    /// Use a logarithmic position axis (and error axis with `--dual-pane`)
+    #[arg(long)]
+    log_y: bool,
+
+    /// Don't show the current simulation time in each frame
+    #[arg(long)]
+    no_time_label: bool,
+
+    /// Don't mark the times at which the true motion changes regime
+    #[arg(long)]
+    no_regime_markers: bool,
+
+    /// Only make every Nth tick a frame, always keeping the last one, to animate a finely
+    /// stepped run at a coarser pace [default: 1]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    frame_stride: Option<u64>,
+
+    /// Render at most this many frames, spread evenly over the run [default: one per tick]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_frames: Option<u64>,
+
+    /// Frames per second of the animation, which sets the GIF frame delay in whole
+    /// hundredths of a second [default: 10]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    fps: Option<u16>,
+
+    /// Only plot the ticks from START to END, in x-axis units, with the axes fitted to them.
+    /// The exports and metrics still cover the whole run.
+    #[arg(long, value_name = "START:END", value_parser = parse_range, allow_hyphen_values = true)]
+    range: Option<(f64, f64)>,
+
+    /// How the reveal of the run is paced over the frames [default: linear]
+    #[arg(long, value_enum)]
+    easing: Option<Easing>,
+
+    /// Also join the measurements into an "unfiltered" line and print its RMSE next to the
+    /// filter's
+    #[arg(long)]
+    passthrough: bool,
+
+    /// Draw an error bar of ±σ, the measurement noise the filter assumes, on each
+    /// measurement
+    #[arg(long)]
+    error_bars: bool,
+
+    /// Shade bands of ±√p around the estimate, ±√q (the uncertainty one prediction adds)
+    /// around the estimate and ±√r around the measurements, to compare the noise levels
+    #[arg(long)]
+    noise_bands: bool,
+
+    /// Shade a band of ±2σ around the estimate, σ = √p being the filter's own standard
+    /// deviation, so the covariance visibly shrinks as the filter converges
+    #[arg(long)]
+    covariance_band: bool,
+
+    /// Standard deviations on either side of the estimate the covariance band spans
+    /// [default: 2]
+    #[arg(long, value_name = "K", requires = "covariance_band", value_parser = parse_positive)]
+    band_sigmas: Option<f64>,
+
+    /// Shape of the measurement markers [default: circle]
+    #[arg(long, value_enum)]
+    marker: Option<MarkerShape>,
+
+    /// Pixels kept free of data around the plot, for labels and legends [default: 0]
+    #[arg(long, value_parser = parse_margin)]
+    margin: Option<f64>,
+
+    /// Number of gridlines across each axis of the plot region [default: 0]
+    #[arg(long)]
+    grid_lines: Option<usize>,
+
+    /// Draw axes with numeric ticks and gridlines at round values, and a framed legend naming
+    /// every series. The margin grows to at least 40 pixels to fit the tick labels
+    #[arg(long)]
+    axes: bool,
+
+    /// Shape where the segments of a plotted line meet [default: round]
+    #[arg(long, value_enum)]
+    line_join: Option<LineJoin>,
+
+    /// Shape of the ends of a plotted line [default: round]
+    #[arg(long, value_enum)]
+    line_cap: Option<LineCap>,
+
+    /// Quantity on the x axis of the plots and exports
+    #[arg(long, value_enum, default_value_t = XAxis::Time)]
+    x_axis: XAxis,
+
+    /// Quantize each GIF frame to at most this many colors for a smaller file [default: 256]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(8..=256))]
+    gif_colors: Option<u16>,
+
+    /// Color scheme of the plot [default: okabe-ito]
+    #[arg(long, value_enum)]
+    theme: Option<Theme>,
+
+    /// Render a second run with this sensor noise standard deviation next to the first, on
+    /// the same axes
+    #[arg(long, value_name = "STDDEV")]
+    compare_noise: Option<f64>,
+
+    /// Overlay the Rauch-Tung-Striebel smoothed track on the filtered one and print both RMSEs
+    #[arg(long)]
+    compare_smoother: bool,
+
+    /// What the main pane plots [default: position]
+    #[arg(long, value_enum)]
+    plot: Option<PlotKind>,
+
+    /// With `--plot gain`, draw the analytic steady-state gain as a labeled dashed line
+    #[arg(long)]
+    steady_state_gain: bool,
+
+    /// With `--dual-pane`, also plot the measurement error `|measured - true|` in the error
+    /// pane
+    #[arg(long)]
+    measurement_error: bool,
+
+    /// Render the estimate error over time in a second pane next to the trajectory
+    #[arg(long)]
+    dual_pane: bool,
+
+    /// Add a strip below the chart plotting the gain `k` and the covariance `p` over time,
+    /// each on its own scale, to watch both settle
+    #[arg(long)]
+    gain_panel: bool,
+
+    /// Add a strip below the chart plotting the innovations, measurement minus prediction,
+    /// inside their ±2√S band, and a correlogram of their autocorrelation, to judge whether
+    /// `q` and `r` are tuned
+    #[arg(long)]
+    innovation_panel: bool,
+
+    /// Draw the frames straight onto the pixels instead of rendering SVG, which is faster but
+    /// leaves out all text: axis labels, legend, time label and scale bar
+    #[arg(long, conflicts_with_all = ["dual_pane", "gain_panel", "innovation_panel"])]
+    fast_render: bool,
+}
+
+impl Args {
+    /// Applies the scenario flags given on the command line on top of `scenario`.
+    fn override_scenario(&self, scenario: &mut Scenario) {
+        if let Some(total_time) = self.total_time {
+            scenario.total_time = total_time;
+        }
+        if let Some(dt) = self.dt {
+            scenario.dt = dt;
+        }
+        if let Some(velocity) = self.velocity {
+            scenario.velocity = velocity;
+        }
+        if let Some(stddev) = self.sensor_noise_stddev {
+            scenario.sensor_noise_stddev = stddev;
+        }
+        if let Some(r) = self.r {
+            scenario.r = Some(r);
+        }
+        if let Some(q) = self.q {
+            scenario.q = q;
+        }
+        if let Some(initial_position) = self.initial_position {
+            scenario.initial_position = initial_position;
+        }
+        if let Some(offset) = self.initial_position_offset {
+            scenario.initial_position_offset = offset;
+        }
+        if let Some(offset) = self.initial_velocity_offset {
+            scenario.initial_velocity_offset = offset;
+        }
+        if let Some(model) = self.filter_model {
+            scenario.model = model;
+        }
+        if let Some(particles) = self.particles {
+            scenario.particle.particles = particles as usize;
+        }
+        if let Some(resampling) = self.resampling {
+            scenario.particle.resampling = resampling;
+        }
+        let unscented = &mut scenario.unscented;
+        unscented.alpha = self.ukf_alpha.unwrap_or(unscented.alpha);
+        unscented.beta = self.ukf_beta.unwrap_or(unscented.beta);
+        unscented.kappa = self.ukf_kappa.unwrap_or(unscented.kappa);
+        if self.adaptive_q {
+            scenario.adaptive_q = Some(AdaptiveQ {
+                sensitivity: self.adaptive_q_sensitivity,
+                decay: self.adaptive_q_decay,
+            });
+        }
+        if let Some(interval) = self.measurement_interval {
+            scenario.measurement_interval = interval as usize;
+        }
+        if let Some(jitter) = self.timestamp_jitter {
+            scenario.timestamp_jitter = jitter;
+        }
+        if let Some(delay) = self.arrival_delay {
+            scenario.arrival_delay = delay;
+        }
+        if let Some(window) = self.reorder_window {
+            scenario.reorder_window = window;
+        }
+        if let Some(preset) = self.sensor {
+            scenario.sensor = self.sensor_model(preset);
+        }
+        if let Some(preset) = self.noise {
+            scenario.noise = self.sensor_noise(preset);
+        }
+        match self.trajectory {
+            Some(preset) => {
+                scenario.trajectory = self.preset_trajectory(preset, scenario.total_time)
+            }
+            None => match &mut scenario.trajectory {
+                Trajectory::RampHold { stop_time } => {
+                    *stop_time = self.stop_time.unwrap_or(*stop_time);
+                }
+                Trajectory::Piecewise { schedule } => {
+                    *schedule = self.velocity_schedule.unwrap_or(*schedule);
+                }
+                Trajectory::Sinusoidal { period, .. } => {
+                    *period = self.period.unwrap_or(*period);
+                }
+                Trajectory::Waypoints { waypoints } => {
+                    *waypoints = self.waypoints.unwrap_or(*waypoints);
+                }
+                _ => {}
+            },
+        }
+
+        let render = &mut scenario.render;
+        render.plot = self.plot.unwrap_or(render.plot);
+        render.theme = self.theme.unwrap_or(render.theme);
+        render.marker = self.marker.unwrap_or(render.marker);
+        render.easing = self.easing.unwrap_or(render.easing);
+        render.line_join = self.line_join.unwrap_or(render.line_join);
+        render.line_cap = self.line_cap.unwrap_or(render.line_cap);
+        render.grid_lines = self.grid_lines.unwrap_or(render.grid_lines);
+        render.margin = self.margin.unwrap_or(render.margin);
+        if self.axes {
+            render.margin = render.margin.max(AXES_MARGIN);
+        }
+        if let Some(frame_stride) = self.frame_stride.and_then(NonZeroU64::new) {
+            render.frame_stride = frame_stride;
+        }
+        if let Some(max_frames) = self.max_frames.and_then(NonZeroU64::new) {
+            render.max_frames = Some(max_frames);
+        }
+        if let Some(fps) = self.fps.and_then(NonZeroU16::new) {
+            render.fps = fps;
+        }
+    }
+
+    /// Sensor model `preset` stands for with the parameters given on the command line.
+    fn sensor_model(&self, preset: SensorPreset) -> SensorModel {
+        match preset {
+            SensorPreset::Additive => SensorModel::Additive,
+            SensorPreset::Multiplicative => SensorModel::Multiplicative {
+                relative_stddev: self.relative_stddev,
+            },
+            SensorPreset::Range => SensorModel::Range {
+                station: self.station,
+                offset: self.station_offset,
+            },
+        }
+    }
+
+    fn sensor_noise(&self, preset: NoisePreset) -> SensorNoise {
+        match preset {
+            NoisePreset::Gaussian => SensorNoise::Gaussian,
+            NoisePreset::Uniform => SensorNoise::Uniform,
+            NoisePreset::Laplace => SensorNoise::Laplace,
+            NoisePreset::StudentT => SensorNoise::StudentT {
+                degrees_of_freedom: self.degrees_of_freedom,
+            },
+        }
+    }
+
+    /// Trajectory `preset` stands for in a run of `total_time`, with the parameters given on
+    /// the command line.
+    fn preset_trajectory(&self, preset: TrajectoryPreset, total_time: f64) -> Trajectory {
+        match preset {
+            TrajectoryPreset::ConstantVelocity => Trajectory::ConstantVelocity,
+            TrajectoryPreset::RampHold => Trajectory::RampHold {
+                stop_time: self.stop_time.unwrap_or(total_time / 2.0),
+            },
+            TrajectoryPreset::Maneuver => Trajectory::Maneuver {
+                start_time: total_time * 0.4,
+                duration: total_time * 0.2,
+                acceleration: self.maneuver_acceleration,
+            },
+            TrajectoryPreset::Piecewise => Trajectory::Piecewise {
+                schedule: self.velocity_schedule.unwrap_or_else(|| {
+                    VelocitySchedule::try_from(vec![
+                        (total_time * 0.3, 2.5),
+                        (total_time * 0.7, 0.0),
+                    ])
+                    .unwrap()
+                }),
+            },
+            TrajectoryPreset::ConstantAcceleration => Trajectory::ConstantAcceleration {
+                acceleration: self.acceleration,
+            },
+            TrajectoryPreset::Sinusoidal => Trajectory::Sinusoidal {
+                amplitude: self.amplitude,
+                period: self.period.unwrap_or(total_time / 4.0),
+            },
+            TrajectoryPreset::Waypoints => Trajectory::Waypoints {
+                waypoints: self.waypoints.unwrap_or_else(|| {
+                    Waypoints::try_from(vec![
+                        (total_time * 0.3, 5.0),
+                        (total_time * 0.6, 5.0),
+                        (total_time, 12.0),
+                    ])
+                    .unwrap()
+                }),
             },
-            p: 1.0,
-            r,
-            q,
-            k: 0.0,
+            TrajectoryPreset::RandomWalk => Trajectory::RandomWalk {
+                density: self.walk_density,
+            },
+        }
+    }
+
+    /// Picks the seed of a run without `--seed`: that of the checkpoint of `--resume`, 0 for
+    /// `--monte-carlo`, or one from the system's entropy, so every run has a concrete seed to
+    /// record and repeat it with.
+    fn resolve_seed(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.seed.is_some() {
+            return Ok(());
         }
+        self.seed = Some(match (&self.resume, self.monte_carlo) {
+            (Some(path), _) => checkpoint::read_seed(path)?,
+            (None, Some(_)) => 0,
+            (None, None) => rand::random(),
+        });
+        Ok(())
     }
 
-    fn predict(&mut self, dt: f64) {
-        self.state.x += self.state.v * dt;
-        self.p += self.q;
+    /// Seed of every random draw of the run, once `resolve_seed` picked one.
+    fn seed(&self) -> u64 {
+        self.seed.expect("resolve_seed runs before the simulation")
     }
 
-    fn update(&mut self, measured_position: f64) {
-        self.k = self.p / (self.p + self.r);
-        self.state.x += self.k * (measured_position - self.state.x);
-        self.p *= 1.0 - self.k;
+    /// Generator of a run's random draws, seeded with `seed`.
+    fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed())
+    }
+
+    /// Loads the scenario at `path`, or the built-in one, with the command-line overrides,
+    /// and returns its parameters and plot settings.
+    fn load_scenario(&self, path: Option<&Path>) -> (SimulateConfig, RenderSettings) {
+        let mut scenario = match path {
+            Some(path) => Scenario::load(path).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            }),
+            None => Scenario::default(),
+        };
+        self.override_scenario(&mut scenario);
+
+        let config = scenario.to_config();
+        if let Err(err) = config.validate() {
+            eprintln!("Invalid parameters: {}", err);
+            process::exit(1);
+        }
+        if let Err(err) = scenario.render.validate(SIZE) {
+            eprintln!("Invalid parameters: {}", err);
+            process::exit(1);
+        }
+        (config, scenario.render)
     }
 }
 
-#[derive(Default)]
-struct SimulateTick {
-    true_positions: (f64, f64),
-    measured_positions: (f64, f64),
-    estimated_positions: (f64, f64),
+/// Writes `document` to `path` as SVG, or rasterized as PNG unless the extension is `svg`.
+fn write_static_plot(
+    document: &svg::Document,
+    path: &Path,
+    animate_options: &AnimateOptions,
+) -> Result<(), Box<dyn Error>> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"))
+    {
+        svg::save(path, document)?;
+    } else {
+        let (width, height) = frame_size(
+            animate_options.size,
+            animate_options.dual_pane,
+            animate_options.strips(),
+        );
+        rasterize(document, width, height)?.save_with_format(path, ImageFormat::Png)?;
+    }
+    Ok(())
+}
+
+/// Files a single run writes. Each one is skipped when its path is `None`.
+struct OutputPaths {
+    /// Animation, in `--format`
+    animation: Option<PathBuf>,
+    /// Final frame of the animation
+    png: Option<PathBuf>,
+    /// Final chart, rendered without the animation
+    static_plot: Option<PathBuf>,
+    csv: Option<PathBuf>,
+    json: Option<PathBuf>,
+    /// Directory for the gnuplot data files and script
+    gnuplot: Option<PathBuf>,
+    /// Directory for the innovation and error histograms
+    histograms: Option<PathBuf>,
+    manifest: Option<PathBuf>,
+    /// Phase timings of the run
+    profile: Option<PathBuf>,
+    /// Final state of the filter
+    snapshot: Option<PathBuf>,
 }
 
-#[derive(Default)]
-struct SimulateResult {
-    ticks: Vec<SimulateTick>,
+/// Simulates `config` with the filter model it selects, or runs that model over the rows of
+/// `--measurements` when given.
+fn simulate_or_import(
+    args: &Args,
+    config: &SimulateConfig,
+    rng: &mut StdRng,
+) -> Result<SimulateResult, Box<dyn Error>> {
+    Ok(match &args.measurements {
+        Some(path) => filter_recorded_model(config, &csv::read_measurements(path)?)?,
+        None => simulate_model(config, rng)?,
+    })
+}
+
+/// Parameters of the `--compare-noise` run with sensor noise `stddev`, keeping the filter's
+/// assumed noise in proportion to the real one.
+fn noisy_config(config: &SimulateConfig, stddev: f64) -> SimulateConfig {
+    let r = if config.sensor_noise_stddev > 0.0 {
+        config.r * (stddev / config.sensor_noise_stddev).powi(2)
+    } else {
+        stddev.powi(2)
+    };
+    SimulateConfig {
+        sensor_noise_stddev: stddev,
+        r,
+        ..*config
+    }
 }
 
-fn simulate(
-    total_time: f64,
-    dt: f64,
-    velocity: f64,
-    sensor_noise_stddev: f64,
-    r: f64,
-    q: f64,
-) -> SimulateResult {
-    let steps = (total_time / dt) as usize;
+/// Ranked RMSE table of `--compare-filters`, one row per filter form.
+fn filter_comparison_table(runs: &[VariantRun]) -> String {
+    let mut table =
+        String::from("RMSE of each filter form over the same measurements, best first:\n");
+    for (rank, run) in runs.iter().enumerate() {
+        table.push_str(&format!(
+            "  {}. {:<12} {:.4}\n",
+            rank + 1,
+            run.variant.name(),
+            run.rmse
+        ));
+    }
+    table
+}
+
+/// Simulates `config` with the known-velocity filter from the start, or from the checkpoint
+/// of `--resume`, saving a checkpoint to `--checkpoint` as it goes.
+fn checkpointed_run(
+    args: &Args,
+    config: &SimulateConfig,
+) -> Result<SimulateResult, Box<dyn Error>> {
+    if config.model != FilterModel::KnownVelocity {
+        return Err("checkpoints need the known-velocity filter model".into());
+    }
+    let filter = KalmanFilter::from_config(config);
+    let mut checkpoint = match &args.resume {
+        Some(path) => checkpoint::read_checkpoint(path, filter)?,
+        None => Checkpoint::new(args.seed(), filter),
+    };
+    if checkpoint.seed != args.seed() {
+        return Err(format!(
+            "the checkpoint was drawn from seed {}, not --seed {}",
+            checkpoint.seed,
+            args.seed()
+        )
+        .into());
+    }
+    if checkpoint.result.ticks.len() > config.steps() {
+        return Err("the checkpoint is from a longer run than this one".into());
+    }
+    // Start the file over from what's known so far, so every later save only appends its ticks
+    if let Some(path) = &args.checkpoint {
+        checkpoint::write_checkpoint(path, &checkpoint)?;
+    }
+    simulate_checkpointed(
+        config,
+        &mut checkpoint,
+        args.checkpoint_every as usize,
+        |checkpoint, ticks| match &args.checkpoint {
+            Some(path) => checkpoint::append_save(path, &checkpoint.filter, ticks),
+            None => Ok(()),
+        },
+    )?;
+    Ok(checkpoint.result)
+}
+
+/// Simulates `config` once and writes every file in `paths` from that result, plotted with
+/// `render`.
+fn run(
+    args: &Args,
+    config: &SimulateConfig,
+    render: &RenderSettings,
+    paths: &OutputPaths,
+) -> Result<Summary, Box<dyn Error>> {
+    let size = SIZE;
+    let mut outputs = Vec::new();
+    let mut timings = PhaseTimings::default();
+    let steady_state_gain = if args.steady_state_gain {
+        Some(metrics::steady_state_gain(config).ok_or(
+            "--steady-state-gain needs a filter whose gain settles: not the particle filter, \
+             and only with the additive sensor",
+        )?)
+    } else {
+        None
+    };
+
+    let mut rng = args.rng();
+    // The `--compare-noise` run starts from the same seed, so both panes share the truth
+    let mut noise_rng = rng.clone();
+    status!(args.quiet, "Simulating...");
+    let start = Instant::now();
+    let result = if args.checkpoint.is_some() || args.resume.is_some() {
+        checkpointed_run(args, config)?
+    } else if args.initial_filter.is_some() || paths.snapshot.is_some() {
+        if config.model != FilterModel::KnownVelocity {
+            return Err("filter snapshots need the known-velocity filter model".into());
+        }
+        let mut filter = match &args.initial_filter {
+            Some(path) => KalmanFilter::from_json(&fs::read_to_string(path)?)?,
+            None => KalmanFilter::from_config(config),
+        };
+        let result = match &args.measurements {
+            Some(path) => filter_recorded(config, &mut filter, &csv::read_measurements(path)?)?,
+            None => simulate(config, &mut filter, &mut rng)?,
+        };
+        if let Some(path) = &paths.snapshot {
+            fs::write(path, filter.to_json()?)?;
+            if !args.quiet {
+                println!("Filter snapshot saved to {}", path.display());
+            }
+            outputs.push(path.to_path_buf());
+        }
+        result
+    } else {
+        simulate_or_import(args, config, &mut rng)?
+    };
+    timings.simulate += start.elapsed().as_secs_f64();
+    if let Some(last) = result.ticks.last() {
+        status!(
+            args.quiet,
+            "Final estimate: x = {:.3}, v = {:.3}, p = {:.4}, k = {:.4}",
+            last.estimated_positions.1,
+            last.estimated_velocity,
+            last.covariance,
+            last.gain
+        );
+    }
+
+    let mut model_positions = Vec::new();
+    if args.compare_models {
+        if !args.quiet {
+            println!("RMSE of each filter model over the same measurements:");
+        }
+        for &model in FilterModel::value_variants() {
+            let model_config = SimulateConfig { model, ..*config };
+            // Only the nonlinear models (extended, unscented and particle) can use a range sensor
+            if model_config.validate().is_err() {
+                continue;
+            }
+            let positions = refilter(&result, &model_config);
+            if !args.quiet {
+                println!(
+                    "  {:<22} {:.4}",
+                    model.name(),
+                    metrics::positions_rmse(&result, &positions)
+                );
+            }
+            if model != config.model {
+                model_positions.push((model, positions));
+            }
+        }
+    }
+
+    // Metrics stay in simulation time; only the plots and exports switch to sample indices
+    let plotted = match args.x_axis {
+        XAxis::Time => None,
+        XAxis::Index => Some(result.by_index()),
+    };
+    let plotted_result = plotted.as_ref().unwrap_or(&result);
+    let x_column = args.x_axis.column_name(&args.time_unit);
+    let exported = (args.every > 1).then(|| plotted_result.every(args.every as usize));
+    let exported_result = exported.as_ref().unwrap_or(plotted_result);
 
-    let mut true_position = 0.0;
+    let start = Instant::now();
+    if let Some(path) = &paths.csv {
+        csv::write_csv(exported_result, path, &x_column)?;
+        if !args.quiet {
+            println!("CSV saved to {}", path.display());
+        }
+        outputs.push(path.to_path_buf());
+    }
+
+    if let Some(path) = &paths.json {
+        // Unlike the CSV, the JSON keeps simulation time whatever `--x-axis` plots
+        let every = (args.every > 1).then(|| result.every(args.every as usize));
+        json::write_json(
+            path,
+            config,
+            args.seed(),
+            &args.time_unit,
+            every.as_ref().unwrap_or(&result),
+        )?;
+        if !args.quiet {
+            println!("JSON saved to {}", path.display());
+        }
+        outputs.push(path.to_path_buf());
+    }
 
-    let mut kalman = KalmanFilter::new(0.0, velocity, r, q);
+    if let Some(dir) = &paths.gnuplot {
+        status!(args.quiet, "Writing gnuplot files...");
+        gnuplot::write_gnuplot(exported_result, dir, &x_column)?;
+        if !args.quiet {
+            println!("gnuplot files saved to {}", dir.display());
+        }
+        outputs.push(dir.clone());
+    }
 
-    let mut rng = rand::thread_rng();
+    if let Some(dir) = &paths.histograms {
+        histogram::write_histograms(&result, dir, args.histogram_bins as usize)?;
+        if !args.quiet {
+            println!("Histograms saved to {}", dir.display());
+        }
+        outputs.push(dir.clone());
+    }
+    timings.export += start.elapsed().as_secs_f64();
 
-    let mut result = SimulateResult::default();
+    // Only the plots are limited to `--range`; the exports and metrics cover the whole run
+    let in_range = |x: f64| {
+        args.range
+            .is_none_or(|(start, end)| (start..=end).contains(&x))
+    };
+    let ranged = args
+        .range
+        .map(|(start, end)| plotted_result.within(start, end));
+    let animated_result = ranged.as_ref().unwrap_or(plotted_result);
 
-    for step in 0..steps {
-        let time = step as f64 * dt;
+    // Pairs positions computed over the whole run with the plotted ticks
+    let plotted_track = |positions: Vec<f64>| {
+        plotted_result
+            .ticks
+            .iter()
+            .zip(positions)
+            .map(|(tick, position)| (tick.true_positions.0, position))
+            .filter(|&(x, _)| in_range(x))
+            .collect::<Vec<_>>()
+    };
 
-        true_position += velocity * dt;
+    // The smoother models the known-velocity filter with its fixed `q`
+    if args.compare_smoother
+        && (config.model != FilterModel::KnownVelocity || config.adaptive_q.is_some())
+    {
+        return Err(
+            "--compare-smoother needs the known-velocity filter model without --adaptive-q".into(),
+        );
+    }
+    let smoothed_positions = args.compare_smoother.then(|| {
+        let smoothed = smoother::rts_smooth(&result, config.q);
+        if !args.quiet {
+            println!(
+                "Filter RMSE: {:.4}, smoother RMSE: {:.4}",
+                metrics::estimate_rmse(&result),
+                metrics::positions_rmse(&result, &smoothed)
+            );
+        }
+        plotted_track(smoothed)
+    });
 
-        let noise: f64 = rng.gen_range(-sensor_noise_stddev..sensor_noise_stddev);
-        let measured_position = true_position + noise;
+    let variant_positions: Vec<_> = if args.compare_filters {
+        if matches!(config.sensor, SensorModel::Range { .. }) {
+            return Err("--compare-filters needs a sensor that measures the position".into());
+        }
+        let runs = variants::compare_filters(&result, config);
+        if !args.quiet {
+            print!("{}", filter_comparison_table(&runs));
+        }
+        runs.into_iter()
+            .map(|run| (run.variant, plotted_track(run.positions)))
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-        kalman.predict(dt);
-        kalman.update(measured_position);
+    let particle_clouds: Vec<_> = if args.show_particles {
+        plotted_result
+            .ticks
+            .iter()
+            .zip(particle::particle_clouds(&result, config))
+            .filter(|(tick, _)| in_range(tick.true_positions.0))
+            .map(|(_, cloud)| cloud)
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-        let tick = SimulateTick {
-            true_positions: (time, true_position),
-            measured_positions: (time, measured_position),
-            estimated_positions: (time, kalman.state.x),
-        };
+    let model_tracks: Vec<_> = model_positions
+        .into_iter()
+        .map(|(model, positions)| (model, plotted_track(positions)))
+        .collect();
 
-        result.ticks.push(tick);
-    }
-
-    result
-}
-
-fn render(
-    true_positions: &[(f64, f64)],
-    measured_positions: &[(f64, f64)],
-    estimated_positions: &[(f64, f64)],
-    size: usize,
-    scale: f64,
-) -> Document {
-    let mut document = Document::new()
-        .set("viewBox", (0, 0, size, size))
-        .set("width", "500")
-        .set("height", "500");
-
-    let background = svg::node::element::Rectangle::new()
-        .set("x", 0)
-        .set("y", 0)
-        .set("width", size)
-        .set("height", size)
-        .set("fill", "white");
-    document = document.add(background);
-
-    for i in 1..true_positions.len() {
-        let (x1, y1) = true_positions[i - 1];
-        let (x2, y2) = true_positions[i];
-        let line = Line::new()
-            .set("x1", x1 * scale)
-            .set("y1", size as f64 - y1 * scale)
-            .set("x2", x2 * scale)
-            .set("y2", size as f64 - y2 * scale)
-            .set("stroke", "red")
-            .set("stroke-width", 2);
-        document = document.add(line);
-    }
-
-    for i in 1..estimated_positions.len() {
-        let (x1, y1) = estimated_positions[i - 1];
-        let (x2, y2) = estimated_positions[i];
-        let line = Line::new()
-            .set("x1", x1 * scale)
-            .set("y1", size as f64 - y1 * scale)
-            .set("x2", x2 * scale)
-            .set("y2", size as f64 - y2 * scale)
-            .set("stroke", "green")
-            .set("stroke-width", 2);
-        document = document.add(line);
-    }
-
-    for (x, y) in measured_positions {
-        let circle = Circle::new()
-            .set("cx", x * scale)
-            .set("cy", size as f64 - y * scale)
-            .set("r", 2.0)
-            .set("fill", "blue");
-        document = document.add(circle);
-    }
-
-    document
-}
-
-fn animate(result: SimulateResult, size: usize, scale: f64) -> Vec<Frame> {
-    let mut frames = Vec::new();
-    let options = usvg::Options::default();
-
-    let mut true_positions = Vec::new();
-    let mut measured_positions = Vec::new();
-    let mut estimated_positions = Vec::new();
-    for (i, tick) in result.ticks.iter().enumerate() {
-        if i % 10 == 9 {
-            eprintln!("{}/{} frames", i + 1, result.ticks.len());
-        }
-        true_positions.push(tick.true_positions);
-        measured_positions.push(tick.measured_positions);
-        estimated_positions.push(tick.estimated_positions);
-
-        let document = render(
-            &true_positions,
-            &measured_positions,
-            &estimated_positions,
-            size,
-            scale,
+    if args.passthrough && !args.quiet {
+        // Passing the measurements through unfiltered makes its error the measurement error
+        println!(
+            "Filter RMSE: {:.4}, unfiltered RMSE: {:.4}",
+            metrics::estimate_rmse(&result),
+            metrics::measurement_rmse(&result)
         );
+    }
 
-        let svg = document.to_string();
-        let tree = usvg::Tree::from_str(&svg, &options).unwrap();
-        let mut pixmap = Pixmap::new(500, 500).unwrap();
-        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    let palette = render.theme.palette();
+    let mut overlays = Vec::new();
+    if let Some(points) = &smoothed_positions {
+        overlays.push(Overlay {
+            label: "smoothed",
+            style: palette.smoothed,
+            points,
+        });
+    }
+    for (variant, points) in &variant_positions {
+        overlays.push(Overlay {
+            label: variant.name(),
+            style: palette.variant(*variant),
+            points,
+        });
+    }
+    for (model, points) in &model_tracks {
+        overlays.push(Overlay {
+            label: model.name(),
+            style: palette.model(*model),
+            points,
+        });
+    }
+
+    status!(args.quiet, "Rendering frames...");
+    let render_options = RenderOptions {
+        time_unit: args.time_unit.clone(),
+        x_axis: args.x_axis,
+        scale_bar: args.scale_bar,
+        log_y: args.log_y,
+        time_annotation: !args.no_time_label,
+        regime_changes: if args.no_regime_markers || !config.trajectory.has_regimes() {
+            Vec::new()
+        } else {
+            metrics::regime_changes(plotted_result)
+                .into_iter()
+                .filter(|change| in_range(change.time))
+                .collect()
+        },
+        marker_shape: render.marker,
+        passthrough: args.passthrough,
+        error_bars: args.error_bars.then_some((config.sensor, config.r)),
+        noise_bands: args.noise_bands.then_some(NoiseBands {
+            sensor: config.sensor,
+            q: config.q,
+            r: config.r,
+        }),
+        covariance_band: args
+            .covariance_band
+            .then(|| args.band_sigmas.unwrap_or(2.0)),
+        steady_state_gain,
+        grid_lines: render.grid_lines,
+        axes: args.axes,
+        palette,
+        line_join: render.line_join,
+        line_cap: render.line_cap,
+    };
+    let animate_options = AnimateOptions {
+        size,
+        dual_pane: args.dual_pane,
+        gain_panel: args.gain_panel,
+        innovation_panel: args.innovation_panel,
+        measurement_error: args.measurement_error,
+        frame_stride: render.frame_stride.get() as usize,
+        // The PNG alone only needs the final frame
+        max_frames: match &paths.animation {
+            Some(_) => render
+                .max_frames
+                .map(|max_frames| max_frames.get() as usize),
+            None => Some(1),
+        },
+        easing: render.easing,
+        margin: render.margin,
+        plot: render.plot,
+        fast_render: args.fast_render,
+        show_progress: !args.quiet,
+    };
+    if let Some(path) = &paths.static_plot {
+        let start = Instant::now();
+        let document = render_final(
+            animated_result,
+            &overlays,
+            &particle_clouds,
+            &render_options,
+            &animate_options,
+        )?;
+        timings.render.total += start.elapsed().as_secs_f64();
+        let start = Instant::now();
+        write_static_plot(&document, path, &animate_options)?;
+        timings.encode += start.elapsed().as_secs_f64();
+        if !args.quiet {
+            println!("Static plot saved to {}", path.display());
+        }
+        outputs.push(path.clone());
+    }
 
-        let image = RgbaImage::from_raw(500, 500, pixmap.data().to_vec()).unwrap();
-        frames.push(Frame::new(image));
+    let start = Instant::now();
+    let mut simulate_noisy = 0.0;
+    let animation = match args.compare_noise {
+        // Nothing left to animate when `--static` is the only image
+        _ if paths.animation.is_none() && paths.png.is_none() => Vec::new(),
+        None => animate(
+            animated_result,
+            animated_result,
+            &overlays,
+            &particle_clouds,
+            &render_options,
+            &animate_options,
+            &mut timings.render,
+        )?,
+        Some(stddev) => {
+            status!(args.quiet, "Simulating with noise {}...", stddev);
+            let simulate_start = Instant::now();
+            let noisy = simulate_model(&noisy_config(config, stddev), &mut noise_rng)?;
+            simulate_noisy = simulate_start.elapsed().as_secs_f64();
+            let noisy = match args.x_axis {
+                XAxis::Time => noisy,
+                XAxis::Index => noisy.by_index(),
+            };
+            let noisy = match args.range {
+                Some((start, end)) => noisy.within(start, end),
+                None => noisy,
+            };
+            let both = SimulateResult {
+                ticks: animated_result
+                    .ticks
+                    .iter()
+                    .chain(&noisy.ticks)
+                    .cloned()
+                    .collect(),
+            };
+            let left = animate(
+                animated_result,
+                &both,
+                &overlays,
+                &particle_clouds,
+                &render_options,
+                &animate_options,
+                &mut timings.render,
+            )?;
+            let right = animate(
+                &noisy,
+                &both,
+                &[],
+                &[],
+                &render_options,
+                &animate_options,
+                &mut timings.render,
+            )?;
+            composite_frames(left, right)
+        }
+    };
+    timings.simulate += simulate_noisy;
+    timings.render.total += start.elapsed().as_secs_f64() - simulate_noisy;
+    let frame_count = animation.len();
+
+    let start = Instant::now();
+
+    if let Some(path) = &paths.png {
+        let last = animation
+            .last()
+            .ok_or("no frame left to write to the PNG")?;
+        last.buffer().save_with_format(path, ImageFormat::Png)?;
+        if !args.quiet {
+            println!("PNG saved to {}", path.display());
+        }
+        outputs.push(path.clone());
+    }
+
+    if let Some(path) = &paths.animation {
+        status!(
+            args.quiet,
+            "Encoding {}...",
+            args.format.extension().to_uppercase()
+        );
+        output::write_animation(
+            animation,
+            args.format,
+            args.gif_colors,
+            render.fps.get(),
+            path,
+            !args.quiet,
+        )?;
+        outputs.push(path.clone());
+
+        if !args.quiet {
+            println!(
+                "Output saved to {} ({:.1} KiB)",
+                path.display(),
+                fs::metadata(path)?.len() as f64 / 1024.0
+            );
+        }
+    }
+    timings.encode += start.elapsed().as_secs_f64();
+
+    if let Some(path) = &paths.profile {
+        profile::write_profile(path, config, frame_count, &timings)?;
+        if !args.quiet {
+            println!("Profile saved to {}", path.display());
+        }
+        outputs.push(path.to_path_buf());
+    }
+
+    if let Some(path) = &paths.manifest {
+        manifest::write_manifest(path, config, args.seed(), &outputs)?;
+        if !args.quiet {
+            println!("Manifest saved to {}", path.display());
+        }
+        outputs.push(path.to_path_buf());
     }
 
-    frames
+    Ok(Summary::new(
+        &result,
+        config,
+        &args.time_unit,
+        args.consistency_confidence,
+        args.warmup,
+        outputs,
+    ))
+}
+
+/// Appends the names and descriptions of the `T` presets under `title` to `listing`.
+fn list_preset_group<T: ValueEnum>(listing: &mut String, title: &str) {
+    listing.push_str(&format!("{}:\n", title));
+    for preset in T::value_variants() {
+        let value = preset.to_possible_value().unwrap();
+        let help = value
+            .get_help()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        listing.push_str(&format!("  {:<24} {}\n", value.get_name(), help));
+    }
+}
+
+/// Every preset that `--trajectory`, `--sensor`, `--noise` and `--filter-model` accept, as
+/// `--list-presets` prints them.
+fn preset_listing() -> String {
+    let mut listing = String::new();
+    list_preset_group::<TrajectoryPreset>(&mut listing, "trajectories (--trajectory)");
+    list_preset_group::<SensorPreset>(&mut listing, "sensors (--sensor)");
+    list_preset_group::<NoisePreset>(&mut listing, "sensor noise (--noise)");
+    list_preset_group::<FilterModel>(&mut listing, "filter models (--filter-model)");
+    listing
+}
+
+/// Prints the scenario-file line every preset named `name` expands to in a run of `config`,
+/// with the parameters given on the command line.
+fn describe_preset(args: &Args, config: &SimulateConfig, name: &str) -> Result<(), Box<dyn Error>> {
+    let expansions = [
+        TrajectoryPreset::from_str(name, false).map(|preset| {
+            (
+                "trajectory",
+                toml::Value::try_from(args.preset_trajectory(preset, config.total_time)),
+            )
+        }),
+        SensorPreset::from_str(name, false)
+            .map(|preset| ("sensor", toml::Value::try_from(args.sensor_model(preset)))),
+        NoisePreset::from_str(name, false)
+            .map(|preset| ("noise", toml::Value::try_from(args.sensor_noise(preset)))),
+        FilterModel::from_str(name, false).map(|model| ("model", toml::Value::try_from(model))),
+    ];
+    let mut found = false;
+    for (key, value) in expansions.into_iter().flatten() {
+        println!("{} = {}", key, value?);
+        found = true;
+    }
+    if !found {
+        return Err(format!("no preset named {}; see --list-presets", name).into());
+    }
+    Ok(())
+}
+
+/// Prints what a run of `config` would produce without simulating it.
+fn print_dry_run(args: &Args, config: &SimulateConfig, render: &RenderSettings) {
+    let size = SIZE;
+    let steps = config.steps();
+    let frames = frame_ticks(
+        steps,
+        render.frame_stride.get() as usize,
+        render
+            .max_frames
+            .map(|max_frames| max_frames.get() as usize),
+        render.easing,
+    )
+    .len();
+    let strips = usize::from(args.gain_panel) + usize::from(args.innovation_panel);
+    let (width, height) = frame_size(size, args.dual_pane, strips);
+    let frame_bytes = width * height;
+
+    println!("steps:             {}", steps);
+    if let Some(gain) = metrics::steady_state_gain(config) {
+        println!("steady-state gain: {:.4}", gain);
+    }
+    println!("frames:            {} of {}x{}", frames, width, height);
+    println!(
+        "duration:          {:.1} s at {} fps",
+        frames as f64 / render.fps.get() as f64,
+        render.fps
+    );
+    // One palette index per pixel before LZW compression, which usually shrinks it a lot
+    println!(
+        "output size:       up to {:.1} MB",
+        (frames * frame_bytes) as f64 / 1e6
+    );
+}
+
+/// Runs every scenario in `config_paths`, naming the outputs after the file stems, and prints
+/// a table of their RMSEs.
+fn run_batch(args: &Args, config_paths: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let mut rows = Vec::new();
+    for path in config_paths {
+        let stem = path.file_stem().unwrap_or(path.as_os_str());
+        let (config, render) = args.load_scenario(Some(path));
+        status!(args.quiet, "=== {} ===", path.display());
+
+        // Appended rather than `with_extension`, which would cut a dotted stem like `run.v2`
+        let with_extension = |extension: &OsStr| {
+            let mut name = stem.to_os_string();
+            name.push(".");
+            name.push(extension);
+            PathBuf::from(name)
+        };
+        let named = |requested: bool, extension: &str| {
+            requested.then(|| with_extension(extension.as_ref()))
+        };
+        let paths = OutputPaths {
+            animation: named(
+                args.gif.is_some() || (args.png.is_none() && args.static_plot.is_none()),
+                args.format.extension(),
+            ),
+            png: named(args.png.is_some(), "png"),
+            static_plot: args
+                .static_plot
+                .as_ref()
+                .map(|path| with_extension(path.extension().unwrap_or("png".as_ref()))),
+            csv: named(args.csv.is_some(), "csv"),
+            json: named(args.json.is_some(), "json"),
+            gnuplot: args.gnuplot.as_ref().map(|dir| dir.join(stem)),
+            histograms: args.histograms.as_ref().map(|dir| dir.join(stem)),
+            manifest: named(args.manifest.is_some(), "manifest.json"),
+            profile: named(args.profile.is_some(), "profile.json"),
+            snapshot: named(args.filter_snapshot.is_some(), "filter.json"),
+        };
+        let summary = run(args, &config, &render, &paths)?;
+        rows.push((path, summary));
+    }
+
+    if !args.quiet {
+        println!(
+            "{:<24} {:>14} {:>17}",
+            "config", "estimate RMSE", "measurement RMSE"
+        );
+        for (path, summary) in &rows {
+            println!(
+                "{:<24} {:>14.4} {:>17.4}",
+                path.display(),
+                summary.estimate_rmse,
+                summary.measurement_rmse
+            );
+        }
+    }
+    Ok(())
 }
 
 fn main() {
-    // === Parameters ===
-    let total_time = 10.0;
-    let dt = 0.1;
-    let velocity = 1.0;
-    let sensor_noise_stddev: f64 = 2.0;
-    let r = sensor_noise_stddev.powi(2);
-    let q = 0.01;
-    // ==================
+    let mut args = Args::parse();
+    if let Err(err) = args.resolve_seed() {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
 
-    let size = 500;
-    let scale = size as f64 / total_time;
+    if let Err(err) = ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()
+    {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
 
-    eprintln!("Simulating...");
-    let result = simulate(total_time, dt, velocity, sensor_noise_stddev, r, q);
+    let config_paths = config::expand_config_paths(&args.config).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let batch = config_paths.len() > 1 || args.config.iter().any(|path| path.is_dir());
 
-    eprintln!("Rendering frames...");
-    let animation = animate(result, size, scale);
+    if args.list_presets {
+        print!("{}", preset_listing());
+        return;
+    }
 
-    let output_file = File::create("output.gif").unwrap();
-    let writer = BufWriter::new(output_file);
+    if let Some(name) = &args.describe_preset {
+        let (config, _) = args.load_scenario(config_paths.first().map(PathBuf::as_path));
+        if let Err(err) = describe_preset(&args, &config, name) {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
 
-    eprintln!("Encoding GIF...");
-    let mut encoder = image::codecs::gif::GifEncoder::new(writer);
-    encoder.encode_frames(animation).unwrap();
+    if args.dry_run {
+        if config_paths.is_empty() {
+            let (config, render) = args.load_scenario(None);
+            print_dry_run(&args, &config, &render);
+        }
+        for path in &config_paths {
+            println!("=== {} ===", path.display());
+            let (config, render) = args.load_scenario(Some(path));
+            print_dry_run(&args, &config, &render);
+        }
+        return;
+    }
 
-    println!("Output saved to output.gif");
+    if batch {
+        if let Err(err) = run_batch(&args, &config_paths) {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let (config, render) = args.load_scenario(config_paths.first().map(PathBuf::as_path));
+
+    if let Some(runs) = args.monte_carlo {
+        status!(args.quiet, "Running {} simulations...", runs);
+        let seed = args.seed();
+        let outcome = montecarlo::run_monte_carlo(
+            &config,
+            runs as usize,
+            seed,
+            args.threads,
+            args.consistency_confidence,
+        );
+        match outcome {
+            Ok(summary) => {
+                if !args.quiet {
+                    println!("Mean ± std over {} runs from seed {}:", summary.runs, seed);
+                    println!(
+                        "  estimate RMSE:    {:.4} ± {:.4}",
+                        summary.estimate_rmse.mean, summary.estimate_rmse.std
+                    );
+                    println!(
+                        "  measurement RMSE: {:.4} ± {:.4}",
+                        summary.measurement_rmse.mean, summary.measurement_rmse.std
+                    );
+                    if let Some(test) = &summary.nees_test {
+                        println!("  NEES chi-square:  {}", ChiSquare(test));
+                    }
+                    if let Some(test) = &summary.nis_test {
+                        println!("  NIS chi-square:   {}", ChiSquare(test));
+                    }
+                }
+                if let Some(path) = &args.monte_carlo_json {
+                    let outcome = montecarlo::write_monte_carlo_json(path, &config, seed, &summary);
+                    if let Err(err) = outcome {
+                        eprintln!("Error: {}", err);
+                        process::exit(1);
+                    }
+                    if !args.quiet {
+                        println!("Monte Carlo results saved to {}", path.display());
+                    }
+                }
+                if let Some(path) = &args.manifest {
+                    let outputs: Vec<PathBuf> = args.monte_carlo_json.iter().cloned().collect();
+                    if let Err(err) = manifest::write_manifest(path, &config, seed, &outputs) {
+                        eprintln!("Error: {}", err);
+                        process::exit(1);
+                    }
+                    if !args.quiet {
+                        println!("Manifest saved to {}", path.display());
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &args.convergence_study {
+        if metrics::steady_state_gain(&config).is_none() {
+            eprintln!(
+                "Error: --convergence-study needs a filter whose gain settles: not the particle \
+                 filter, and only with the additive sensor"
+            );
+            process::exit(1);
+        }
+        status!(args.quiet, "Running convergence study...");
+        let outcome = study::run_convergence_study(
+            &config,
+            args.study_parameter,
+            &args.study_values,
+            &mut args.rng(),
+        )
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|rows| {
+            study::write_study_csv(&rows, args.study_parameter, path)
+                .map_err(Box::<dyn Error>::from)
+        });
+        if let Err(err) = outcome {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        if !args.quiet {
+            println!("Convergence study saved to {}", path.display());
+        }
+        return;
+    }
+
+    if let Some(path) = &args.tradeoff {
+        status!(args.quiet, "Running measurement-rate tradeoff...");
+        let outcome = tradeoff::run_tradeoff(&config, &args.tradeoff_rates, &mut args.rng())
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|rows| {
+                tradeoff::write_tradeoff_csv(&rows, path).map_err(Box::<dyn Error>::from)
+            });
+        if let Err(err) = outcome {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        if !args.quiet {
+            println!("Tradeoff results saved to {}", path.display());
+        }
+        return;
+    }
+
+    if let Some(path) = &args.sweep {
+        status!(
+            args.quiet,
+            "Sweeping {} q by {} r values...",
+            args.sweep_q.len(),
+            args.sweep_r.len()
+        );
+        let outcome = sweep::run_sweep(&config, &args.sweep_q, &args.sweep_r, &args.rng())
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|sweep| sweep::write_sweep(&sweep, path).map(|()| sweep));
+        match outcome {
+            Ok(sweep) => {
+                if !args.quiet {
+                    if let Some((q, r, rmse)) = sweep.best() {
+                        println!("Lowest estimate RMSE {:.4} at q = {}, r = {}", rmse, q, r);
+                    }
+                    println!("Sweep saved to {}", path.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.auto_tune {
+        status!(args.quiet, "Tuning q and r...");
+        let objective = args.tune_objective;
+        let outcome = match &args.measurements {
+            Some(path) => csv::read_measurements(path).and_then(|measurements| {
+                Ok(tune::run_auto_tune(&config, objective, |config| {
+                    filter_recorded_model(config, &measurements)
+                })?)
+            }),
+            None => {
+                let rng = args.rng();
+                tune::run_auto_tune(&config, objective, |config| {
+                    simulate_model(config, &mut rng.clone())
+                })
+                .map_err(Box::<dyn Error>::from)
+            }
+        };
+        match outcome {
+            Ok(tuned) => {
+                if !args.quiet {
+                    let (q, r, cost) = tuned.start;
+                    println!(
+                        "Started from q = {:.4e}, r = {:.4e}: {} {:.4}",
+                        q,
+                        r,
+                        objective.name(),
+                        objective.value(cost)
+                    );
+                    let (q, r, cost) = tuned.best;
+                    println!(
+                        "Tuned after {} runs:  q = {:.4e}, r = {:.4e}: {} {:.4}",
+                        tuned.evaluations,
+                        q,
+                        r,
+                        objective.name(),
+                        objective.value(cost)
+                    );
+                    if tuned.at_bound {
+                        println!(
+                            "The best q or r is at the edge of the search range, so the {} may \
+                             keep improving beyond it",
+                            objective.name()
+                        );
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.stream {
+        if let Err(err) = stream::run_stream(&config) {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if args.tui {
+        let outcome = simulate_or_import(&args, &config, &mut args.rng()).and_then(|result| {
+            tui::run_tui(&result, &render.theme.palette(), &args.time_unit)
+                .map_err(Box::<dyn Error>::from)
+        });
+        if let Err(err) = outcome {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let paths = OutputPaths {
+        animation: match (&args.gif, &args.png, &args.static_plot) {
+            (Some(path), ..) => Some(path.clone()),
+            (None, None, None) => {
+                Some(PathBuf::from("output").with_extension(args.format.extension()))
+            }
+            _ => None,
+        },
+        png: args.png.clone(),
+        static_plot: args.static_plot.clone(),
+        csv: args.csv.clone(),
+        json: args.json.clone(),
+        gnuplot: args.gnuplot.clone(),
+        histograms: args.histograms.clone(),
+        manifest: args.manifest.clone(),
+        profile: args.profile.clone(),
+        snapshot: args.filter_snapshot.clone(),
+    };
+    match run(&args, &config, &render, &paths) {
+        Ok(summary) => {
+            if args.summary && !args.quiet {
+                print!("{}", summary);
+            }
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kalman_filter_simulator::trajectory::Trajectory;
+    use kalman_filter_simulator::variants::FilterVariant;
+
+    use super::*;
+    use crate::testing::{default_config, seeded_run};
+
+    #[test]
+    fn noise_comparison_shares_the_random_truth() {
+        let config = SimulateConfig {
+            trajectory: Trajectory::RandomWalk { density: 0.5 },
+            timestamp_jitter: 0.03,
+            ..default_config()
+        };
+        let rng = StdRng::seed_from_u64(3);
+        let quiet = simulate_model(&config, &mut rng.clone()).unwrap();
+        let noisy = simulate_model(&noisy_config(&config, 6.0), &mut rng.clone()).unwrap();
+        let truth = |result: &SimulateResult| -> Vec<(f64, f64)> {
+            result
+                .ticks
+                .iter()
+                .map(|tick| tick.true_positions)
+                .collect()
+        };
+        assert_eq!(truth(&quiet), truth(&noisy));
+        assert_ne!(
+            metrics::measurement_rmse(&quiet),
+            metrics::measurement_rmse(&noisy)
+        );
+    }
+
+    /// Names of the `T` presets, as the command line spells them.
+    fn preset_names<T: ValueEnum>() -> Vec<String> {
+        T::value_variants()
+            .iter()
+            .map(|preset| preset.to_possible_value().unwrap().get_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn preset_listing_names_every_preset() {
+        let listing = preset_listing();
+        let names = [
+            preset_names::<TrajectoryPreset>(),
+            preset_names::<SensorPreset>(),
+            preset_names::<NoisePreset>(),
+            preset_names::<FilterModel>(),
+        ]
+        .concat();
+        assert!(names.len() > 10);
+        for name in names {
+            assert!(
+                listing
+                    .lines()
+                    .any(|line| line.trim_start().starts_with(&format!("{} ", name))),
+                "{} missing from\n{}",
+                name,
+                listing
+            );
+        }
+    }
+
+    #[test]
+    fn every_run_gets_a_concrete_seed() {
+        let resolved = |args: &[&str]| {
+            let mut args = Args::parse_from(
+                std::iter::once("kalman-filter-simulator").chain(args.iter().copied()),
+            );
+            args.resolve_seed().unwrap();
+            args.seed()
+        };
+        assert_eq!(resolved(&["--seed", "5"]), 5);
+        assert_eq!(resolved(&["--monte-carlo", "10"]), 0);
+        // Two entropy draws only collide with probability 2^-64
+        assert_ne!(resolved(&[]), resolved(&[]));
+    }
+
+    #[test]
+    fn filter_comparison_has_a_row_per_variant() {
+        let config = default_config();
+        let result = seeded_run(&config);
+        let table = filter_comparison_table(&variants::compare_filters(&result, &config));
+        let rows: Vec<&str> = table.lines().skip(1).collect();
+        assert_eq!(rows.len(), FilterVariant::ALL.len(), "{}", table);
+        for variant in FilterVariant::ALL {
+            let row = rows
+                .iter()
+                .find(|row| row.split_whitespace().nth(1) == Some(variant.name()));
+            assert!(row.is_some(), "{} missing from\n{}", variant.name(), table);
+        }
+        assert!(rows[0].trim_start().starts_with("1. "));
+    }
 }
@@ -0,0 +1,34 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use kalman_filter_simulator::sim::SimulateConfig;
+
+/// What a run was configured with and which files it produced, for downstream pipelines.
+#[derive(Serialize)]
+struct Manifest<'a> {
+    config: &'a SimulateConfig,
+    /// Seed every random draw was derived from
+    seed: u64,
+    outputs: &'a [PathBuf],
+}
+
+/// Writes the manifest of a run that wrote `outputs` as pretty-printed JSON.
+pub fn write_manifest(
+    path: &Path,
+    config: &SimulateConfig,
+    seed: u64,
+    outputs: &[PathBuf],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let manifest = Manifest {
+        config,
+        seed,
+        outputs,
+    };
+    serde_json::to_writer_pretty(&mut writer, &manifest)?;
+    writeln!(writer)?;
+    writer.flush()
+}
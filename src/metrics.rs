@@ -0,0 +1,737 @@
+use nalgebra::{Matrix1, SMatrix, SVector, Vector1};
+use serde::Serialize;
+
+use crate::dynamics::{ConstantAcceleration, ConstantVelocity, Dynamics, FilterModel};
+use crate::linear::KalmanFilter;
+use crate::sensor::SensorModel;
+use crate::sim::{SimulateConfig, SimulateResult};
+
+/// Gain within this fraction of the steady-state gain counts as converged.
+pub const CONVERGENCE_TOLERANCE: f64 = 0.01;
+
+/// Most updates `steady_state_gain` iterates the covariance recursion for before it settles
+/// on the latest gain.
+const STEADY_STATE_ITERATIONS: usize = 100_000;
+
+fn rmse(errors: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = errors.fold((0.0, 0usize), |(sum, count), error| {
+        (sum + error * error, count + 1)
+    });
+    if count == 0 {
+        return 0.0;
+    }
+    (sum / count as f64).sqrt()
+}
+
+/// Root mean square error of the filter estimate against the true position.
+pub fn estimate_rmse(result: &SimulateResult) -> f64 {
+    rmse(
+        result
+            .ticks
+            .iter()
+            .map(|tick| tick.estimated_positions.1 - tick.true_positions.1),
+    )
+}
+
+/// Root mean square error of the raw measurements against the true position.
+pub fn measurement_rmse(result: &SimulateResult) -> f64 {
+    rmse(result.ticks.iter().filter_map(|tick| {
+        tick.measured_positions
+            .map(|(_, measured)| measured - tick.true_positions.1)
+    }))
+}
+
+/// Gain the filter selected by `config` settles on, or `None` when its gain depends on the
+/// measurements: the particle filter's does, and so does every filter's with a sensor whose
+/// variance or linearization follows the position.
+///
+/// The known-velocity filter has a closed form. The models with a velocity (and
+/// acceleration) iterate their covariance recursion, which needs no measurements, to its fixed
+/// point and report the position component of the gain, as the runs record it. The extended
+/// and unscented filters follow the constant-velocity recursion exactly under a linear sensor.
+pub fn steady_state_gain(config: &SimulateConfig) -> Option<f64> {
+    if config.sensor != SensorModel::Additive {
+        return None;
+    }
+    match config.model {
+        FilterModel::KnownVelocity => Some(scalar_steady_state_gain(
+            config.r,
+            config.q,
+            config.measurement_interval,
+        )),
+        FilterModel::ConstantVelocity | FilterModel::Extended | FilterModel::Unscented => {
+            Some(model_steady_state_gain(ConstantVelocity, config))
+        }
+        FilterModel::ConstantAcceleration => {
+            Some(model_steady_state_gain(ConstantAcceleration, config))
+        }
+        FilterModel::Particle => None,
+    }
+}
+
+/// Gain the scalar filter settles on when it measures every `measurement_interval` ticks.
+///
+/// The predicted variance `m` before each update is the fixed point of
+/// `m = m * r / (m + r) + q_total`, where `q_total` is the process noise accumulated between
+/// updates.
+pub fn scalar_steady_state_gain(r: f64, q: f64, measurement_interval: usize) -> f64 {
+    let q_total = q * measurement_interval as f64;
+    let predicted = (q_total + (q_total * q_total + 4.0 * q_total * r).sqrt()) / 2.0;
+    predicted / (predicted + r)
+}
+
+/// Position gain of a filter over `dynamics` once repeated updates every
+/// `measurement_interval` ticks of `dt` stop changing it, starting from the unit covariance
+/// the runs start from.
+fn model_steady_state_gain<D: Dynamics<N>, const N: usize>(
+    dynamics: D,
+    config: &SimulateConfig,
+) -> f64 {
+    let mut h = SMatrix::zeros();
+    h[0] = 1.0;
+    let mut filter = KalmanFilter::<N, 1>::new(
+        SVector::zeros(),
+        SMatrix::identity(),
+        dynamics.transition(config.dt),
+        h,
+        dynamics.process_noise(config.q, config.dt),
+        Matrix1::new(config.r),
+    );
+    let mut gain = f64::NAN;
+    for _ in 0..STEADY_STATE_ITERATIONS {
+        for _ in 0..config.measurement_interval {
+            filter.predict();
+        }
+        // The gain and covariance don't depend on the measurement, so the state stays at zero
+        filter.update(&Vector1::zeros());
+        let previous = std::mem::replace(&mut gain, filter.gain()[0]);
+        if gain == previous {
+            break;
+        }
+    }
+    gain
+}
+
+/// First time after which the gain stays within `tolerance` (relative) of `steady_state_gain`
+/// for the rest of the run.
+pub fn convergence_time(
+    result: &SimulateResult,
+    steady_state_gain: f64,
+    tolerance: f64,
+) -> Option<f64> {
+    let within = |gain: f64| (gain - steady_state_gain).abs() <= tolerance * steady_state_gain;
+    let last_outside = result.ticks.iter().rposition(|tick| !within(tick.gain));
+    match last_outside {
+        None => result.ticks.first().map(|tick| tick.true_positions.0),
+        Some(i) => result.ticks.get(i + 1).map(|tick| tick.true_positions.0),
+    }
+}
+
+/// Sum of squared errors, each normalized by the variance the filter predicted for it, and
+/// the number of terms. For a consistent filter it follows a chi-square distribution with
+/// one degree of freedom per term.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct NormalizedSquares {
+    pub sum: f64,
+    pub dof: usize,
+}
+
+impl NormalizedSquares {
+    fn of(terms: impl Iterator<Item = (f64, f64)>) -> Self {
+        terms.fold(
+            NormalizedSquares::default(),
+            |squares, (error, variance)| NormalizedSquares {
+                sum: squares.sum + error * error / variance,
+                dof: squares.dof + 1,
+            },
+        )
+    }
+
+    /// Pools the terms of both, as of several independent runs.
+    pub fn pool(self, other: NormalizedSquares) -> Self {
+        NormalizedSquares {
+            sum: self.sum + other.sum,
+            dof: self.dof + other.dof,
+        }
+    }
+
+    /// Two-sided chi-square test at `confidence`. Returns `None` without any terms.
+    pub fn test(self, confidence: f64) -> Option<ConsistencyTest> {
+        if self.dof == 0 {
+            return None;
+        }
+        let tail = (1.0 - confidence) / 2.0;
+        Some(ConsistencyTest {
+            squares: self,
+            confidence,
+            lower: chi_square_quantile(tail, self.dof),
+            upper: chi_square_quantile(1.0 - tail, self.dof),
+        })
+    }
+}
+
+/// Result of a chi-square consistency test over normalized squared errors.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ConsistencyTest {
+    #[serde(flatten)]
+    pub squares: NormalizedSquares,
+    pub confidence: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl ConsistencyTest {
+    /// Whether the sum falls inside the two-sided confidence interval. Below it the filter
+    /// is underconfident, predicting larger errors than it makes, and above it
+    /// overconfident.
+    pub fn passed(&self) -> bool {
+        self.lower <= self.squares.sum && self.squares.sum <= self.upper
+    }
+}
+
+/// Normalized innovation squares (NIS) `ν² / S` over every measurement of `result`.
+pub fn nis(result: &SimulateResult) -> NormalizedSquares {
+    NormalizedSquares::of(result.ticks.iter().filter_map(|tick| tick.innovation))
+}
+
+/// Normalized estimation error squares (NEES) `(x̂ - x)² / p` of the position over every tick
+/// of `result`. Only the position variance is recorded, so each tick adds one degree of
+/// freedom whatever the state of the filter model.
+pub fn nees(result: &SimulateResult) -> NormalizedSquares {
+    NormalizedSquares::of(result.ticks.iter().map(|tick| {
+        (
+            tick.estimated_positions.1 - tick.true_positions.1,
+            tick.covariance,
+        )
+    }))
+}
+
+/// Log-likelihood of the measurements of `result` under the filter, `Σ -(ln 2πS + ν² / S) / 2`
+/// over the innovations `ν` and their predicted variances `S`. Each measurement is predicted
+/// before the filter sees it, so it needs no true position, and it peaks where `q` and `r`
+/// match the real noise.
+pub fn innovation_log_likelihood(result: &SimulateResult) -> f64 {
+    result
+        .ticks
+        .iter()
+        .filter_map(|tick| tick.innovation)
+        .map(|(innovation, variance)| {
+            -((2.0 * std::f64::consts::PI * variance).ln() + innovation * innovation / variance)
+                / 2.0
+        })
+        .sum()
+}
+
+/// Tests whether the innovations are consistent with the variance the filter predicted for
+/// them. Returns `None` when there are no measurements.
+pub fn nis_test(result: &SimulateResult, confidence: f64) -> Option<ConsistencyTest> {
+    nis(result).test(confidence)
+}
+
+/// Tests whether the estimate errors are consistent with the variance `p` the filter
+/// reports for them, which needs the true position and so only means something for
+/// simulated runs. Returns `None` for a run without ticks.
+pub fn nees_test(result: &SimulateResult, confidence: f64) -> Option<ConsistencyTest> {
+    nees(result).test(confidence)
+}
+
+/// Wilson-Hilferty approximation of the `p` quantile of the chi-square distribution with
+/// `dof` degrees of freedom, accurate to well under a percent from a handful of degrees on.
+fn chi_square_quantile(p: f64, dof: usize) -> f64 {
+    let k = dof as f64;
+    let spread = 2.0 / (9.0 * k);
+    (k * (1.0 - spread + normal_quantile(p) * spread.sqrt()).powi(3)).max(0.0)
+}
+
+/// Acklam's rational approximation of the standard normal quantile, for `0 < p < 1`.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Root mean square error of per-tick `positions` (such as smoothed estimates) against the
+/// true position.
+pub fn positions_rmse(result: &SimulateResult, positions: &[f64]) -> f64 {
+    rmse(
+        result
+            .ticks
+            .iter()
+            .zip(positions)
+            .map(|(tick, position)| position - tick.true_positions.1),
+    )
+}
+
+/// Normalized correlations of the innovations at lags `1..=max_lag` measurements, with
+/// earlier measurements or with themselves.
+pub struct Correlogram {
+    /// Correlation at lag `i + 1`
+    pub correlations: Vec<f64>,
+    /// Half-width of the 95% interval a white innovation sequence stays in, `1.96 / √n`
+    pub bound: f64,
+}
+
+impl Correlogram {
+    /// Lag and value of the correlation with the largest magnitude.
+    pub fn peak(&self) -> Option<(usize, f64)> {
+        self.correlations
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+            .map(|(i, &correlation)| (i + 1, correlation))
+    }
+
+    /// Whether every lag stays within the white-noise bound.
+    pub fn within_bound(&self) -> bool {
+        self.correlations
+            .iter()
+            .all(|correlation| correlation.abs() <= self.bound)
+    }
+}
+
+/// Cross-correlates the innovations with earlier measurements. An optimal filter has already
+/// used everything the past measurements say, so its innovations are uncorrelated with them.
+/// Returns `None` with fewer than `max_lag + 2` measurements.
+pub fn innovation_cross_correlation(
+    result: &SimulateResult,
+    max_lag: usize,
+) -> Option<Correlogram> {
+    let (innovations, measurements): (Vec<f64>, Vec<f64>) = result
+        .ticks
+        .iter()
+        .filter_map(|tick| {
+            let (innovation, _) = tick.innovation?;
+            let (_, measured) = tick.measured_positions?;
+            Some((innovation, measured))
+        })
+        .unzip();
+    let n = innovations.len();
+    if n < max_lag + 2 {
+        return None;
+    }
+
+    let innovations = centered(&innovations);
+    let measurements = centered(&measurements);
+    let norm = (innovations.iter().map(|value| value * value).sum::<f64>()
+        * measurements.iter().map(|value| value * value).sum::<f64>())
+    .sqrt();
+
+    let correlations = (1..=max_lag)
+        .map(|lag| {
+            let sum: f64 = innovations[lag..]
+                .iter()
+                .zip(&measurements)
+                .map(|(innovation, measured)| innovation * measured)
+                .sum();
+            if norm > 0.0 {
+                sum / norm
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    Some(Correlogram {
+        correlations,
+        bound: 1.96 / (n as f64).sqrt(),
+    })
+}
+
+/// `values` minus their mean.
+fn centered(values: &[f64]) -> Vec<f64> {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values.iter().map(|value| value - mean).collect()
+}
+
+/// Normalized innovations `innovation / √variance` of every measurement of `result`, which
+/// are standard normal and independent for a filter whose `q` and `r` match the real noise.
+pub fn normalized_innovations(result: &SimulateResult) -> Vec<f64> {
+    result
+        .ticks
+        .iter()
+        .filter_map(|tick| tick.innovation)
+        .map(|(innovation, variance)| innovation / variance.sqrt())
+        .collect()
+}
+
+/// Autocorrelation of `values` at lags `1..=max_lag`. Returns `None` with fewer than
+/// `max_lag + 2` values.
+pub fn autocorrelation(values: &[f64], max_lag: usize) -> Option<Correlogram> {
+    let n = values.len();
+    if n < max_lag + 2 {
+        return None;
+    }
+    let values = centered(values);
+    let norm: f64 = values.iter().map(|value| value * value).sum();
+    let correlations = (1..=max_lag)
+        .map(|lag| {
+            let sum: f64 = values[lag..]
+                .iter()
+                .zip(&values)
+                .map(|(value, earlier)| value * earlier)
+                .sum();
+            if norm > 0.0 {
+                sum / norm
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    Some(Correlogram {
+        correlations,
+        bound: 1.96 / (n as f64).sqrt(),
+    })
+}
+
+/// Ljung-Box test of whether the normalized innovations are white, over their
+/// autocorrelation.
+pub struct WhitenessTest {
+    pub autocorrelation: Correlogram,
+    /// Ljung-Box statistic `Q = n (n + 2) Σ ρₖ² / (n - k)` over the lags `k`
+    pub q: f64,
+    /// 95% quantile of the chi-square distribution with one degree of freedom per lag, which
+    /// the `q` of a white sequence stays below
+    pub threshold: f64,
+}
+
+impl WhitenessTest {
+    pub fn passed(&self) -> bool {
+        self.q <= self.threshold
+    }
+}
+
+/// Tests the normalized innovations of `result` for whiteness over lags `1..=max_lag`. A
+/// filter with too small a `q` lags behind the motion, and too large an `r` smooths too
+/// much, so consecutive innovations share a sign and correlate. Returns `None` with fewer
+/// than `max_lag + 2` measurements.
+pub fn whiteness_test(result: &SimulateResult, max_lag: usize) -> Option<WhitenessTest> {
+    ljung_box(&normalized_innovations(result), max_lag)
+}
+
+/// Ljung-Box test of `values` for whiteness over lags `1..=max_lag`. Returns `None` with
+/// fewer than `max_lag + 2` values.
+pub fn ljung_box(values: &[f64], max_lag: usize) -> Option<WhitenessTest> {
+    let n = values.len() as f64;
+    let autocorrelation = autocorrelation(values, max_lag)?;
+    let q = n
+        * (n + 2.0)
+        * autocorrelation
+            .correlations
+            .iter()
+            .enumerate()
+            .map(|(i, correlation)| correlation * correlation / (n - (i + 1) as f64))
+            .sum::<f64>();
+    Some(WhitenessTest {
+        autocorrelation,
+        q,
+        threshold: chi_square_quantile(0.95, max_lag),
+    })
+}
+
+/// Motion of the truth between two regime changes.
+#[derive(Debug, Clone, Copy)]
+pub enum Regime {
+    /// Moving at this velocity
+    Velocity(f64),
+    /// Accelerating at this rate
+    Acceleration(f64),
+}
+
+/// Tick at which the true motion switches to a new regime.
+#[derive(Debug, Clone, Copy)]
+pub struct RegimeChange {
+    pub time: f64,
+    pub regime: Regime,
+}
+
+/// Times at which the true velocity starts or stops changing, or jumps, together with the
+/// regime that follows. A jump shows up as two changes in the velocity slope on consecutive
+/// ticks, so changes within a tick of the previous one are merged into it.
+pub fn regime_changes(result: &SimulateResult) -> Vec<RegimeChange> {
+    let ticks = &result.ticks;
+    let slope = |i: usize| {
+        let dt = ticks[i].true_positions.0 - ticks[i - 1].true_positions.0;
+        (ticks[i].true_velocity - ticks[i - 1].true_velocity) / dt
+    };
+    // Velocity slopes that differ by less than this are the same regime
+    let tolerance = 1e-9
+        * ticks
+            .iter()
+            .map(|tick| tick.true_velocity.abs())
+            .fold(1.0, f64::max);
+    // The slope changes at tick `i - 1` when the segments on either side of it differ
+    let change_at = |i: usize| {
+        let acceleration = slope(i);
+        RegimeChange {
+            time: ticks[i - 1].true_positions.0,
+            regime: if acceleration.abs() <= tolerance {
+                Regime::Velocity(ticks[i - 1].true_velocity)
+            } else {
+                Regime::Acceleration(acceleration)
+            },
+        }
+    };
+
+    let mut changes: Vec<(usize, RegimeChange)> = Vec::new();
+    for i in 2..ticks.len() {
+        if (slope(i) - slope(i - 1)).abs() <= tolerance {
+            continue;
+        }
+        match changes.last_mut() {
+            Some((last, change)) if i - *last <= 1 => {
+                *last = i;
+                *change = change_at(i);
+            }
+            _ => changes.push((i, change_at(i))),
+        }
+    }
+    changes.into_iter().map(|(_, change)| change).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::FilterModel;
+    use crate::sim::tests::{config, run};
+    use crate::sim::SimulateConfig;
+    use crate::trajectory::Trajectory;
+
+    /// A random walk tracked by the constant-velocity model it was drawn from, with `r` as
+    /// the filter's measurement variance where the sensor's is 4.
+    fn random_walk(r: f64) -> SimulateConfig {
+        SimulateConfig {
+            total_time: 60.0,
+            trajectory: Trajectory::RandomWalk { density: 0.5 },
+            model: FilterModel::ConstantVelocity,
+            q: 0.5,
+            r,
+            ..config()
+        }
+    }
+
+    /// Chi-square test of `squares` pooled over 50 seeded runs of `config`.
+    fn monte_carlo_test(
+        config: &SimulateConfig,
+        squares: impl Fn(&SimulateResult) -> NormalizedSquares,
+    ) -> ConsistencyTest {
+        (1..=50)
+            .map(|seed| squares(&run(config, seed)))
+            .fold(NormalizedSquares::default(), NormalizedSquares::pool)
+            .test(0.95)
+            .unwrap()
+    }
+
+    #[test]
+    fn passthrough_rmse_is_the_measurement_rmse() {
+        let mut result = run(&config(), 1);
+        let measurement = measurement_rmse(&result);
+        assert!(estimate_rmse(&result) < measurement);
+
+        // The passthrough line joins the measurements, so it errs exactly as they do
+        for tick in &mut result.ticks {
+            tick.estimated_positions = tick.measured_positions.unwrap();
+        }
+        assert_eq!(estimate_rmse(&result), measurement);
+
+        // A filter that trusts the sensor completely passes its measurements through too
+        let trusting = run(
+            &SimulateConfig {
+                r: 1e-12,
+                ..config()
+            },
+            1,
+        );
+        assert!((estimate_rmse(&trusting) - measurement_rmse(&trusting)).abs() < 1e-6);
+        assert_eq!(measurement_rmse(&trusting), measurement);
+    }
+
+    #[test]
+    fn nis_test_rejects_a_too_small_r() {
+        let matched = nis_test(&run(&config(), 1), 0.95).unwrap();
+        assert_eq!(matched.squares.dof, 100);
+        assert!(matched.passed(), "{:?}", matched);
+
+        let overconfident = SimulateConfig { r: 0.4, ..config() };
+        let test = nis_test(&run(&overconfident, 1), 0.95).unwrap();
+        assert!(!test.passed());
+        assert!(test.squares.sum > test.upper, "{:?}", test);
+    }
+
+    #[test]
+    fn matched_innovations_are_uncorrelated_with_past_measurements() {
+        let matched = SimulateConfig {
+            total_time: 60.0,
+            ..config()
+        };
+        let correlogram = innovation_cross_correlation(&run(&matched, 1), 10).unwrap();
+        assert_eq!(correlogram.correlations.len(), 10);
+        assert!(correlogram.within_bound(), "{:?}", correlogram.peak());
+        assert!(correlogram.peak().unwrap().1.abs() < 0.1);
+    }
+
+    #[test]
+    fn warmup_excludes_the_transient_of_a_wrong_guess() {
+        let result = run(
+            &SimulateConfig {
+                initial_position_offset: 20.0,
+                ..config()
+            },
+            1,
+        );
+        let overall = estimate_rmse(&result);
+        let steady = estimate_rmse(&result.since(3.0));
+        assert!(steady < overall / 2.0, "{} vs {}", steady, overall);
+        assert_eq!(result.since(3.0).ticks.len(), 70);
+    }
+
+    #[test]
+    fn single_run_tests_tell_over_from_underconfidence() {
+        let matched = nis_test(&run(&random_walk(4.0), 1), 0.95).unwrap();
+        assert!(matched.passed(), "{:?}", matched);
+
+        let overconfident = run(&random_walk(0.4), 1);
+        for test in [
+            nees_test(&overconfident, 0.95),
+            nis_test(&overconfident, 0.95),
+        ] {
+            let test = test.unwrap();
+            assert!(test.squares.sum > test.upper, "{:?}", test);
+        }
+        let underconfident = run(&random_walk(40.0), 1);
+        for test in [
+            nees_test(&underconfident, 0.95),
+            nis_test(&underconfident, 0.95),
+        ] {
+            let test = test.unwrap();
+            assert!(test.squares.sum < test.lower, "{:?}", test);
+        }
+    }
+
+    #[test]
+    fn pooled_tests_pass_the_matched_filter_and_reject_a_mild_mismatch() {
+        // The errors of one run are correlated from tick to tick, so its NEES alone strays out
+        // of the interval too often to pass a matched filter; pooling many runs doesn't. The
+        // first ten seconds are left out, where the truth starting on the initial guess holds
+        // the NEES low.
+        let settled_nees = |result: &SimulateResult| nees(&result.since(10.0));
+        for test in [
+            monte_carlo_test(&random_walk(4.0), settled_nees),
+            monte_carlo_test(&random_walk(4.0), nis),
+        ] {
+            assert!(test.passed(), "{:?}", test);
+        }
+
+        for test in [
+            monte_carlo_test(&random_walk(3.0), settled_nees),
+            monte_carlo_test(&random_walk(3.0), nis),
+        ] {
+            assert!(test.squares.sum > test.upper, "{:?}", test);
+        }
+        for test in [
+            monte_carlo_test(&random_walk(5.0), settled_nees),
+            monte_carlo_test(&random_walk(5.0), nis),
+        ] {
+            assert!(test.squares.sum < test.lower, "{:?}", test);
+        }
+    }
+
+    #[test]
+    fn every_linear_model_settles_on_its_steady_state_gain() {
+        for model in [
+            FilterModel::KnownVelocity,
+            FilterModel::ConstantVelocity,
+            FilterModel::ConstantAcceleration,
+            FilterModel::Extended,
+            FilterModel::Unscented,
+        ] {
+            let config = SimulateConfig {
+                total_time: 60.0,
+                q: 0.5,
+                model,
+                ..config()
+            };
+            let gain = steady_state_gain(&config).unwrap();
+            let result = run(&config, 1);
+            let last = result.ticks.last().unwrap().gain;
+            assert!(
+                (last - gain).abs() < 1e-6,
+                "{:?}: {} vs {}",
+                model,
+                last,
+                gain
+            );
+            assert!(convergence_time(&result, gain, CONVERGENCE_TOLERANCE).unwrap() < 30.0);
+        }
+        let known_velocity = config();
+        assert_eq!(
+            steady_state_gain(&known_velocity),
+            Some(scalar_steady_state_gain(4.0, 0.01, 1))
+        );
+    }
+
+    #[test]
+    fn gains_that_follow_the_measurements_have_no_steady_state() {
+        let particle = SimulateConfig {
+            model: FilterModel::Particle,
+            ..config()
+        };
+        assert_eq!(steady_state_gain(&particle), None);
+        for sensor in [
+            SensorModel::Multiplicative {
+                relative_stddev: 0.1,
+            },
+            SensorModel::Range {
+                station: 0.0,
+                offset: 5.0,
+            },
+        ] {
+            let config = SimulateConfig {
+                sensor,
+                model: FilterModel::Extended,
+                ..config()
+            };
+            assert_eq!(steady_state_gain(&config), None);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde::Serialize;
+
+use kalman_filter_simulator::metrics::{self, ConsistencyTest, NormalizedSquares};
+use kalman_filter_simulator::sim::{simulate_model, SimulateConfig, SimulateError};
+
+/// Errors of one run of a Monte Carlo study.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MonteCarloRun {
+    pub seed: u64,
+    pub estimate_rmse: f64,
+    pub measurement_rmse: f64,
+    pub nees: NormalizedSquares,
+    pub nis: NormalizedSquares,
+}
+
+/// Mean and sample standard deviation of one error over the runs.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Spread {
+    pub mean: f64,
+    /// 0 for a single run
+    pub std: f64,
+}
+
+impl Spread {
+    fn of(values: impl ExactSizeIterator<Item = f64> + Clone) -> Self {
+        let n = values.len() as f64;
+        let mean = values.clone().sum::<f64>() / n;
+        let std = if n > 1.0 {
+            (values.map(|value| (value - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+        Spread { mean, std }
+    }
+}
+
+/// RMSEs of many independent runs of the same scenario, and their spread over the runs.
+#[derive(Serialize)]
+pub struct MonteCarloSummary {
+    pub runs: usize,
+    pub estimate_rmse: Spread,
+    pub measurement_rmse: Spread,
+    /// Consistency test over the NEES of every tick of every run, pooled. Where a single run
+    /// may pass by chance, thousands of degrees of freedom narrow the interval to a few
+    /// percent of its center, so even a slightly over- or underconfident filter fails.
+    pub nees_test: Option<ConsistencyTest>,
+    /// Consistency test over the NIS of every measurement of every run, pooled
+    pub nis_test: Option<ConsistencyTest>,
+    /// Every run, in run order
+    #[serde(rename = "per_run")]
+    pub run_errors: Vec<MonteCarloRun>,
+}
+
+/// Seed of the run at `index`, so every run draws different noise but the whole study is
+/// reproducible from `base_seed`.
+///
+/// This is output `index` of a SplitMix64 generator seeded with `base_seed`. Hashing keeps the
+/// runs of nearby base seeds apart, where `base_seed + index` would make run 1 of seed 0 the
+/// same as run 0 of seed 1.
+pub fn derive_seed(base_seed: u64, index: usize) -> u64 {
+    let mut z = base_seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Simulates `config` `runs` times on up to `threads` threads (all cores for 0), testing the
+/// consistency of the filter at `confidence`.
+///
+/// Each run seeds its own generator from `base_seed` and the statistics are taken in run
+/// order, so the result doesn't depend on the thread count.
+pub fn run_monte_carlo(
+    config: &SimulateConfig,
+    runs: usize,
+    base_seed: u64,
+    threads: usize,
+    confidence: f64,
+) -> Result<MonteCarloSummary, Box<dyn Error>> {
+    let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+    let run_errors = pool.install(|| {
+        (0..runs)
+            .into_par_iter()
+            .map(|index| {
+                let seed = derive_seed(base_seed, index);
+                let mut rng = StdRng::seed_from_u64(seed);
+                let result = simulate_model(config, &mut rng)?;
+                Ok(MonteCarloRun {
+                    seed,
+                    estimate_rmse: metrics::estimate_rmse(&result),
+                    measurement_rmse: metrics::measurement_rmse(&result),
+                    nees: metrics::nees(&result),
+                    nis: metrics::nis(&result),
+                })
+            })
+            .collect::<Result<Vec<_>, SimulateError>>()
+    })?;
+
+    Ok(MonteCarloSummary {
+        runs,
+        estimate_rmse: Spread::of(run_errors.iter().map(|run| run.estimate_rmse)),
+        measurement_rmse: Spread::of(run_errors.iter().map(|run| run.measurement_rmse)),
+        nees_test: pooled(&run_errors, |run| run.nees).test(confidence),
+        nis_test: pooled(&run_errors, |run| run.nis).test(confidence),
+        run_errors,
+    })
+}
+
+/// Normalized squares of every run pooled together.
+fn pooled(
+    runs: &[MonteCarloRun],
+    squares: impl Fn(&MonteCarloRun) -> NormalizedSquares,
+) -> NormalizedSquares {
+    runs.iter()
+        .map(squares)
+        .fold(NormalizedSquares::default(), NormalizedSquares::pool)
+}
+
+/// Study of `config` from `seed`, as written by `write_monte_carlo_json`.
+#[derive(Serialize)]
+struct MonteCarloExport<'a> {
+    config: &'a SimulateConfig,
+    seed: u64,
+    #[serde(flatten)]
+    summary: &'a MonteCarloSummary,
+}
+
+/// Writes `summary`, a study of `config` from the base seed `seed`, as pretty-printed JSON.
+pub fn write_monte_carlo_json(
+    path: &Path,
+    config: &SimulateConfig,
+    seed: u64,
+    summary: &MonteCarloSummary,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let export = MonteCarloExport {
+        config,
+        seed,
+        summary,
+    };
+    serde_json::to_writer_pretty(&mut writer, &export)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::default_config;
+
+    #[test]
+    fn thread_count_does_not_change_the_result() {
+        let config = SimulateConfig {
+            total_time: 5.0,
+            ..default_config()
+        };
+        let one = run_monte_carlo(&config, 40, 7, 1, 0.95).unwrap();
+        let four = run_monte_carlo(&config, 40, 7, 4, 0.95).unwrap();
+        assert_eq!(
+            one.estimate_rmse.mean.to_bits(),
+            four.estimate_rmse.mean.to_bits()
+        );
+        assert_eq!(
+            one.estimate_rmse.std.to_bits(),
+            four.estimate_rmse.std.to_bits()
+        );
+        assert_eq!(
+            one.measurement_rmse.mean.to_bits(),
+            four.measurement_rmse.mean.to_bits()
+        );
+        let seeds = |summary: &MonteCarloSummary| -> Vec<u64> {
+            summary.run_errors.iter().map(|run| run.seed).collect()
+        };
+        assert_eq!(seeds(&one), seeds(&four));
+
+        let other = run_monte_carlo(&config, 40, 8, 4, 0.95).unwrap();
+        assert_ne!(other.estimate_rmse.mean, one.estimate_rmse.mean);
+    }
+}
@@ -0,0 +1,102 @@
+use rand::{Rng, RngCore};
+use rand_distr::{Exp1, StandardNormal, StudentT};
+use serde::{Deserialize, Serialize};
+
+/// Distribution of zero-mean sensor noise, scaled to any standard deviation so the filter's
+/// `r = stddev²` is the real measurement variance whatever its shape.
+pub trait NoiseModel {
+    /// Draws one noise value with standard deviation `stddev`.
+    fn sample(&self, stddev: f64, rng: &mut dyn RngCore) -> f64;
+}
+
+/// Shape of the sensor noise, as selected per scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum SensorNoise {
+    /// Normal distribution, which the Kalman filter is optimal for.
+    Gaussian,
+    /// Flat between `±√3 stddev`, with no tails at all.
+    Uniform,
+    /// Double exponential: sharper peak and heavier tails than the normal distribution.
+    Laplace,
+    /// Student's t with `degrees_of_freedom` above 2, whose tails grow heavier as it
+    /// approaches 2 and which tends to the normal distribution as it grows.
+    StudentT { degrees_of_freedom: f64 },
+}
+
+impl SensorNoise {
+    /// Whether the noise has a finite variance to scale, which Student's t needs more than 2
+    /// degrees of freedom for. Sampling noise that isn't valid panics.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            SensorNoise::StudentT { degrees_of_freedom } => {
+                degrees_of_freedom.is_finite() && degrees_of_freedom > 2.0
+            }
+            _ => true,
+        }
+    }
+}
+
+impl NoiseModel for SensorNoise {
+    fn sample(&self, stddev: f64, rng: &mut dyn RngCore) -> f64 {
+        let unit = match *self {
+            SensorNoise::Gaussian => rng.sample(StandardNormal),
+            SensorNoise::Uniform => (rng.gen::<f64>() * 2.0 - 1.0) * 3.0_f64.sqrt(),
+            // The difference of two unit exponentials is Laplace with variance 2
+            SensorNoise::Laplace => {
+                (rng.sample::<f64, _>(Exp1) - rng.sample::<f64, _>(Exp1)) / 2.0_f64.sqrt()
+            }
+            SensorNoise::StudentT { degrees_of_freedom } => {
+                let t: f64 = rng.sample(StudentT::new(degrees_of_freedom).unwrap());
+                t * ((degrees_of_freedom - 2.0) / degrees_of_freedom).sqrt()
+            }
+        };
+        unit * stddev
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn every_shape_has_the_requested_variance() {
+        let samples = 200_000;
+        for noise in [
+            SensorNoise::Gaussian,
+            SensorNoise::Uniform,
+            SensorNoise::Laplace,
+            // Far enough above 4 degrees of freedom for the sample variance to settle
+            SensorNoise::StudentT {
+                degrees_of_freedom: 10.0,
+            },
+        ] {
+            let mut rng = StdRng::seed_from_u64(1);
+            let draws: Vec<f64> = (0..samples).map(|_| noise.sample(2.0, &mut rng)).collect();
+            let mean = draws.iter().sum::<f64>() / samples as f64;
+            let variance =
+                draws.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples - 1) as f64;
+            assert!(mean.abs() < 0.02, "{:?}: mean {}", noise, mean);
+            assert!(
+                (variance - 4.0).abs() < 0.1,
+                "{:?}: variance {}",
+                noise,
+                variance
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_noise_stays_within_its_bounds() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let bound = 3.0_f64.sqrt() * 2.0;
+        assert!((0..10_000).all(|_| SensorNoise::Uniform.sample(2.0, &mut rng).abs() <= bound));
+        assert!(!SensorNoise::StudentT {
+            degrees_of_freedom: 2.0
+        }
+        .is_valid());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::dynamics::{ConstantAcceleration, ConstantVelocity, FilterModel, ModelFilter};
+use crate::ekf::ExtendedKalmanFilter;
+use crate::filter::{Filter, KalmanFilter};
+use crate::particle::ParticleFilter;
+use crate::sim::{filter_tick, SimulateConfig, SimulateError};
+use crate::ukf::UnscentedKalmanFilter;
+
+/// The filter model a `SimulateConfig` selects, fed one timestamped measurement at a time as
+/// it arrives, for live estimation rather than a recorded run.
+pub struct OnlineFilter {
+    config: SimulateConfig,
+    filter: Box<dyn Filter>,
+    previous_time: Option<f64>,
+    /// Number of measurement times pushed so far
+    steps: usize,
+}
+
+impl OnlineFilter {
+    /// Starts the filter model of `config` from its initial guess.
+    pub fn from_config(config: &SimulateConfig) -> Result<Self, SimulateError> {
+        config.validate()?;
+        let filter: Box<dyn Filter> = match config.model {
+            FilterModel::KnownVelocity => Box::new(KalmanFilter::from_config(config)),
+            FilterModel::ConstantVelocity => {
+                Box::new(ModelFilter::from_config(ConstantVelocity, config))
+            }
+            FilterModel::ConstantAcceleration => {
+                Box::new(ModelFilter::from_config(ConstantAcceleration, config))
+            }
+            FilterModel::Extended => Box::new(ExtendedKalmanFilter::from_config(config)),
+            FilterModel::Unscented => Box::new(UnscentedKalmanFilter::from_config(config)),
+            FilterModel::Particle => Box::new(ParticleFilter::from_config(config)),
+        };
+        Ok(OnlineFilter {
+            config: *config,
+            filter,
+            previous_time: None,
+            steps: 0,
+        })
+    }
+
+    /// Advances the filter to `time`, like a tick of `simulate`, and corrects it with
+    /// `measurement` when there is one. The first call comes a nominal `dt` after the start.
+    ///
+    /// Returns `false`, leaving the filter as it was, for a `time` before the previous one.
+    /// Like `simulate`, fails with `SimulateError::NonFinite` once the estimate becomes NaN
+    /// or infinite, after which the filter can't recover.
+    pub fn push(&mut self, time: f64, measurement: Option<f64>) -> Result<bool, SimulateError> {
+        let previous_time = self.previous_time.unwrap_or(time - self.config.dt);
+        if time < previous_time {
+            return Ok(false);
+        }
+        filter_tick(
+            &mut *self.filter,
+            &self.config,
+            time - previous_time,
+            measurement,
+        );
+        self.previous_time = Some(time);
+        let step = self.steps;
+        self.steps += 1;
+        match self.non_finite_quantity() {
+            Some(quantity) => Err(SimulateError::NonFinite { step, quantity }),
+            None => Ok(true),
+        }
+    }
+
+    /// Name of the first estimated quantity that is NaN or infinite, if any.
+    fn non_finite_quantity(&self) -> Option<&'static str> {
+        let filter = self.filter();
+        [
+            ("estimated position", filter.position()),
+            ("estimated velocity", filter.velocity()),
+            ("covariance", filter.uncertainty()),
+            ("gain", filter.gain()),
+        ]
+        .into_iter()
+        .find(|(_, value)| !value.is_finite())
+        .map(|(name, _)| name)
+    }
+
+    /// The filter, to read its latest estimate off.
+    pub fn filter(&self) -> &dyn Filter {
+        &*self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::config;
+
+    #[test]
+    fn pushed_measurements_step_the_filter_in_time_order() {
+        let mut online = OnlineFilter::from_config(&config()).unwrap();
+        let mut offline = KalmanFilter::from_config(&config());
+        for (time, measurement) in [(0.1, Some(0.3)), (0.2, None), (0.35, Some(0.2))] {
+            assert_eq!(online.push(time, measurement), Ok(true));
+        }
+        offline.step(0.1, 0.3);
+        offline.predict(0.1);
+        offline.step(0.35 - 0.2, 0.2);
+        assert!((online.filter().position() - offline.position()).abs() < 1e-12);
+        assert!((online.filter().uncertainty() - offline.uncertainty()).abs() < 1e-12);
+
+        let position = online.filter().position();
+        assert_eq!(online.push(0.3, Some(5.0)), Ok(false));
+        assert_eq!(online.filter().position(), position);
+    }
+
+    #[test]
+    fn a_nan_estimate_is_reported() {
+        let mut online = OnlineFilter::from_config(&config()).unwrap();
+        assert_eq!(online.push(0.1, Some(0.3)), Ok(true));
+        assert_eq!(
+            online.push(0.2, Some(f64::NAN)),
+            Err(SimulateError::NonFinite {
+                step: 1,
+                quantity: "estimated position"
+            })
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use image::codecs::gif::GifEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{Delay, ExtendedColorType, Frame};
+
+use crate::quantize::QuantizedGifEncoder;
+use crate::video::{encode_video, VideoFormat};
+
+/// Container the animation is written in.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Animated GIF
+    Gif,
+    /// Animated PNG, with full color and alpha
+    Apng,
+    /// Lossless animated WebP, with full color and alpha
+    Webp,
+    /// H.264 video, encoded by ffmpeg
+    Mp4,
+    /// VP9 video, encoded by ffmpeg
+    Webm,
+}
+
+impl OutputFormat {
+    /// File extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Gif => "gif",
+            OutputFormat::Apng => "apng",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Mp4 => "mp4",
+            OutputFormat::Webm => "webm",
+        }
+    }
+}
+
+/// Writes `animation` to `path` in `format` at `fps` frames per second, with the GIF palette
+/// capped at `gif_colors` colors when given.
+pub fn write_animation(
+    animation: Vec<Frame>,
+    format: OutputFormat,
+    gif_colors: Option<u16>,
+    fps: u16,
+    path: &Path,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Gif => {
+            fs::write(path, encode_gif(animation, gif_colors, fps, show_progress)?)?
+        }
+        OutputFormat::Apng => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            encode_apng(animation, fps, &mut writer, show_progress)?;
+            writer.flush()?;
+        }
+        OutputFormat::Webp => fs::write(path, encode_webp(animation, fps, show_progress)?)?,
+        OutputFormat::Mp4 => encode_video(animation, VideoFormat::Mp4, fps, path, show_progress)?,
+        OutputFormat::Webm => encode_video(animation, VideoFormat::Webm, fps, path, show_progress)?,
+    }
+    Ok(())
+}
+
+/// GIF encoder of a run: `image`'s own, or one with a capped palette for `--gif-colors`.
+enum GifWriter<W: Write> {
+    Full(GifEncoder<W>),
+    Quantized(QuantizedGifEncoder<W>),
+}
+
+impl<W: Write> GifWriter<W> {
+    fn encode_frame(&mut self, frame: Frame) -> Result<(), Box<dyn Error>> {
+        match self {
+            GifWriter::Full(encoder) => encoder.encode_frame(frame)?,
+            GifWriter::Quantized(encoder) => encoder.encode_frame(frame)?,
+        }
+        Ok(())
+    }
+}
+
+/// Encodes `animation` as a GIF in memory at `fps` frames per second, quantized to at most
+/// `colors` colors when given.
+fn encode_gif(
+    animation: Vec<Frame>,
+    colors: Option<u16>,
+    fps: u16,
+    show_progress: bool,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    let mut encoder = match colors {
+        None => GifWriter::Full(GifEncoder::new(&mut bytes)),
+        Some(colors) => {
+            let (width, height) = animation
+                .first()
+                .map_or((0, 0), |frame| frame.buffer().dimensions());
+            GifWriter::Quantized(QuantizedGifEncoder::new(
+                &mut bytes,
+                width as u16,
+                height as u16,
+                colors as usize,
+            )?)
+        }
+    };
+    let delay = Delay::from_numer_denom_ms(1000, fps.into());
+    let frame_count = animation.len();
+    for (i, frame) in animation.into_iter().enumerate() {
+        encoder.encode_frame(Frame::from_parts(frame.into_buffer(), 0, 0, delay))?;
+        if show_progress && i % 10 == 9 {
+            eprintln!("{}/{} frames encoded", i + 1, frame_count);
+        }
+    }
+    // Dropping the encoder writes the GIF trailer
+    drop(encoder);
+    Ok(bytes)
+}
+
+/// Encodes `animation` as an APNG at `fps` frames per second that loops forever.
+fn encode_apng(
+    animation: Vec<Frame>,
+    fps: u16,
+    writer: impl Write,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = animation
+        .first()
+        .ok_or("no frame to encode")?
+        .buffer()
+        .dimensions();
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(animation.len() as u32, 0)?;
+    encoder.set_frame_delay(1, fps)?;
+    let mut writer = encoder.write_header()?;
+    let frame_count = animation.len();
+    for (i, frame) in animation.iter().enumerate() {
+        writer.write_image_data(frame.buffer().as_raw())?;
+        if show_progress && i % 10 == 9 {
+            eprintln!("{}/{} frames encoded", i + 1, frame_count);
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Encodes `animation` as a lossless animated WebP at `fps` frames per second that loops
+/// forever.
+///
+/// `image` only writes still WebP images, so each frame is encoded as one, and its `VP8L`
+/// bitstream chunk is wrapped in an `ANMF` frame chunk of the animation.
+fn encode_webp(
+    animation: Vec<Frame>,
+    fps: u16,
+    show_progress: bool,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (width, height) = animation
+        .first()
+        .ok_or("no frame to encode")?
+        .buffer()
+        .dimensions();
+    let mut chunks = Vec::new();
+
+    // Alpha and animation flags, reserved bytes and the canvas size
+    let mut header = vec![0x10 | 0x02, 0, 0, 0];
+    header.extend(u24(width - 1));
+    header.extend(u24(height - 1));
+    push_chunk(&mut chunks, b"VP8X", &header);
+    // White background in BGRA, and a loop count of 0 for forever
+    push_chunk(&mut chunks, b"ANIM", &[0xff, 0xff, 0xff, 0xff, 0, 0]);
+
+    // Frame durations are in whole milliseconds
+    let duration = (1000.0 / fps as f64).round() as u32;
+    let frame_count = animation.len();
+    for (i, frame) in animation.iter().enumerate() {
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still).encode(
+            frame.buffer().as_raw(),
+            width,
+            height,
+            ExtendedColorType::Rgba8,
+        )?;
+        // Offset, size and duration of the frame, then a flag to replace the previous frame
+        // rather than blend with it
+        let mut payload = Vec::new();
+        payload.extend(u24(0));
+        payload.extend(u24(0));
+        payload.extend(u24(width - 1));
+        payload.extend(u24(height - 1));
+        payload.extend(u24(duration));
+        payload.push(0x02);
+        // The chunks of a still image without metadata follow its 12-byte `RIFF` header
+        payload.extend_from_slice(&still[12..]);
+        push_chunk(&mut chunks, b"ANMF", &payload);
+        if show_progress && i % 10 == 9 {
+            eprintln!("{}/{} frames encoded", i + 1, frame_count);
+        }
+    }
+
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend((chunks.len() as u32 + 4).to_le_bytes());
+    bytes.extend(b"WEBP");
+    bytes.extend(chunks);
+    Ok(bytes)
+}
+
+/// Appends a RIFF chunk of `payload` tagged `fourcc` to `bytes`, padded to an even length.
+fn push_chunk(bytes: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    bytes.extend(fourcc);
+    bytes.extend((payload.len() as u32).to_le_bytes());
+    bytes.extend(payload);
+    if payload.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+/// Little-endian 24-bit field of a WebP chunk.
+fn u24(value: u32) -> [u8; 3] {
+    let [a, b, c, _] = value.to_le_bytes();
+    [a, b, c]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::webp::WebPDecoder;
+    use image::{AnimationDecoder, Rgba, RgbaImage};
+
+    use super::*;
+
+    /// `count` frames of 32 by 24 pixels, each a different gradient.
+    fn frames(count: usize) -> Vec<Frame> {
+        (0..count)
+            .map(|i| {
+                Frame::new(RgbaImage::from_fn(32, 24, |x, y| {
+                    Rgba([(x * 8) as u8, (y * 10) as u8, (i * 40) as u8, 255])
+                }))
+            })
+            .collect()
+    }
+
+    /// Number and size of the frames of the GIF `bytes`.
+    fn decode_gif(bytes: &[u8]) -> (usize, (u32, u32)) {
+        let frames = GifDecoder::new(Cursor::new(bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        (frames.len(), frames[0].buffer().dimensions())
+    }
+
+    #[test]
+    fn encodes_a_gif_with_every_frame() {
+        for colors in [None, Some(16)] {
+            let bytes = encode_gif(frames(5), colors, 10, false).unwrap();
+            assert_eq!(decode_gif(&bytes), (5, (32, 24)), "{:?} colors", colors);
+        }
+    }
+
+    #[test]
+    fn smaller_palettes_give_smaller_gifs() {
+        let sizes: Vec<usize> = [256, 32, 4]
+            .into_iter()
+            .map(|colors| {
+                encode_gif(frames(5), Some(colors), 10, false)
+                    .unwrap()
+                    .len()
+            })
+            .collect();
+        assert!(
+            sizes.windows(2).all(|pair| pair[1] < pair[0]),
+            "{:?}",
+            sizes
+        );
+    }
+
+    #[test]
+    fn in_memory_animations_decode_to_the_frame_size() {
+        let webp = encode_webp(frames(3), 10, false).unwrap();
+        let decoded = WebPDecoder::new(Cursor::new(&webp))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0].buffer().dimensions(), (32, 24));
+        assert_eq!(decoded[2].buffer(), frames(3)[2].buffer());
+
+        let mut apng = Vec::new();
+        encode_apng(frames(3), 10, &mut apng, false).unwrap();
+        let reader = png::Decoder::new(Cursor::new(&apng)).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (32, 24));
+        assert_eq!(info.animation_control.unwrap().num_frames, 3);
+
+        let still = image::load_from_memory(&apng).unwrap();
+        assert_eq!((still.width(), still.height()), (32, 24));
+    }
+}
@@ -0,0 +1,377 @@
+use clap::ValueEnum;
+use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+use crate::dynamics::{ConstantVelocity, Dynamics};
+use crate::filter::{Filter, State};
+use crate::sensor::SensorModel;
+use crate::sim::{replay_with, SimulateConfig, SimulateResult};
+
+/// Fraction of the particle count below which the effective sample size triggers resampling.
+const RESAMPLE_THRESHOLD: f64 = 0.5;
+
+/// Seed of every `ParticleFilter`'s own draws, so replaying the same measurements reproduces
+/// the same estimate.
+const SEED: u64 = 0;
+
+/// How a `ParticleFilter` draws its new, equally weighted particles from the weighted ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Resampling {
+    /// An independent draw for each particle
+    Multinomial,
+    /// One draw from each of `N` equal slices of the cumulative weights
+    Stratified,
+    /// A single draw, repeated at even spacing through the cumulative weights
+    Systematic,
+    /// `⌊N w⌋` copies of each particle, then multinomial draws on what is left of the weights
+    Residual,
+}
+
+impl Resampling {
+    /// Indices of the particles to keep, `weights.len()` of them with repeats, for weights
+    /// that sum to 1.
+    fn resample(self, weights: &[f64], rng: &mut impl Rng) -> Vec<usize> {
+        let count = weights.len();
+        match self {
+            Resampling::Multinomial => {
+                let mut draws: Vec<f64> = (0..count).map(|_| rng.gen()).collect();
+                draws.sort_by(f64::total_cmp);
+                select(weights, draws)
+            }
+            Resampling::Stratified => {
+                let draws = (0..count).map(|i| (i as f64 + rng.gen::<f64>()) / count as f64);
+                select(weights, draws)
+            }
+            Resampling::Systematic => {
+                let offset: f64 = rng.gen();
+                select(
+                    weights,
+                    (0..count).map(|i| (i as f64 + offset) / count as f64),
+                )
+            }
+            Resampling::Residual => {
+                let copies: Vec<usize> = weights
+                    .iter()
+                    .map(|weight| (weight * count as f64).floor() as usize)
+                    .collect();
+                let mut indices: Vec<usize> = copies
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, &copies)| std::iter::repeat_n(i, copies))
+                    .collect();
+                let remaining = count - indices.len();
+                if remaining > 0 {
+                    let residuals: Vec<f64> = weights
+                        .iter()
+                        .zip(&copies)
+                        .map(|(weight, &copies)| weight * count as f64 - copies as f64)
+                        .collect();
+                    let total: f64 = residuals.iter().sum();
+                    let residuals: Vec<f64> = residuals.iter().map(|r| r / total).collect();
+                    let mut draws: Vec<f64> = (0..remaining).map(|_| rng.gen()).collect();
+                    draws.sort_by(f64::total_cmp);
+                    indices.extend(select(&residuals, draws));
+                }
+                indices
+            }
+        }
+    }
+}
+
+/// Index of the particle whose slice of the cumulative `weights` holds each of the ascending
+/// `draws` in `[0, 1)`.
+fn select(weights: &[f64], draws: impl IntoIterator<Item = f64>) -> Vec<usize> {
+    let mut indices = Vec::with_capacity(weights.len());
+    let mut i = 0;
+    let mut cumulative = weights[0];
+    for draw in draws {
+        // Round-off can leave the last cumulative weight just below 1
+        while draw >= cumulative && i + 1 < weights.len() {
+            i += 1;
+            cumulative += weights[i];
+        }
+        indices.push(i);
+    }
+    indices
+}
+
+/// Particle count and resampling of the `particle` model.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParticleParameters {
+    pub particles: usize,
+    pub resampling: Resampling,
+}
+
+impl Default for ParticleParameters {
+    fn default() -> Self {
+        ParticleParameters {
+            particles: 1000,
+            resampling: Resampling::Systematic,
+        }
+    }
+}
+
+/// Sequential importance resampling filter: a cloud of weighted position and velocity samples
+/// moved at constant velocity plus white-noise acceleration, and reweighted by how likely
+/// each makes every measurement. It makes no Gaussian assumption, so it can follow the
+/// two-sided posterior of the range sensor.
+///
+/// Whenever the effective sample size falls below half the particle count, the cloud is
+/// resampled into equally weighted particles. The random draws come from the filter's own
+/// generator with a fixed seed.
+#[derive(Debug, Clone)]
+pub struct ParticleFilter {
+    sensor: SensorModel,
+    resampling: Resampling,
+    particles: Vec<State>,
+    /// Normalized weight of each particle
+    weights: Vec<f64>,
+    q: f64,
+    r: f64,
+    rng: StdRng,
+    gain: f64,
+    innovation: (f64, f64),
+}
+
+impl ParticleFilter {
+    /// Draws the initial particles around the initial guess described by `config`, with unit
+    /// variance on the position and velocity like the other models.
+    pub fn from_config(config: &SimulateConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let count = config.particle.particles;
+        let particles = (0..count)
+            .map(|_| State {
+                x: config.initial_position
+                    + config.initial_position_offset
+                    + rng.sample::<f64, _>(StandardNormal),
+                v: config.velocity
+                    + config.initial_velocity_offset
+                    + rng.sample::<f64, _>(StandardNormal),
+            })
+            .collect();
+        ParticleFilter {
+            sensor: config.sensor,
+            resampling: config.particle.resampling,
+            particles,
+            weights: vec![1.0 / count as f64; count],
+            q: config.q,
+            r: config.r,
+            rng,
+            gain: 0.0,
+            innovation: (0.0, 0.0),
+        }
+    }
+
+    /// Current position of every particle.
+    pub fn positions(&self) -> impl Iterator<Item = f64> + '_ {
+        self.particles.iter().map(|particle| particle.x)
+    }
+
+    /// Weighted mean of `value` over the particles.
+    fn mean(&self, value: impl Fn(&State) -> f64) -> f64 {
+        self.particles
+            .iter()
+            .zip(&self.weights)
+            .map(|(particle, weight)| value(particle) * weight)
+            .sum()
+    }
+}
+
+impl Filter for ParticleFilter {
+    fn predict(&mut self, dt: f64) {
+        // Without process noise, Q has no Cholesky factor and the particles move deterministically
+        let noise = ConstantVelocity
+            .process_noise(self.q, dt)
+            .cholesky()
+            .map(|cholesky| cholesky.l());
+        for particle in &mut self.particles {
+            particle.x += particle.v * dt;
+            if let Some(l) = noise {
+                let w = l * Vector2::new(
+                    self.rng.sample(StandardNormal),
+                    self.rng.sample(StandardNormal),
+                );
+                particle.x += w[0];
+                particle.v += w[1];
+            }
+        }
+    }
+
+    fn update(&mut self, measurement: f64) {
+        self.update_with_variance(measurement, self.r);
+    }
+
+    fn update_with_variance(&mut self, measurement: f64, r: f64) {
+        let expected: Vec<f64> = self
+            .particles
+            .iter()
+            .map(|particle| self.sensor.expected(particle.x))
+            .collect();
+        let predicted = self.mean(|particle| particle.x);
+        let predicted_measurement: f64 =
+            expected.iter().zip(&self.weights).map(|(z, w)| z * w).sum();
+        let spread: f64 = expected
+            .iter()
+            .zip(&self.weights)
+            .map(|(z, w)| (z - predicted_measurement).powi(2) * w)
+            .sum();
+        let innovation = measurement - predicted_measurement;
+
+        // Weigh in log space, so particles far from the measurement don't all underflow to 0
+        let log_weights: Vec<f64> = expected
+            .iter()
+            .zip(&self.weights)
+            .map(|(z, w)| w.ln() - (measurement - z).powi(2) / (2.0 * r))
+            .collect();
+        let max = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = log_weights.iter().map(|lw| (lw - max).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        self.weights = weights.iter().map(|w| w / total).collect();
+
+        self.innovation = (innovation, spread + r);
+        self.gain = if innovation != 0.0 {
+            (self.position() - predicted) / innovation
+        } else {
+            0.0
+        };
+
+        let effective = 1.0 / self.weights.iter().map(|w| w * w).sum::<f64>();
+        if effective < RESAMPLE_THRESHOLD * self.particles.len() as f64 {
+            let indices = self.resampling.resample(&self.weights, &mut self.rng);
+            self.particles = indices.into_iter().map(|i| self.particles[i]).collect();
+            self.weights = vec![1.0 / self.particles.len() as f64; self.particles.len()];
+        }
+    }
+
+    fn position(&self) -> f64 {
+        self.mean(|particle| particle.x)
+    }
+
+    fn velocity(&self) -> f64 {
+        self.mean(|particle| particle.v)
+    }
+
+    fn uncertainty(&self) -> f64 {
+        let mean = self.position();
+        self.mean(|particle| (particle.x - mean).powi(2))
+    }
+
+    /// How far the latest update moved the position estimate, relative to its innovation.
+    fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    fn innovation(&self) -> (f64, f64) {
+        self.innovation
+    }
+
+    fn process_noise(&self) -> f64 {
+        self.q
+    }
+
+    fn set_process_noise(&mut self, q: f64) {
+        self.q = q;
+    }
+}
+
+/// Positions of the particles of a `ParticleFilter` run over the measurements recorded in
+/// `result`, at every tick. With the fixed seed, this is the same cloud whose mean the
+/// `particle` model reports.
+pub fn particle_clouds(result: &SimulateResult, config: &SimulateConfig) -> Vec<Vec<f64>> {
+    replay_with(
+        result,
+        config,
+        ParticleFilter::from_config(config),
+        |filter| filter.positions().collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::FilterModel;
+    use crate::metrics;
+    use crate::sim::tests::{config, run};
+
+    const WEIGHTS: [f64; 5] = [0.12, 0.0, 0.43, 0.3, 0.15];
+
+    #[test]
+    fn every_scheme_keeps_the_particle_count() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for &resampling in Resampling::value_variants() {
+            for weights in [&WEIGHTS[..], &[0.0, 1.0, 0.0], &[1.0]] {
+                let indices = resampling.resample(weights, &mut rng);
+                assert_eq!(indices.len(), weights.len(), "{:?}", resampling);
+                // A particle without weight is never drawn
+                assert!(
+                    indices.iter().all(|&i| weights[i] > 0.0),
+                    "{:?}: {:?}",
+                    resampling,
+                    indices
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn residual_keeps_the_whole_copies_of_each_particle() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let weights: Vec<f64> = [3.0, 1.0, 4.0, 1.0, 5.0, 6.0]
+            .iter()
+            .map(|weight| weight / 20.0)
+            .collect();
+        for _ in 0..20 {
+            let indices = Resampling::Residual.resample(&weights, &mut rng);
+            for (i, weight) in weights.iter().enumerate() {
+                let copies = indices.iter().filter(|&&index| index == i).count();
+                let whole = (weight * weights.len() as f64).floor() as usize;
+                assert!(copies >= whole, "{} copies of {}, not {}", copies, i, whole);
+            }
+        }
+    }
+
+    #[test]
+    fn select_finds_the_slice_of_each_draw() {
+        let weights = [0.2, 0.5, 0.3];
+        assert_eq!(
+            select(&weights, [0.0, 0.19, 0.21, 0.69, 0.71, 0.99]),
+            [0, 0, 1, 1, 2, 2]
+        );
+        // Ten tenths add up to just below 1, and a draw that reaches the sum still lands on the
+        // last particle
+        assert_eq!(select(&[0.1; 10], [1.0 - f64::EPSILON / 2.0]), [9]);
+    }
+
+    #[test]
+    fn tracks_a_linear_run_about_as_well_as_the_kalman_filter() {
+        let particle = SimulateConfig {
+            total_time: 30.0,
+            q: 0.1,
+            model: FilterModel::Particle,
+            ..config()
+        };
+        let kalman = SimulateConfig {
+            model: FilterModel::ConstantVelocity,
+            ..particle
+        };
+        for seed in 1..=3 {
+            let particle_rmse = metrics::estimate_rmse(&run(&particle, seed));
+            let kalman_rmse = metrics::estimate_rmse(&run(&kalman, seed));
+            assert!(
+                (particle_rmse - kalman_rmse).abs() < 0.05 * kalman_rmse,
+                "seed {}: {} vs {}",
+                seed,
+                particle_rmse,
+                kalman_rmse
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use kalman_filter_simulator::render::RenderTimings;
+use kalman_filter_simulator::sim::SimulateConfig;
+
+/// Wall-clock seconds spent in each phase of a run.
+#[derive(Debug, Default, Serialize)]
+pub struct PhaseTimings {
+    /// Simulating the run, and the noisier one of `--compare-noise`
+    pub simulate: f64,
+    /// Writing the CSV, JSON, gnuplot and histogram exports
+    pub export: f64,
+    pub render: RenderTimings,
+    /// Encoding and writing the PNG and GIF
+    pub encode: f64,
+}
+
+/// What a profiled run was configured with and how long its phases took.
+#[derive(Serialize)]
+struct ProfileReport<'a> {
+    config: &'a SimulateConfig,
+    frames: usize,
+    seconds: &'a PhaseTimings,
+}
+
+/// Writes the profile of a run that rendered `frames` frames as pretty-printed JSON.
+pub fn write_profile(
+    path: &Path,
+    config: &SimulateConfig,
+    frames: usize,
+    timings: &PhaseTimings,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let report = ProfileReport {
+        config,
+        frames,
+        seconds: timings,
+    };
+    serde_json::to_writer_pretty(&mut writer, &report)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir};
+
+    #[test]
+    fn report_has_every_timing_key() {
+        let timings = PhaseTimings {
+            simulate: 0.5,
+            render: RenderTimings {
+                total: 2.0,
+                draw: 0.75,
+                rasterize: 1.25,
+            },
+            ..PhaseTimings::default()
+        };
+        let path = scratch_dir("profile").join("profile.json");
+        write_profile(&path, &default_config(), 100, &timings).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(report["frames"], 100);
+        assert_eq!(report["config"]["dt"], default_config().dt);
+        let seconds = &report["seconds"];
+        assert_eq!(seconds["simulate"], 0.5);
+        assert_eq!(seconds["export"], 0.0);
+        assert_eq!(seconds["encode"], 0.0);
+        assert_eq!(seconds["render"]["total"], 2.0);
+        assert_eq!(seconds["render"]["draw"], 0.75);
+        assert_eq!(seconds["render"]["rasterize"], 1.25);
+    }
+}
@@ -0,0 +1,52 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use color_quant::NeuQuant;
+use image::{Frame, RgbaImage};
+
+/// NeuQuant samples every `SAMPLE_FACTOR`th pixel while learning the palette, 1 being the
+/// slowest and most faithful. The plots are mostly flat color, so a coarse sample suffices.
+const SAMPLE_FACTOR: i32 = 10;
+
+/// GIF encoder that quantizes every frame to a palette of at most `colors` entries instead of
+/// the full 256, trading color fidelity for file size.
+pub struct QuantizedGifEncoder<W: Write> {
+    encoder: gif::Encoder<W>,
+    colors: usize,
+}
+
+impl<W: Write> QuantizedGifEncoder<W> {
+    pub fn new(
+        writer: W,
+        width: u16,
+        height: u16,
+        colors: usize,
+    ) -> Result<Self, gif::EncodingError> {
+        Ok(QuantizedGifEncoder {
+            encoder: gif::Encoder::new(writer, width, height, &[])?,
+            colors,
+        })
+    }
+
+    pub fn encode_frame(&mut self, frame: Frame) -> Result<(), gif::EncodingError> {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let delay = (numerator as f64 / denominator as f64 / 10.0).round() as u16;
+        let image: RgbaImage = frame.into_buffer();
+
+        let quantizer = NeuQuant::new(SAMPLE_FACTOR, self.colors, image.as_raw());
+        let indices: Vec<u8> = image
+            .as_raw()
+            .chunks_exact(4)
+            .map(|pixel| quantizer.index_of(pixel) as u8)
+            .collect();
+        let frame = gif::Frame {
+            width: image.width() as u16,
+            height: image.height() as u16,
+            delay,
+            palette: Some(quantizer.color_map_rgb()),
+            buffer: Cow::Owned(indices),
+            ..gif::Frame::default()
+        };
+        self.encoder.write_frame(&frame)
+    }
+}
@@ -0,0 +1,551 @@
+use tiny_skia::{
+    Color, FillRule, Paint, Path, PathBuilder, Pixmap, Rect, Stroke, StrokeDash, Transform,
+};
+
+use crate::render::{
+    axis_ticks, covariance_band, latest_time, BandPoint, LineCap, LineJoin, MarkerShape, Overlay,
+    RenderOptions, SeriesStyle, Viewport, BAND_OPACITY, COVARIANCE_BAND_OPACITY, ERROR_BAR_CAP,
+    MEASUREMENT_MARKER_RADIUS, PARTICLE_OPACITY, PARTICLE_RADIUS, STEADY_STATE_COLOR,
+    TICK_GRID_COLOR, TICK_LENGTH,
+};
+
+/// Draws the plots straight onto a pixmap with tiny-skia, for `--fast-render`.
+///
+/// Every shape matches its counterpart in the SVG renderer, so the pixels agree up to
+/// anti-aliasing, but nothing textual is drawn: no axis or tick labels, legend, regime labels,
+/// time annotation or scale bar.
+///
+/// The plot methods paint incrementally: given series that only grew since the previous
+/// call, they paint just the new segments and points onto the same pixmap, so animating `n`
+/// ticks paints `O(n)` shapes in all. `snapshot` copies the plot so far to add the moving
+/// markers of a single frame. Each segment is stroked on its own, so unlike a full redraw,
+/// newer shapes overlap older ones where they meet.
+pub struct Canvas<'a> {
+    pixmap: Pixmap,
+    viewport: &'a Viewport,
+    options: &'a RenderOptions,
+    painted: Painted,
+}
+
+/// How much of each series the plot methods already painted.
+#[derive(Default)]
+struct Painted {
+    /// Points painted of each series line, in the order the plot strokes them, and how long
+    /// the line is so far, where its dash pattern carries on
+    lines: Vec<(usize, f32)>,
+    /// Index into `lines` of the next series line the current plot call strokes
+    next_line: usize,
+    measurements: usize,
+    estimates: usize,
+    regime_changes: usize,
+    reference_line: bool,
+}
+
+impl<'a> Canvas<'a> {
+    /// Starts a pane like `plot_background`: a white background with the configured gridlines,
+    /// and the axes without their labels.
+    pub fn new(viewport: &'a Viewport, options: &'a RenderOptions) -> Self {
+        let size = viewport.size as u32;
+        let mut canvas = Canvas {
+            pixmap: Pixmap::new(size, size).unwrap(),
+            viewport,
+            options,
+            painted: Painted::default(),
+        };
+        canvas.pixmap.fill(Color::WHITE);
+
+        let (start, end) = (viewport.margin, viewport.size as f64 - viewport.margin);
+        for i in 1..=options.grid_lines {
+            let offset = start + viewport.plot_size() * i as f64 / (options.grid_lines + 1) as f64;
+            canvas.stroke(
+                line((offset, start), (offset, end)),
+                "#dddddd",
+                1.0,
+                &width(1.0),
+            );
+            canvas.stroke(
+                line((start, offset), (end, offset)),
+                "#dddddd",
+                1.0,
+                &width(1.0),
+            );
+        }
+        if options.axes {
+            canvas.axes();
+        }
+        canvas
+    }
+
+    /// Axis lines with a tick and gridline at every round value, like `add_axes`.
+    fn axes(&mut self) {
+        let viewport = self.viewport;
+        let (start, end) = (viewport.margin, viewport.size as f64 - viewport.margin);
+        for (x, _) in axis_ticks(viewport.time_range, false) {
+            let x = viewport.x(x);
+            self.stroke(
+                line((x, start), (x, end)),
+                TICK_GRID_COLOR,
+                1.0,
+                &width(1.0),
+            );
+            self.stroke(
+                line((x, end), (x, end + TICK_LENGTH)),
+                "#000000",
+                1.0,
+                &width(1.0),
+            );
+        }
+        for (y, _) in axis_ticks(viewport.position_range, viewport.log_y) {
+            let y = viewport.y(y);
+            self.stroke(
+                line((start, y), (end, y)),
+                TICK_GRID_COLOR,
+                1.0,
+                &width(1.0),
+            );
+            self.stroke(
+                line((start - TICK_LENGTH, y), (start, y)),
+                "#000000",
+                1.0,
+                &width(1.0),
+            );
+        }
+        let mut axes = PathBuilder::new();
+        axes.move_to(start as f32, start as f32);
+        axes.line_to(start as f32, end as f32);
+        axes.line_to(end as f32, end as f32);
+        if let Some(axes) = axes.finish() {
+            self.stroke(axes, "#000000", 1.0, &width(1.0));
+        }
+    }
+
+    pub fn into_pixmap(self) -> Pixmap {
+        self.pixmap
+    }
+
+    /// Copy of the plot painted so far, to draw the markers of one frame on.
+    pub fn snapshot(&self) -> Canvas<'a> {
+        Canvas {
+            pixmap: self.pixmap.clone(),
+            viewport: self.viewport,
+            options: self.options,
+            painted: Painted::default(),
+        }
+    }
+
+    /// Draws the position plot like `render`, minus the text.
+    pub fn position_plot(
+        &mut self,
+        true_positions: &[(f64, f64)],
+        measured_positions: &[(f64, f64)],
+        estimated_positions: &[(f64, f64)],
+        estimate_variances: &[f64],
+        overlays: &[Overlay],
+    ) {
+        let options = self.options;
+        let palette = &options.palette;
+        self.painted.next_line = 0;
+        self.regime_markers(latest_time(true_positions));
+
+        // Start the bands from the last painted point, so each new stretch joins the old one
+        let measured = self.painted.measurements.saturating_sub(1);
+        let estimated = self.painted.estimates.saturating_sub(1);
+        if let Some(sigmas) = options.covariance_band {
+            let points = covariance_band(
+                &estimated_positions[estimated..],
+                &estimate_variances[estimated..],
+                sigmas,
+            );
+            self.band(&points, palette.estimate.color, COVARIANCE_BAND_OPACITY);
+        }
+        if let Some(noise_bands) = options.noise_bands {
+            let layers = noise_bands.layers(
+                &measured_positions[measured..],
+                &estimated_positions[estimated..],
+                &estimate_variances[estimated..],
+            );
+            for (band, points) in &layers {
+                self.band(points, band.color(palette), BAND_OPACITY);
+            }
+        }
+
+        if options.passthrough {
+            self.series_line(measured_positions, palette.measurement_line());
+        }
+        self.series_line(true_positions, palette.truth);
+        self.series_line(estimated_positions, palette.estimate);
+        for overlay in overlays {
+            self.series_line(overlay.points, overlay.style);
+        }
+
+        let new_measurements = &measured_positions[self.painted.measurements..];
+        if let Some((sensor, r)) = options.error_bars {
+            for &(x, y) in new_measurements {
+                let stddev = sensor.measurement_variance(y, r).sqrt();
+                let (x, low, high) = (
+                    self.viewport.x(x),
+                    self.viewport.y(y - stddev),
+                    self.viewport.y(y + stddev),
+                );
+                let mut path = PathBuilder::new();
+                for (from, to) in [
+                    ((x - ERROR_BAR_CAP, low), (x + ERROR_BAR_CAP, low)),
+                    ((x, low), (x, high)),
+                    ((x - ERROR_BAR_CAP, high), (x + ERROR_BAR_CAP, high)),
+                ] {
+                    path.move_to(from.0 as f32, from.1 as f32);
+                    path.line_to(to.0 as f32, to.1 as f32);
+                }
+                if let Some(path) = path.finish() {
+                    self.stroke(path, palette.measurement, 0.5, &width(1.0));
+                }
+            }
+        }
+
+        for &(x, y) in new_measurements {
+            self.marker(self.viewport.x(x), self.viewport.y(y));
+        }
+        self.painted.measurements = measured_positions.len();
+        self.painted.estimates = estimated_positions.len();
+    }
+
+    /// Draws the velocity plot like `render_velocity`, minus the text, on a viewport whose
+    /// position range is the velocity range.
+    pub fn velocity_plot(
+        &mut self,
+        true_velocities: &[(f64, f64)],
+        estimated_velocities: &[(f64, f64)],
+    ) {
+        let options = self.options;
+        let palette = &options.palette;
+        self.painted.next_line = 0;
+        self.regime_markers(latest_time(true_velocities));
+        self.series_line(true_velocities, palette.truth);
+        self.series_line(estimated_velocities, palette.estimate);
+    }
+
+    /// Draws the gain plot like `render_gain`, minus the label of the steady-state line, on a
+    /// viewport whose position range is the gain range.
+    pub fn gain_plot(&mut self, gains: &[(f64, f64)]) {
+        let options = self.options;
+        let viewport = self.viewport;
+        self.painted.next_line = 0;
+        self.regime_markers(latest_time(gains));
+        if let Some(steady_state_gain) = options
+            .steady_state_gain
+            .filter(|_| !self.painted.reference_line)
+        {
+            self.painted.reference_line = true;
+            let y = viewport.y(steady_state_gain);
+            let mut stroke = width(1.0);
+            stroke.dash = StrokeDash::new(vec![6.0, 4.0], 0.0);
+            let reference = line(
+                (viewport.margin, y),
+                (viewport.size as f64 - viewport.margin, y),
+            );
+            self.stroke(reference, STEADY_STATE_COLOR, 1.0, &stroke);
+        }
+        self.series_line(gains, options.palette.estimate);
+    }
+
+    /// Dots for the particle positions in `cloud` at `x`, like `add_particle_cloud`.
+    pub fn particle_cloud(&mut self, x: f64, cloud: &[f64]) {
+        let paint = solid(self.options.palette.estimate.color, PARTICLE_OPACITY);
+        let x = self.viewport.x(x) as f32;
+        for &position in cloud {
+            let dot = PathBuilder::from_circle(
+                x,
+                self.viewport.y(position) as f32,
+                PARTICLE_RADIUS as f32,
+            );
+            if let Some(dot) = dot {
+                self.fill(&dot, &paint);
+            }
+        }
+    }
+
+    /// Emphasized marker for the latest position of a series, like `current_position_marker`.
+    pub fn current_position_marker(&mut self, (x, y): (f64, f64), fill: &str) {
+        let circle =
+            PathBuilder::from_circle(self.viewport.x(x) as f32, self.viewport.y(y) as f32, 6.0);
+        if let Some(circle) = circle {
+            self.fill(&circle, &solid(fill, 1.0));
+            self.stroke(circle, "black", 1.0, &width(2.0));
+        }
+    }
+
+    fn regime_markers(&mut self, until: f64) {
+        let viewport = self.viewport;
+        let mut stroke = width(1.0);
+        stroke.dash = StrokeDash::new(vec![4.0, 3.0], 0.0);
+        let painted = self.painted.regime_changes;
+        let changes = self.options.regime_changes.iter().skip(painted);
+        for change in changes.take_while(|change| change.time <= until) {
+            self.painted.regime_changes += 1;
+            let x = viewport.x(change.time);
+            let marker = line(
+                (x, viewport.margin),
+                (x, viewport.size as f64 - viewport.margin),
+            );
+            self.stroke(marker, "#888888", 1.0, &stroke);
+        }
+    }
+
+    /// Strokes the points of `points` added since the previous call as a polyline in `style`
+    /// continuing the painted one, or a dot for a lone point, like `add_series_line`.
+    fn series_line(&mut self, points: &[(f64, f64)], style: SeriesStyle) {
+        let viewport = self.viewport;
+        let slot = self.painted.next_line;
+        self.painted.next_line += 1;
+        if slot == self.painted.lines.len() {
+            self.painted.lines.push((0, 0.0));
+        }
+        let (painted, length) = self.painted.lines[slot];
+        if points.len() <= painted {
+            return;
+        }
+        self.painted.lines[slot].0 = points.len();
+
+        let mut stroke = width(2.0);
+        if let [(x, y)] = *points {
+            if let Some(dot) =
+                PathBuilder::from_circle(viewport.x(x) as f32, viewport.y(y) as f32, 1.0)
+            {
+                self.stroke(dot, style.color, 1.0, &stroke);
+            }
+            return;
+        }
+        let mut path = PathBuilder::new();
+        let mut previous: Option<(f32, f32)> = None;
+        let mut new_length = 0.0;
+        for &(x, y) in &points[painted.saturating_sub(1)..] {
+            let (x, y) = (viewport.x(x) as f32, viewport.y(y) as f32);
+            match previous {
+                None => path.move_to(x, y),
+                Some((previous_x, previous_y)) => {
+                    path.line_to(x, y);
+                    new_length += (x - previous_x).hypot(y - previous_y);
+                }
+            }
+            previous = Some((x, y));
+        }
+        self.painted.lines[slot].1 += new_length;
+        let Some(path) = path.finish() else {
+            return;
+        };
+        stroke.line_join = match self.options.line_join {
+            LineJoin::Round => tiny_skia::LineJoin::Round,
+            LineJoin::Miter => tiny_skia::LineJoin::Miter,
+            LineJoin::Bevel => tiny_skia::LineJoin::Bevel,
+        };
+        stroke.line_cap = match self.options.line_cap {
+            LineCap::Round => tiny_skia::LineCap::Round,
+            LineCap::Butt => tiny_skia::LineCap::Butt,
+            LineCap::Square => tiny_skia::LineCap::Square,
+        };
+        stroke.dash = style
+            .dash
+            .and_then(|(dash, gap)| StrokeDash::new(vec![dash as f32, gap as f32], length));
+        self.stroke(path, style.color, 1.0, &stroke);
+    }
+
+    /// Fills a band of `(x, center, half_width)` points at `opacity` like `add_band`.
+    fn band(&mut self, points: &[BandPoint], fill: &str, opacity: f64) {
+        if points.len() < 2 {
+            return;
+        }
+        let upper = points
+            .iter()
+            .map(|&(x, center, half_width)| (x, center + half_width));
+        let lower = points
+            .iter()
+            .rev()
+            .map(|&(x, center, half_width)| (x, center - half_width));
+        let mut path = PathBuilder::new();
+        for (i, (x, y)) in upper.chain(lower).enumerate() {
+            let (x, y) = (self.viewport.x(x) as f32, self.viewport.y(y) as f32);
+            if i == 0 {
+                path.move_to(x, y);
+            } else {
+                path.line_to(x, y);
+            }
+        }
+        path.close();
+        if let Some(path) = path.finish() {
+            self.fill(&path, &solid(fill, opacity));
+        }
+    }
+
+    /// Measurement marker of the configured shape, like `MarkerShape::marker`.
+    fn marker(&mut self, cx: f64, cy: f64) {
+        let radius = MEASUREMENT_MARKER_RADIUS;
+        let (cx, cy, radius) = (cx as f32, cy as f32, radius as f32);
+        let measurement = self.options.palette.measurement;
+        let shape = match self.options.marker_shape {
+            MarkerShape::Circle => PathBuilder::from_circle(cx, cy, radius),
+            MarkerShape::Square => {
+                Rect::from_xywh(cx - radius, cy - radius, radius * 2.0, radius * 2.0)
+                    .map(PathBuilder::from_rect)
+            }
+            MarkerShape::Triangle => {
+                let mut path = PathBuilder::new();
+                path.move_to(cx, cy - radius);
+                path.line_to(cx + radius, cy + radius);
+                path.line_to(cx - radius, cy + radius);
+                path.close();
+                path.finish()
+            }
+            MarkerShape::Cross => {
+                let mut path = PathBuilder::new();
+                path.move_to(cx - radius, cy - radius);
+                path.line_to(cx + radius, cy + radius);
+                path.move_to(cx - radius, cy + radius);
+                path.line_to(cx + radius, cy - radius);
+                if let Some(cross) = path.finish() {
+                    self.stroke(cross, measurement, 1.0, &width(1.0));
+                }
+                return;
+            }
+        };
+        if let Some(shape) = shape {
+            self.fill(&shape, &solid(measurement, 1.0));
+        }
+    }
+
+    fn fill(&mut self, path: &Path, paint: &Paint) {
+        self.pixmap
+            .fill_path(path, paint, FillRule::Winding, Transform::identity(), None);
+    }
+
+    fn stroke(&mut self, path: Path, color: &str, opacity: f64, stroke: &Stroke) {
+        self.pixmap.stroke_path(
+            &path,
+            &solid(color, opacity),
+            stroke,
+            Transform::identity(),
+            None,
+        );
+    }
+}
+
+fn line((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> Path {
+    let mut path = PathBuilder::new();
+    path.move_to(x1 as f32, y1 as f32);
+    path.line_to(x2 as f32, y2 as f32);
+    path.finish().unwrap()
+}
+
+fn width(width: f32) -> Stroke {
+    Stroke {
+        width,
+        ..Stroke::default()
+    }
+}
+
+/// Anti-aliased paint of the CSS `color` at `opacity`.
+fn solid(color_name: &str, opacity: f64) -> Paint<'static> {
+    let mut paint = Paint::default();
+    let mut color = color(color_name);
+    color.apply_opacity(opacity as f32);
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+/// Parses a `#rrggbb` color or one of the named colors the palettes use.
+fn color(name: &str) -> Color {
+    if let Some(hex) = name.strip_prefix('#') {
+        if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            return Color::from_rgba8(r, g, b, 255);
+        }
+    }
+    let (r, g, b) = match name {
+        "white" => (255, 255, 255),
+        "red" => (255, 0, 0),
+        "green" => (0, 128, 0),
+        "blue" => (0, 0, 255),
+        "darkorange" => (255, 140, 0),
+        "purple" => (128, 0, 128),
+        "teal" => (0, 128, 128),
+        "magenta" => (255, 0, 255),
+        "brown" => (165, 42, 42),
+        "gray" => (128, 128, 128),
+        "olive" => (128, 128, 0),
+        "navy" => (0, 0, 128),
+        "sienna" => (160, 82, 45),
+        "crimson" => (220, 20, 60),
+        "darkviolet" => (148, 0, 211),
+        "goldenrod" => (218, 165, 32),
+        _ => (0, 0, 0),
+    };
+    Color::from_rgba8(r, g, b, 255)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use crate::render::tests::{animate_options, render_options};
+    use crate::render::{animate, AnimateOptions, RenderOptions, RenderTimings};
+    use crate::sim::tests::{config, run};
+
+    /// Rows along the top and bottom edges of a pane that the SVG axis labels may cover.
+    const LABEL_ROWS: u32 = 24;
+
+    /// Largest difference over the channels of each pixel of `a` and `b`, skipping the rows
+    /// of the axis labels that only the SVG frames draw.
+    fn pixel_differences(a: &RgbaImage, b: &RgbaImage) -> Vec<u8> {
+        let unlabeled = LABEL_ROWS..a.height() - LABEL_ROWS;
+        a.enumerate_pixels()
+            .zip(b.pixels())
+            .filter(|((_, y, _), _)| unlabeled.contains(y))
+            .map(|((_, _, a), b)| {
+                (0..4)
+                    .map(|channel| a[channel].abs_diff(b[channel]))
+                    .max()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn direct_raster_matches_the_svg_render() {
+        let result = run(&config(), 1);
+        let render_options = RenderOptions {
+            time_annotation: false,
+            ..render_options()
+        };
+        let frames = |fast_render| {
+            let animate_options = AnimateOptions {
+                max_frames: Some(4),
+                fast_render,
+                ..animate_options(200)
+            };
+            let mut timings = RenderTimings::default();
+            animate(
+                &result,
+                &result,
+                &[],
+                &[],
+                &render_options,
+                &animate_options,
+                &mut timings,
+            )
+            .unwrap()
+        };
+        let (svg, fast) = (frames(false), frames(true));
+        assert_eq!(svg.len(), fast.len());
+        for (svg, fast) in svg.iter().zip(&fast) {
+            assert_eq!(svg.buffer().dimensions(), fast.buffer().dimensions());
+            let differences = pixel_differences(svg.buffer(), fast.buffer());
+            // Anti-aliasing differs slightly, and segments stroked one by one overlap where
+            // they join, but only a handful of pixels may differ noticeably
+            let mean =
+                differences.iter().map(|&d| d as f64).sum::<f64>() / differences.len() as f64;
+            let far = differences.iter().filter(|&&d| d > 64).count();
+            assert!(mean < 0.5, "mean difference {}", mean);
+            assert!(far * 1000 < differences.len(), "{} pixels differ", far);
+        }
+    }
+}
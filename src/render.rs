@@ -0,0 +1,3185 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Instant;
+
+use clap::ValueEnum;
+use image::{Frame, RgbaImage};
+use rayon::prelude::*;
+use resvg::tiny_skia::Pixmap;
+use resvg::usvg;
+use serde::{Deserialize, Serialize};
+use svg::node::element::{self, Circle, Line, Polygon, Polyline, Rectangle, Text};
+use svg::node::Node;
+use svg::Document;
+
+use crate::dynamics::FilterModel;
+use crate::metrics::{self, Regime, RegimeChange};
+use crate::raster::Canvas;
+use crate::sensor::SensorModel;
+use crate::sim::{SimulateResult, SimulateTick};
+use crate::variants::FilterVariant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlotKind {
+    /// True, measured and estimated position
+    Position,
+    /// True and estimated velocity
+    Velocity,
+    /// Filter gain `k`, which settles to a steady state
+    Gain,
+}
+
+/// How the animation's reveal speeds up or slows down over the run.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    /// The same number of ticks every frame
+    Linear,
+    /// Slow at the start, to dwell on the initial convergence
+    EaseIn,
+    /// Fast at the start and slow towards the end
+    EaseOut,
+    /// Slow at both ends
+    EaseInOut,
+}
+
+impl Easing {
+    /// Fraction of the run revealed after the fraction `progress` of the frames.
+    pub fn apply(self, progress: f64) -> f64 {
+        match self {
+            Easing::Linear => progress,
+            Easing::EaseIn => progress * progress,
+            Easing::EaseOut => 1.0 - (1.0 - progress) * (1.0 - progress),
+            Easing::EaseInOut => progress * progress * (3.0 - 2.0 * progress),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MarkerShape {
+    Circle,
+    Square,
+    Triangle,
+    Cross,
+}
+
+/// Maps simulation coordinates (time, position) onto a square canvas of `size` pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    pub size: usize,
+    pub time_range: (f64, f64),
+    pub position_range: (f64, f64),
+    /// Map positions logarithmically. `position_range` must then be positive.
+    pub log_y: bool,
+    /// Pixels kept free of data on every side of the canvas, for labels and legends
+    pub margin: f64,
+}
+
+impl Viewport {
+    /// Fits the time and position ranges to every series of `result`, so the whole run is
+    /// visible wherever it starts.
+    pub fn fit(result: &SimulateResult, size: usize, log_y: bool, margin: f64) -> Self {
+        let mut time_range = (f64::INFINITY, f64::NEG_INFINITY);
+        let mut position_range = (f64::INFINITY, f64::NEG_INFINITY);
+        for tick in &result.ticks {
+            let points = [
+                Some(tick.true_positions),
+                tick.measured_positions,
+                Some(tick.estimated_positions),
+            ];
+            for (time, position) in points.into_iter().flatten() {
+                time_range = (time_range.0.min(time), time_range.1.max(time));
+                if log_y && position <= 0.0 {
+                    continue;
+                }
+                position_range = (
+                    position_range.0.min(position),
+                    position_range.1.max(position),
+                );
+            }
+        }
+
+        Viewport {
+            size,
+            time_range: padded(time_range, 0.02),
+            position_range: if log_y {
+                padded_log(position_range, 0.05)
+            } else {
+                padded(position_range, 0.05)
+            },
+            log_y,
+            margin,
+        }
+    }
+
+    /// Side length of the inset region the data is mapped into.
+    pub fn plot_size(&self) -> f64 {
+        self.size as f64 - 2.0 * self.margin
+    }
+
+    pub fn x(&self, time: f64) -> f64 {
+        let (min, max) = self.time_range;
+        self.margin + (time - min) / (max - min) * self.plot_size()
+    }
+
+    pub fn y(&self, position: f64) -> f64 {
+        let (min, max) = self.position_range;
+        let fraction = if self.log_y {
+            // Non-positive values have no logarithm, so pin them to the bottom edge
+            (position.max(min).log10() - min.log10()) / (max.log10() - min.log10())
+        } else {
+            (position - min) / (max - min)
+        };
+        self.size as f64 - self.margin - fraction * self.plot_size()
+    }
+
+    /// Pixels per unit of time.
+    pub fn time_scale(&self) -> f64 {
+        self.plot_size() / (self.time_range.1 - self.time_range.0)
+    }
+
+    /// Pixels per unit of position. Only meaningful on a linear axis.
+    pub fn position_scale(&self) -> f64 {
+        self.plot_size() / (self.position_range.1 - self.position_range.0)
+    }
+}
+
+/// Widens `range` by `padding` of its span on both sides, falling back to a unit span when
+/// it is empty or a single value.
+fn padded((min, max): (f64, f64), padding: f64) -> (f64, f64) {
+    if !(min.is_finite() && max.is_finite()) || min >= max {
+        let center = if min.is_finite() { min } else { 0.0 };
+        return (center - 0.5, center + 0.5);
+    }
+    let margin = (max - min) * padding;
+    (min - margin, max + margin)
+}
+
+/// Like `padded`, but pads a positive range in log space.
+fn padded_log((min, max): (f64, f64), padding: f64) -> (f64, f64) {
+    let (min, max) = padded((min.log10(), max.log10()), padding);
+    (10f64.powf(min), 10f64.powf(max))
+}
+
+/// resvg matches font families against the fonts installed on the system and draws nothing when
+/// none matches, so list common sans-serif fonts explicitly before the generic family.
+const FONT_FAMILY: &str = "DejaVu Sans, Liberation Sans, Arial, Helvetica, sans-serif";
+
+/// Number of frames `animate` rasterizes in parallel at a time. Their SVG documents are held
+/// until then, so the batch bounds the memory of long animations.
+const RASTER_BATCH: usize = 64;
+
+/// Half the extent of a measurement marker in pixels.
+pub(crate) const MEASUREMENT_MARKER_RADIUS: f64 = 2.0;
+
+/// Radius of each particle of a particle cloud in pixels.
+pub(crate) const PARTICLE_RADIUS: f64 = 1.5;
+
+/// Opacity of the particles, low enough that dense parts of the cloud read darker.
+pub(crate) const PARTICLE_OPACITY: f64 = 0.2;
+
+/// Half-width of the caps at both ends of a measurement error bar, in pixels
+pub(crate) const ERROR_BAR_CAP: f64 = 2.5;
+
+/// Smallest margin `RenderOptions::axes` needs around the plot region for its tick labels.
+pub const AXES_MARGIN: f64 = 40.0;
+
+/// Length of the axis ticks in pixels, pointing out of the plot region.
+pub(crate) const TICK_LENGTH: f64 = 5.0;
+
+/// Color of the gridlines through the axis ticks, fainter than the `grid_lines` ones.
+pub(crate) const TICK_GRID_COLOR: &str = "#eeeeee";
+
+/// Rough width of a character of the 12-pixel legend text, to size the legend box with.
+const LEGEND_CHAR_WIDTH: f64 = 7.0;
+
+impl MarkerShape {
+    /// Builds a measurement marker of this shape centered on `(cx, cy)` that fits in a circle
+    /// of `radius`. The filled shapes have the class `measured-dot` and the cross, which is
+    /// stroked instead, `measured-cross`.
+    fn marker(self, cx: f64, cy: f64, radius: f64) -> Box<dyn Node> {
+        match self {
+            MarkerShape::Circle => Circle::new()
+                .set("cx", cx)
+                .set("cy", cy)
+                .set("r", radius)
+                .set("class", "measured-dot")
+                .into(),
+            MarkerShape::Square => Rectangle::new()
+                .set("x", cx - radius)
+                .set("y", cy - radius)
+                .set("width", radius * 2.0)
+                .set("height", radius * 2.0)
+                .set("class", "measured-dot")
+                .into(),
+            MarkerShape::Triangle => Polygon::new()
+                .set(
+                    "points",
+                    format!(
+                        "{},{} {},{} {},{}",
+                        cx,
+                        cy - radius,
+                        cx + radius,
+                        cy + radius,
+                        cx - radius,
+                        cy + radius
+                    ),
+                )
+                .set("class", "measured-dot")
+                .into(),
+            MarkerShape::Cross => element::Path::new()
+                .set(
+                    "d",
+                    format!(
+                        "M {} {} L {} {} M {} {} L {} {}",
+                        cx - radius,
+                        cy - radius,
+                        cx + radius,
+                        cy + radius,
+                        cx - radius,
+                        cy + radius,
+                        cx + radius,
+                        cy - radius
+                    ),
+                )
+                .set("class", "measured-cross")
+                .into(),
+        }
+    }
+}
+
+pub struct RenderOptions {
+    /// Unit of the simulation time, shown in the time axis label
+    pub time_unit: String,
+    /// Quantity on the x axis
+    pub x_axis: XAxis,
+    /// Draw a scale bar for each axis in the bottom-left corner
+    pub scale_bar: bool,
+    /// Use a logarithmic position (and error) axis
+    pub log_y: bool,
+    /// Show the time of the latest tick in each animation frame
+    pub time_annotation: bool,
+    /// Changes of the true motion to mark in the plots
+    pub regime_changes: Vec<RegimeChange>,
+    /// Shape of the measurement markers
+    pub marker_shape: MarkerShape,
+    /// Join the measurements into a line, to show what no filtering looks like
+    pub passthrough: bool,
+    /// Sensor model and `r` to draw ±σ error bars on the measurements from, when drawn
+    pub error_bars: Option<(SensorModel, f64)>,
+    /// Noise to draw the `p`, `q` and `r` bands from, when drawn
+    pub noise_bands: Option<NoiseBands>,
+    /// Standard deviations `√p` of the estimate to shade on either side of it, when drawn
+    pub covariance_band: Option<f64>,
+    /// Gain to draw the steady-state reference line of the gain plot at, when drawn
+    pub steady_state_gain: Option<f64>,
+    /// Number of evenly spaced gridlines across each axis of the plot region
+    pub grid_lines: usize,
+    /// Draw axes with numeric ticks along the plot region, and a framed legend naming every
+    /// series. The viewport's margin should be at least `AXES_MARGIN` to fit the tick labels.
+    pub axes: bool,
+    pub palette: Palette,
+    /// Shape where the segments of a series meet
+    pub line_join: LineJoin,
+    /// Shape of the ends of a series
+    pub line_cap: LineCap,
+}
+
+impl RenderOptions {
+    fn time_label(&self) -> String {
+        match self.x_axis {
+            XAxis::Time => format!("time [{}]", self.time_unit),
+            XAxis::Index => "sample index".to_string(),
+        }
+    }
+
+    /// Unit of x-axis lengths, for the scale bar.
+    fn x_unit(&self) -> &str {
+        match self.x_axis {
+            XAxis::Time => &self.time_unit,
+            XAxis::Index => "samples",
+        }
+    }
+
+    /// Text of the annotation naming the x value of the latest tick.
+    fn x_annotation(&self, x: f64) -> String {
+        match self.x_axis {
+            XAxis::Time => format!("t = {:.2} {}", x, self.time_unit),
+            XAxis::Index => format!("i = {}", x),
+        }
+    }
+}
+
+/// Adds the x (time) and y axis labels along the bottom and left edges of a pane.
+fn add_axis_labels(document: Document, x_label: &str, y_label: &str, size: usize) -> Document {
+    let x_label = Text::new(x_label)
+        .set("x", size as f64 - 8.0)
+        .set("y", size as f64 - 8.0)
+        .set("text-anchor", "end")
+        .set("font-family", FONT_FAMILY)
+        .set("font-size", 14)
+        .set("fill", "black");
+    let y_label = Text::new(y_label)
+        .set("x", 8.0)
+        .set("y", 20.0)
+        .set("font-family", FONT_FAMILY)
+        .set("font-size", 14)
+        .set("fill", "black");
+    document.add(x_label).add(y_label)
+}
+
+/// Largest 1, 2 or 5 times a power of ten that is not greater than `max`.
+fn round_length(max: f64) -> f64 {
+    let magnitude = 10f64.powf(max.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|&length| length <= max)
+        .unwrap_or(magnitude)
+}
+
+/// Ticks of an axis spanning `range`, as the value and label of each: round values 1, 2 or 5
+/// times a power of ten apart, five to ten of them, or the powers of ten on a logarithmic
+/// axis.
+pub(crate) fn axis_ticks((min, max): (f64, f64), log: bool) -> Vec<(f64, String)> {
+    if !(min.is_finite() && max.is_finite() && min < max) {
+        return Vec::new();
+    }
+    if log {
+        return (min.log10().ceil() as i32..=max.log10().floor() as i32)
+            .map(|exponent| (10f64.powi(exponent), format!("1e{}", exponent)))
+            .collect();
+    }
+    let step = round_length((max - min) / 5.0);
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    ((min / step).ceil() as i64..=(max / step).floor() as i64)
+        .map(|i| {
+            let value = i as f64 * step;
+            (value, format!("{:.*}", decimals, value))
+        })
+        .collect()
+}
+
+/// Adds axis lines along the bottom and left edges of the plot region, with a tick, a
+/// numeric label and a faint gridline at every value of `axis_ticks`.
+fn add_axes(mut document: Document, viewport: &Viewport) -> Document {
+    let (start, end) = (viewport.margin, viewport.size as f64 - viewport.margin);
+    let grid_line = |from: (f64, f64), to: (f64, f64)| {
+        Line::new()
+            .set("x1", from.0)
+            .set("y1", from.1)
+            .set("x2", to.0)
+            .set("y2", to.1)
+            .set("stroke", TICK_GRID_COLOR)
+            .set("stroke-width", 1)
+    };
+    let tick = |from: (f64, f64), to: (f64, f64)| {
+        Line::new()
+            .set("x1", from.0)
+            .set("y1", from.1)
+            .set("x2", to.0)
+            .set("y2", to.1)
+            .set("stroke", "black")
+            .set("stroke-width", 1)
+    };
+    let tick_label = |label: String, x: f64, y: f64, anchor: &str| {
+        Text::new(label)
+            .set("x", x)
+            .set("y", y)
+            .set("text-anchor", anchor)
+            .set("font-family", FONT_FAMILY)
+            .set("font-size", 11)
+            .set("fill", "black")
+    };
+
+    for (x, label) in axis_ticks(viewport.time_range, false) {
+        let x = viewport.x(x);
+        document = document
+            .add(grid_line((x, start), (x, end)))
+            .add(tick((x, end), (x, end + TICK_LENGTH)))
+            .add(tick_label(label, x, end + TICK_LENGTH + 11.0, "middle"));
+    }
+    for (y, label) in axis_ticks(viewport.position_range, viewport.log_y) {
+        let y = viewport.y(y);
+        document = document
+            .add(grid_line((start, y), (end, y)))
+            .add(tick((start - TICK_LENGTH, y), (start, y)))
+            .add(tick_label(label, start - TICK_LENGTH - 2.0, y + 4.0, "end"));
+    }
+
+    let axes = element::Path::new()
+        .set("d", format!("M {} {} V {} H {}", start, start, end, end))
+        .set("fill", "none")
+        .set("stroke", "black")
+        .set("stroke-width", 1);
+    document.add(axes)
+}
+
+/// Adds an L-shaped scale bar in the bottom-left corner, each arm covering a round amount of
+/// data that fits in about a fifth of the pane. A logarithmic axis has no fixed scale, so it
+/// only gets the time arm.
+fn add_scale_bar(document: Document, viewport: &Viewport, time_unit: &str) -> Document {
+    let size = viewport.size as f64;
+    let (origin_x, origin_y) = (viewport.margin + 20.0, size - viewport.margin - 30.0);
+    let max_pixels = viewport.plot_size() / 5.0;
+
+    let time_length = round_length(max_pixels / viewport.time_scale());
+    let position_length = round_length(max_pixels / viewport.position_scale());
+    let time_pixels = time_length * viewport.time_scale();
+    let position_pixels = position_length * viewport.position_scale();
+
+    let time_bar = Line::new()
+        .set("x1", origin_x)
+        .set("y1", origin_y)
+        .set("x2", origin_x + time_pixels)
+        .set("y2", origin_y)
+        .set("stroke", "black")
+        .set("stroke-width", 2);
+    let position_bar = Line::new()
+        .set("x1", origin_x)
+        .set("y1", origin_y)
+        .set("x2", origin_x)
+        .set("y2", origin_y - position_pixels)
+        .set("stroke", "black")
+        .set("stroke-width", 2);
+    let time_label = Text::new(format!("{} {}", time_length, time_unit))
+        .set("x", origin_x + time_pixels / 2.0)
+        .set("y", origin_y + 16.0)
+        .set("text-anchor", "middle")
+        .set("font-family", FONT_FAMILY)
+        .set("font-size", 12)
+        .set("fill", "black");
+    let position_label = Text::new(format!("{} units", position_length))
+        .set("x", origin_x + 6.0)
+        .set("y", origin_y - position_pixels / 2.0)
+        .set("font-family", FONT_FAMILY)
+        .set("font-size", 12)
+        .set("fill", "black");
+
+    let document = document.add(time_bar).add(time_label);
+    if viewport.log_y {
+        return document;
+    }
+    document.add(position_bar).add(position_label)
+}
+
+/// Time of the last point of `series`, or negative infinity when it's empty.
+pub(crate) fn latest_time(series: &[(f64, f64)]) -> f64 {
+    series.last().map_or(f64::NEG_INFINITY, |&(time, _)| time)
+}
+
+/// Adds a dashed vertical line, labeled with the regime that follows, at every change of the
+/// true motion up to `until`.
+fn add_regime_markers(
+    mut document: Document,
+    viewport: &Viewport,
+    options: &RenderOptions,
+    until: f64,
+) -> Document {
+    let size = viewport.size as f64;
+    for change in options
+        .regime_changes
+        .iter()
+        .filter(|change| change.time <= until)
+    {
+        let x = viewport.x(change.time);
+        let line = Line::new()
+            .set("x1", x)
+            .set("y1", viewport.margin)
+            .set("x2", x)
+            .set("y2", size - viewport.margin)
+            .set("stroke", "#888888")
+            .set("stroke-width", 1)
+            .set("stroke-dasharray", "4 3");
+        let label = match change.regime {
+            Regime::Velocity(velocity) => format!("v = {:.2}", velocity),
+            Regime::Acceleration(acceleration) => format!("a = {:.2}", acceleration),
+        };
+        let label = Text::new(label)
+            .set("x", x + 4.0)
+            .set("y", viewport.margin + 40.0)
+            .set("font-family", FONT_FAMILY)
+            .set("font-size", 12)
+            .set("fill", "#555555");
+        document = document.add(line).add(label);
+    }
+    document
+}
+
+/// Starts a pane: a white background with the configured gridlines across the plot region,
+/// and the axes when `options.axes` asks for them.
+fn plot_background(viewport: &Viewport, options: &RenderOptions) -> Document {
+    let size = viewport.size;
+    let mut document = Document::new()
+        .set("viewBox", (0, 0, size, size))
+        .set("width", size)
+        .set("height", size)
+        .add(element::Style::new(options.palette.stylesheet()));
+
+    let background = Rectangle::new()
+        .set("x", 0)
+        .set("y", 0)
+        .set("width", size)
+        .set("height", size)
+        .set("fill", "white");
+    document = document.add(background);
+
+    let (start, end) = (viewport.margin, size as f64 - viewport.margin);
+    for i in 1..=options.grid_lines {
+        let offset = start + viewport.plot_size() * i as f64 / (options.grid_lines + 1) as f64;
+        let vertical = Line::new()
+            .set("x1", offset)
+            .set("y1", start)
+            .set("x2", offset)
+            .set("y2", end);
+        let horizontal = Line::new()
+            .set("x1", start)
+            .set("y1", offset)
+            .set("x2", end)
+            .set("y2", offset);
+        for line in [vertical, horizontal] {
+            document = document.add(line.set("stroke", "#dddddd").set("stroke-width", 1));
+        }
+    }
+
+    if options.axes {
+        document = add_axes(document, viewport);
+    }
+    document
+}
+
+/// Noise parameters behind the uncertainty bands of `--noise-bands`.
+#[derive(Debug, Clone, Copy)]
+pub struct NoiseBands {
+    pub sensor: SensorModel,
+    /// Process noise the filter adds on every prediction
+    pub q: f64,
+    /// Measurement noise the filter assumes
+    pub r: f64,
+}
+
+impl NoiseBands {
+    /// Points of each band, widest first so the narrower bands stay
+    /// visible when drawn on top.
+    pub(crate) fn layers(
+        &self,
+        measured_positions: &[(f64, f64)],
+        estimated_positions: &[(f64, f64)],
+        estimate_variances: &[f64],
+    ) -> Vec<(Band, Vec<BandPoint>)> {
+        let NoiseBands { sensor, q, r } = *self;
+        let mut layers = vec![
+            (
+                Band::R,
+                measured_positions
+                    .iter()
+                    .map(|&(x, y)| (x, y, sensor.measurement_variance(y, r).sqrt()))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                Band::P,
+                estimated_positions
+                    .iter()
+                    .zip(estimate_variances)
+                    .map(|(&(x, y), p)| (x, y, p.sqrt()))
+                    .collect(),
+            ),
+            (
+                Band::Q,
+                estimated_positions
+                    .iter()
+                    .map(|&(x, y)| (x, y, q.sqrt()))
+                    .collect(),
+            ),
+        ];
+        layers.sort_by(|(_, a), (_, b)| mean_half_width(b).total_cmp(&mean_half_width(a)));
+        layers
+    }
+}
+
+/// Point `(x, center, half_width)` of a band.
+pub(crate) type BandPoint = (f64, f64, f64);
+
+/// One of the uncertainty bands of `--noise-bands`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Band {
+    /// ±√p around the estimate
+    P,
+    /// ±√q around the estimate
+    Q,
+    /// ±√r around the measurements
+    R,
+}
+
+impl Band {
+    fn class(self) -> &'static str {
+        match self {
+            Band::P => "p-band",
+            Band::Q => "q-band",
+            Band::R => "r-band",
+        }
+    }
+
+    /// Fill color, that of the series the band belongs to.
+    pub(crate) fn color(self, palette: &Palette) -> &'static str {
+        match self {
+            Band::P => palette.estimate.color,
+            Band::Q => palette.truth.color,
+            Band::R => palette.measurement,
+        }
+    }
+}
+
+/// Opacity of the noise band fills.
+pub(crate) const BAND_OPACITY: f64 = 0.15;
+
+/// Opacity of the covariance band fill, stronger than the noise bands' since it stands alone.
+pub(crate) const COVARIANCE_BAND_OPACITY: f64 = 0.25;
+
+/// Points of the band of `sigmas` standard deviations `√p` around the estimate.
+pub(crate) fn covariance_band(
+    estimated_positions: &[(f64, f64)],
+    estimate_variances: &[f64],
+    sigmas: f64,
+) -> Vec<BandPoint> {
+    estimated_positions
+        .iter()
+        .zip(estimate_variances)
+        .map(|(&(x, y), p)| (x, y, sigmas * p.sqrt()))
+        .collect()
+}
+
+/// Stroke of one plotted series.
+#[derive(Debug, Clone, Copy)]
+pub struct SeriesStyle {
+    /// CSS class of the series' line and legend sample, which the stylesheet styles
+    pub class: &'static str,
+    pub color: &'static str,
+    /// Dash and gap length in pixels, `None` for a solid line
+    pub dash: Option<(f64, f64)>,
+}
+
+/// Colors and line styles of every plotted series.
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub truth: SeriesStyle,
+    pub estimate: SeriesStyle,
+    pub smoothed: SeriesStyle,
+    pub measurement: &'static str,
+    pub error: SeriesStyle,
+    /// Style of each filter variant of `--compare-filters`, in `FilterVariant::ALL` order
+    pub variants: [SeriesStyle; 5],
+    /// Style of each filter model of `--compare-models`, in `FilterModel` order
+    pub models: [SeriesStyle; 6],
+}
+
+/// SVG `stroke-linejoin` of the plotted lines.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineJoin {
+    Round,
+    Miter,
+    Bevel,
+}
+
+impl LineJoin {
+    fn svg_name(self) -> &'static str {
+        match self {
+            LineJoin::Round => "round",
+            LineJoin::Miter => "miter",
+            LineJoin::Bevel => "bevel",
+        }
+    }
+}
+
+/// SVG `stroke-linecap` of the plotted lines.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineCap {
+    Round,
+    Butt,
+    Square,
+}
+
+impl LineCap {
+    fn svg_name(self) -> &'static str {
+        match self {
+            LineCap::Round => "round",
+            LineCap::Butt => "butt",
+            LineCap::Square => "square",
+        }
+    }
+}
+
+/// Quantity plotted along the x axis.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum XAxis {
+    /// Simulation time
+    Time,
+    /// Sample index, for data without meaningful timestamps
+    Index,
+}
+
+impl XAxis {
+    /// Name of the x column in the exports, such as `time[s]`.
+    pub fn column_name(self, time_unit: &str) -> String {
+        match self {
+            XAxis::Time => format!("time[{}]", time_unit),
+            XAxis::Index => "index".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// Red truth, green estimate and blue measurements
+    Classic,
+    /// Okabe-Ito colors plus a dashed truth line, distinguishable with any color vision
+    OkabeIto,
+}
+
+impl Theme {
+    pub fn palette(self) -> Palette {
+        let solid = |class, color| SeriesStyle {
+            class,
+            color,
+            dash: None,
+        };
+        let dashed = |class, color, dash| SeriesStyle {
+            class,
+            color,
+            dash: Some(dash),
+        };
+        match self {
+            Theme::Classic => Palette {
+                truth: solid("true-line", "red"),
+                estimate: solid("estimated-line", "green"),
+                smoothed: solid("smoothed-line", "darkorange"),
+                measurement: "blue",
+                error: solid("error-line", "purple"),
+                variants: [
+                    solid("standard-line", "teal"),
+                    dashed("joseph-line", "black", (6.0, 4.0)),
+                    dashed("square-root-line", "magenta", (2.0, 3.0)),
+                    dashed("information-line", "brown", (10.0, 3.0)),
+                    solid("alpha-beta-line", "gray"),
+                ],
+                models: [
+                    dashed("known-velocity-line", "olive", (6.0, 4.0)),
+                    dashed("constant-velocity-line", "navy", (2.0, 3.0)),
+                    dashed("constant-acceleration-line", "sienna", (10.0, 3.0)),
+                    solid("extended-line", "crimson"),
+                    solid("unscented-line", "darkviolet"),
+                    solid("particle-line", "goldenrod"),
+                ],
+            },
+            Theme::OkabeIto => Palette {
+                truth: SeriesStyle {
+                    class: "true-line",
+                    color: "#D55E00",
+                    dash: Some((8.0, 5.0)),
+                },
+                estimate: solid("estimated-line", "#0072B2"),
+                smoothed: solid("smoothed-line", "#009E73"),
+                measurement: "#E69F00",
+                error: solid("error-line", "#CC79A7"),
+                variants: [
+                    solid("standard-line", "#56B4E9"),
+                    dashed("joseph-line", "#000000", (6.0, 4.0)),
+                    dashed("square-root-line", "#CC79A7", (2.0, 3.0)),
+                    dashed("information-line", "#F0E442", (10.0, 3.0)),
+                    solid("alpha-beta-line", "#999999"),
+                ],
+                models: [
+                    dashed("known-velocity-line", "#999999", (6.0, 4.0)),
+                    dashed("constant-velocity-line", "#56B4E9", (2.0, 3.0)),
+                    dashed("constant-acceleration-line", "#F0E442", (10.0, 3.0)),
+                    solid("extended-line", "#000000"),
+                    solid("unscented-line", "#CC79A7"),
+                    solid("particle-line", "#882255"),
+                ],
+            },
+        }
+    }
+}
+
+impl Palette {
+    /// Style of lines through measurements, such as the unfiltered baseline.
+    pub(crate) fn measurement_line(&self) -> SeriesStyle {
+        SeriesStyle {
+            class: "measured-line",
+            color: self.measurement,
+            dash: None,
+        }
+    }
+
+    pub fn variant(&self, variant: FilterVariant) -> SeriesStyle {
+        self.variants[variant as usize]
+    }
+
+    pub fn model(&self, model: FilterModel) -> SeriesStyle {
+        self.models[model as usize]
+    }
+
+    /// CSS giving every class of plotted element its colors and strokes. Each plot embeds it
+    /// in a `<style>` element, so a written SVG can be restyled by editing the stylesheet.
+    fn stylesheet(&self) -> String {
+        let mut css = String::new();
+        let series = [
+            self.truth,
+            self.estimate,
+            self.smoothed,
+            self.error,
+            self.measurement_line(),
+        ];
+        for style in series.into_iter().chain(self.variants).chain(self.models) {
+            let dash = style.dash.map_or(String::new(), |(dash, gap)| {
+                format!(" stroke-dasharray: {} {};", dash, gap)
+            });
+            css += &format!(
+                ".{} {{ fill: none; stroke: {}; stroke-width: 2;{} }}\n",
+                style.class, style.color, dash
+            );
+        }
+        let measurement = self.measurement;
+        css += &format!(".measured-dot {{ fill: {}; }}\n", measurement);
+        css += &format!(
+            ".measured-cross {{ fill: none; stroke: {}; stroke-width: 1; }}\n",
+            measurement
+        );
+        css += &format!(
+            ".measured-error-bar {{ fill: none; stroke: {}; stroke-width: 1; stroke-opacity: 0.5; }}\n",
+            measurement
+        );
+        css += &format!(
+            ".particle {{ fill: {}; fill-opacity: {}; stroke: none; }}\n",
+            self.estimate.color, PARTICLE_OPACITY
+        );
+        for band in [Band::P, Band::Q, Band::R] {
+            css += &format!(
+                ".{} {{ fill: {}; fill-opacity: {}; stroke: none; }}\n",
+                band.class(),
+                band.color(self),
+                BAND_OPACITY
+            );
+        }
+        css += &format!(
+            ".covariance-band {{ fill: {}; fill-opacity: {}; stroke: none; }}\n",
+            self.estimate.color, COVARIANCE_BAND_OPACITY
+        );
+        css += &format!(
+            ".covariance-line {{ fill: none; stroke: {}; stroke-width: 2; stroke-dasharray: 6 3; }}\n",
+            COVARIANCE_COLOR
+        );
+        for (class, color) in [
+            ("true-marker", self.truth.color),
+            ("estimated-marker", self.estimate.color),
+            ("covariance-marker", COVARIANCE_COLOR),
+        ] {
+            css += &format!(
+                ".{} {{ fill: {}; stroke: black; stroke-width: 2; }}\n",
+                class, color
+            );
+        }
+        css
+    }
+}
+
+/// Draws `points` as a single polyline in `style`. A lone point, as in a one-step run, becomes
+/// a dot instead, since a polyline needs two points to show up.
+fn add_series_line(
+    document: Document,
+    points: &[(f64, f64)],
+    viewport: &Viewport,
+    style: SeriesStyle,
+    options: &RenderOptions,
+) -> Document {
+    if let [(x, y)] = *points {
+        let dot = Circle::new()
+            .set("cx", viewport.x(x))
+            .set("cy", viewport.y(y))
+            .set("r", 1.0)
+            .set("class", style.class);
+        return document.add(dot);
+    }
+    if points.len() < 2 {
+        return document;
+    }
+    let coordinates = points
+        .iter()
+        .map(|&(x, y)| format!("{},{}", viewport.x(x), viewport.y(y)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let line = Polyline::new()
+        .set("points", coordinates)
+        .set("class", style.class)
+        .set("stroke-linejoin", options.line_join.svg_name())
+        .set("stroke-linecap", options.line_cap.svg_name());
+    document.add(line)
+}
+
+/// What a legend entry shows next to its label.
+#[derive(Clone, Copy)]
+enum LegendSample {
+    /// A stretch of a series line
+    Line(SeriesStyle),
+    /// A measurement marker
+    Marker(MarkerShape),
+    /// A patch of a band, styled by its CSS class
+    Band(&'static str),
+}
+
+/// Adds a legend of `entries` below the y axis label in the top-left corner or, with
+/// `options.axes`, in a box in the top-left corner of the plot region.
+fn add_legend(
+    mut document: Document,
+    entries: &[(&str, LegendSample)],
+    viewport: &Viewport,
+    options: &RenderOptions,
+) -> Document {
+    let (left, top) = if options.axes {
+        (viewport.margin + 12.0, viewport.margin + 18.0)
+    } else {
+        (8.0, 40.0)
+    };
+    if options.axes {
+        let longest = entries
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let frame = Rectangle::new()
+            .set("x", left - 6.0)
+            .set("y", top - 12.0)
+            .set("width", 42.0 + LEGEND_CHAR_WIDTH * longest as f64)
+            .set("height", 18.0 * entries.len() as f64)
+            .set("fill", "white")
+            .set("fill-opacity", 0.85)
+            .set("stroke", "#888888")
+            .set("stroke-width", 1);
+        document = document.add(frame);
+    }
+    for (i, (label, sample)) in entries.iter().enumerate() {
+        let y = top + 18.0 * i as f64;
+        let sample: Box<dyn Node> = match *sample {
+            LegendSample::Line(style) => Line::new()
+                .set("x1", left)
+                .set("y1", y)
+                .set("x2", left + 24.0)
+                .set("y2", y)
+                .set("class", style.class)
+                .into(),
+            LegendSample::Marker(shape) => shape.marker(left + 12.0, y, MEASUREMENT_MARKER_RADIUS),
+            LegendSample::Band(class) => Rectangle::new()
+                .set("x", left)
+                .set("y", y - 5.0)
+                .set("width", 24.0)
+                .set("height", 10.0)
+                .set("class", class)
+                .into(),
+        };
+        let text = Text::new(*label)
+            .set("x", left + 30.0)
+            .set("y", y + 4.0)
+            .set("font-family", FONT_FAMILY)
+            .set("font-size", 12)
+            .set("fill", "black");
+        document = document.add(sample).add(text);
+    }
+    document
+}
+
+/// Further track drawn over the position plot, such as the smoothed one.
+#[derive(Debug, Clone, Copy)]
+pub struct Overlay<'a> {
+    /// Legend entry of the track
+    pub label: &'a str,
+    pub style: SeriesStyle,
+    pub points: &'a [(f64, f64)],
+}
+
+/// Renders the position plot. The `overlays` are drawn on top as further series, as are the
+/// measurements joined into a line with `options.passthrough`, and a legend then tells the
+/// tracks apart.
+pub fn render(
+    true_positions: &[(f64, f64)],
+    measured_positions: &[(f64, f64)],
+    estimated_positions: &[(f64, f64)],
+    estimate_variances: &[f64],
+    overlays: &[Overlay],
+    viewport: &Viewport,
+    options: &RenderOptions,
+) -> Document {
+    let size = viewport.size;
+    let mut document = plot_background(viewport, options);
+    document = add_regime_markers(document, viewport, options, latest_time(true_positions));
+
+    if let Some(sigmas) = options.covariance_band {
+        let points = covariance_band(estimated_positions, estimate_variances, sigmas);
+        document = add_band(document, &points, "covariance-band", viewport);
+    }
+    if let Some(noise_bands) = options.noise_bands {
+        let layers =
+            noise_bands.layers(measured_positions, estimated_positions, estimate_variances);
+        for (band, points) in &layers {
+            document = add_band(document, points, band.class(), viewport);
+        }
+    }
+
+    let palette = &options.palette;
+    let band_label = options
+        .covariance_band
+        .map(|sigmas| format!("±{}σ", sigmas));
+    let mut legend = vec![
+        ("true", LegendSample::Line(palette.truth)),
+        ("filtered", LegendSample::Line(palette.estimate)),
+    ];
+    if let Some(band_label) = &band_label {
+        legend.push((band_label, LegendSample::Band("covariance-band")));
+    }
+    // With axes, the legend names the measurements too, and so is always shown
+    if options.axes {
+        legend.push(("measured", LegendSample::Marker(options.marker_shape)));
+    }
+    if options.passthrough {
+        // Behind the other series, since it is the most jagged
+        let passthrough = palette.measurement_line();
+        document = add_series_line(document, measured_positions, viewport, passthrough, options);
+        legend.push(("unfiltered", LegendSample::Line(passthrough)));
+    }
+    document = add_series_line(document, true_positions, viewport, palette.truth, options);
+    document = add_series_line(
+        document,
+        estimated_positions,
+        viewport,
+        palette.estimate,
+        options,
+    );
+    for overlay in overlays {
+        document = add_series_line(document, overlay.points, viewport, overlay.style, options);
+        legend.push((overlay.label, LegendSample::Line(overlay.style)));
+    }
+    if legend.len() > 2 {
+        document = add_legend(document, &legend, viewport, options);
+    }
+
+    if let Some((sensor, r)) = options.error_bars {
+        for &(x, y) in measured_positions {
+            // The multiplicative sensor's σ depends on the position, so use the measured one
+            let stddev = sensor.measurement_variance(y, r).sqrt();
+            document = document.add(error_bar(x, (y - stddev, y + stddev), viewport));
+        }
+    }
+
+    for &(x, y) in measured_positions {
+        let marker =
+            options
+                .marker_shape
+                .marker(viewport.x(x), viewport.y(y), MEASUREMENT_MARKER_RADIUS);
+        document = document.add(marker);
+    }
+
+    if options.scale_bar {
+        document = add_scale_bar(document, viewport, options.x_unit());
+    }
+
+    add_axis_labels(document, &options.time_label(), "position", size)
+}
+
+/// Mean half-width of a band of `(x, center, half_width)` points, 0 for an empty one.
+fn mean_half_width(band: &[BandPoint]) -> f64 {
+    band.iter()
+        .map(|&(_, _, half_width)| half_width)
+        .sum::<f64>()
+        / band.len().max(1) as f64
+}
+
+/// Fills the area between `center - half_width` and `center + half_width` along `points` of
+/// `(x, center, half_width)`, styled by the CSS `class`. Fewer than two points enclose no area
+/// and draw nothing.
+fn add_band(
+    document: Document,
+    points: &[BandPoint],
+    class: &str,
+    viewport: &Viewport,
+) -> Document {
+    if points.len() < 2 {
+        return document;
+    }
+    let upper = points
+        .iter()
+        .map(|&(x, center, half_width)| (x, center + half_width));
+    let lower = points
+        .iter()
+        .rev()
+        .map(|&(x, center, half_width)| (x, center - half_width));
+    let outline = upper
+        .chain(lower)
+        .map(|(x, y)| format!("{},{}", viewport.x(x), viewport.y(y)))
+        .collect::<Vec<_>>()
+        .join(" L ");
+    let band = element::Path::new()
+        .set("d", format!("M {} Z", outline))
+        .set("class", class);
+    document.add(band)
+}
+
+/// Vertical bar with caps at `x` spanning the position range `(low, high)`.
+fn error_bar(x: f64, (low, high): (f64, f64), viewport: &Viewport) -> element::Path {
+    let (x, low, high) = (viewport.x(x), viewport.y(low), viewport.y(high));
+    element::Path::new()
+        .set(
+            "d",
+            format!(
+                "M {} {} H {} M {} {} V {} M {} {} H {}",
+                x - ERROR_BAR_CAP,
+                low,
+                x + ERROR_BAR_CAP,
+                x,
+                low,
+                high,
+                x - ERROR_BAR_CAP,
+                high,
+                x + ERROR_BAR_CAP
+            ),
+        )
+        .set("class", "measured-error-bar")
+}
+
+/// Adds a dot at `x` for each of the particle positions in `cloud`, so the spread of a particle
+/// filter's posterior shows as a vertical smear.
+fn add_particle_cloud(
+    mut document: Document,
+    x: f64,
+    cloud: &[f64],
+    viewport: &Viewport,
+) -> Document {
+    for &position in cloud {
+        let particle = Circle::new()
+            .set("cx", viewport.x(x))
+            .set("cy", viewport.y(position))
+            .set("r", PARTICLE_RADIUS)
+            .set("class", "particle");
+        document = document.add(particle);
+    }
+    document
+}
+
+/// Emphasized marker for the latest position of a series, styled by the CSS `class` in the
+/// series' color.
+fn current_position_marker(position: (f64, f64), class: &str, viewport: &Viewport) -> Circle {
+    let (x, y) = position;
+    Circle::new()
+        .set("cx", viewport.x(x))
+        .set("cy", viewport.y(y))
+        .set("r", 6.0)
+        .set("class", class)
+}
+
+/// Label showing the simulation time (or sample index) of an animation frame, for the
+/// top-right corner.
+fn time_annotation(label: String, size: usize) -> Text {
+    Text::new(label)
+        .set("x", size as f64 - 8.0)
+        .set("y", 20.0)
+        .set("text-anchor", "end")
+        .set("font-family", FONT_FAMILY)
+        .set("font-size", 14)
+        .set("fill", "black")
+}
+
+/// Renders the true velocity and the filter's velocity estimate over time.
+///
+/// The viewport's position range is interpreted as the velocity range.
+pub fn render_velocity(
+    true_velocities: &[(f64, f64)],
+    estimated_velocities: &[(f64, f64)],
+    viewport: &Viewport,
+    options: &RenderOptions,
+) -> Document {
+    let size = viewport.size;
+    let mut document = plot_background(viewport, options);
+    document = add_regime_markers(document, viewport, options, latest_time(true_velocities));
+
+    let palette = &options.palette;
+    document = add_series_line(document, true_velocities, viewport, palette.truth, options);
+    document = add_series_line(
+        document,
+        estimated_velocities,
+        viewport,
+        palette.estimate,
+        options,
+    );
+    if options.axes {
+        document = add_legend(
+            document,
+            &[
+                ("true", LegendSample::Line(palette.truth)),
+                ("filtered", LegendSample::Line(palette.estimate)),
+            ],
+            viewport,
+            options,
+        );
+    }
+
+    add_axis_labels(document, &options.time_label(), "velocity", size)
+}
+
+/// Renders the filter gain over time, with a dashed reference line at
+/// `options.steady_state_gain` when given.
+///
+/// The viewport's position range is interpreted as the gain range.
+pub fn render_gain(gains: &[(f64, f64)], viewport: &Viewport, options: &RenderOptions) -> Document {
+    let size = viewport.size;
+    let mut document = plot_background(viewport, options);
+    document = add_regime_markers(document, viewport, options, latest_time(gains));
+
+    if let Some(steady_state_gain) = options.steady_state_gain {
+        let y = viewport.y(steady_state_gain);
+        let line = Line::new()
+            .set("x1", viewport.margin)
+            .set("y1", y)
+            .set("x2", size as f64 - viewport.margin)
+            .set("y2", y)
+            .set("stroke", STEADY_STATE_COLOR)
+            .set("stroke-width", 1)
+            .set("stroke-dasharray", "6 4");
+        let label = Text::new(format!("steady state k = {:.4}", steady_state_gain))
+            .set("x", size as f64 - viewport.margin - 4.0)
+            .set("y", y - 4.0)
+            .set("text-anchor", "end")
+            .set("font-family", FONT_FAMILY)
+            .set("font-size", 12)
+            .set("fill", STEADY_STATE_COLOR);
+        document = document.add(line).add(label);
+    }
+
+    let palette = &options.palette;
+    document = add_series_line(document, gains, viewport, palette.estimate, options);
+
+    add_axis_labels(document, &options.time_label(), "gain", size)
+}
+
+/// Color of the steady-state reference line of the gain plot and its label.
+pub(crate) const STEADY_STATE_COLOR: &str = "#555555";
+
+/// Color of the covariance `p` in the `gain_panel` strip, and of the bounds of the
+/// `innovation_panel` correlogram.
+const COVARIANCE_COLOR: &str = "#555555";
+
+/// Half-width of the band around the innovations of the `innovation_panel` strip, in standard
+/// deviations `√S` of the innovation.
+const INNOVATION_BAND_SIGMAS: f64 = 2.0;
+
+/// Largest lag of the autocorrelation the `innovation_panel` correlogram shows.
+const CORRELOGRAM_LAGS: usize = 10;
+
+/// Renders the absolute estimate error `|estimated - true|` over time, and the measurement
+/// error `|measured - true|` next to it when `measured_errors` is given.
+///
+/// The viewport's position range is interpreted as the error range.
+pub fn render_error(
+    errors: &[(f64, f64)],
+    measured_errors: Option<&[(f64, f64)]>,
+    viewport: &Viewport,
+    options: &RenderOptions,
+) -> Document {
+    let size = viewport.size;
+    let mut document = plot_background(viewport, options);
+
+    let palette = &options.palette;
+    let Some(measured_errors) = measured_errors else {
+        document = add_series_line(document, errors, viewport, palette.error, options);
+        return add_axis_labels(document, &options.time_label(), "|estimated - true|", size);
+    };
+
+    let measured_style = palette.measurement_line();
+    document = add_series_line(document, measured_errors, viewport, measured_style, options);
+    document = add_series_line(document, errors, viewport, palette.error, options);
+    document = add_legend(
+        document,
+        &[
+            ("measured", LegendSample::Line(measured_style)),
+            ("estimated", LegendSample::Line(palette.error)),
+        ],
+        viewport,
+        options,
+    );
+    add_axis_labels(document, &options.time_label(), "|error|", size)
+}
+
+/// Lays out `panes` side by side, each `size` pixels square, in a single wider canvas.
+pub fn render_composite(panes: Vec<Document>, size: usize) -> Document {
+    let width = size * panes.len();
+    let mut document = Document::new()
+        .set("viewBox", (0, 0, width, size))
+        .set("width", width)
+        .set("height", size);
+
+    for (i, pane) in panes.into_iter().enumerate() {
+        document = document.add(pane.set("x", i * size).set("y", 0));
+    }
+
+    document
+}
+
+/// Grid of values over every pair of a row and a column parameter, as `render_heatmap` draws
+/// it.
+pub struct Heatmap<'a> {
+    pub title: &'a str,
+    /// Name of the parameter that varies across the columns
+    pub column_name: &'a str,
+    pub columns: &'a [f64],
+    /// Name of the parameter that varies down the rows
+    pub row_name: &'a str,
+    pub rows: &'a [f64],
+    /// `values[row][column]`
+    pub values: &'a [Vec<f64>],
+}
+
+/// Side length of a heatmap cell in pixels.
+const HEATMAP_CELL: usize = 72;
+
+/// Room for the row labels left of a heatmap, and for the title and column labels above it.
+const HEATMAP_MARGIN: usize = 64;
+
+/// Colors of the lowest and highest value of a heatmap, which the cells interpolate between.
+const HEATMAP_COLORS: [(f64, f64, f64); 2] = [(247.0, 251.0, 255.0), (8.0, 48.0, 107.0)];
+
+impl Heatmap<'_> {
+    /// Width and height of the heatmap in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        (
+            HEATMAP_MARGIN + self.columns.len() * HEATMAP_CELL + HEATMAP_MARGIN / 4,
+            HEATMAP_MARGIN + self.rows.len() * HEATMAP_CELL + HEATMAP_MARGIN / 4,
+        )
+    }
+
+    /// Row and column of the smallest finite value, if any.
+    pub fn minimum(&self) -> Option<(usize, usize)> {
+        self.values
+            .iter()
+            .enumerate()
+            .flat_map(|(row, values)| {
+                values
+                    .iter()
+                    .enumerate()
+                    .map(move |(column, &value)| (row, column, value))
+            })
+            .filter(|(_, _, value)| value.is_finite())
+            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+            .map(|(row, column, _)| (row, column))
+    }
+}
+
+/// Renders `heatmap` as a grid of cells shaded from light at its smallest value to dark at its
+/// largest, on a log scale so a few badly tuned cells don't wash out the rest. Each cell is
+/// labeled with its value, and the smallest one is outlined. Non-finite values are left gray.
+pub fn render_heatmap(heatmap: &Heatmap) -> Document {
+    let (width, height) = heatmap.size();
+    let mut document = Document::new()
+        .set("viewBox", (0, 0, width, height))
+        .set("width", width)
+        .set("height", height)
+        .add(
+            Rectangle::new()
+                .set("x", 0)
+                .set("y", 0)
+                .set("width", width)
+                .set("height", height)
+                .set("fill", "white"),
+        );
+
+    let logs = heatmap
+        .values
+        .iter()
+        .flatten()
+        .filter(|value| value.is_finite() && **value > 0.0)
+        .map(|value| value.log10());
+    let (min, max) = logs.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    let shade = |value: f64| {
+        if max > min {
+            ((value.log10() - min) / (max - min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    };
+
+    let label = |text: String, x: f64, y: f64, anchor: &str| {
+        Text::new(text)
+            .set("x", x)
+            .set("y", y)
+            .set("font-family", FONT_FAMILY)
+            .set("font-size", 12)
+            .set("text-anchor", anchor)
+            .set("fill", "black")
+    };
+    let (cell, margin) = (HEATMAP_CELL as f64, HEATMAP_MARGIN as f64);
+    document = document.add(
+        label(heatmap.title.to_string(), 8.0, 18.0, "start")
+            .set("font-size", 14)
+            .set("font-weight", "bold"),
+    );
+    document = document.add(label(
+        format!("{} →", heatmap.column_name),
+        margin + heatmap.columns.len() as f64 * cell / 2.0,
+        36.0,
+        "middle",
+    ));
+    document = document.add(label(
+        format!("{} ↓", heatmap.row_name),
+        margin / 2.0,
+        36.0,
+        "middle",
+    ));
+    for (column, value) in heatmap.columns.iter().enumerate() {
+        let x = margin + (column as f64 + 0.5) * cell;
+        document = document.add(label(format!("{}", value), x, margin - 8.0, "middle"));
+    }
+    for (row, value) in heatmap.rows.iter().enumerate() {
+        let y = margin + (row as f64 + 0.5) * cell + 4.0;
+        document = document.add(label(format!("{}", value), margin - 8.0, y, "end"));
+    }
+
+    for (row, values) in heatmap.values.iter().enumerate() {
+        for (column, &value) in values.iter().enumerate() {
+            let (x, y) = (margin + column as f64 * cell, margin + row as f64 * cell);
+            let (fill, text_color) = if value.is_finite() && value > 0.0 {
+                let t = shade(value);
+                let [(r0, g0, b0), (r1, g1, b1)] = HEATMAP_COLORS;
+                let mix = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+                let fill = format!("#{:02x}{:02x}{:02x}", mix(r0, r1), mix(g0, g1), mix(b0, b1));
+                (fill, if t > 0.5 { "white" } else { "black" })
+            } else {
+                ("#cccccc".to_string(), "black")
+            };
+            let rectangle = Rectangle::new()
+                .set("x", x)
+                .set("y", y)
+                .set("width", cell)
+                .set("height", cell)
+                .set("fill", fill)
+                .set("stroke", "white")
+                .set("stroke-width", 1);
+            document = document.add(rectangle).add(
+                label(
+                    format!("{:.3}", value),
+                    x + cell / 2.0,
+                    y + cell / 2.0 + 4.0,
+                    "middle",
+                )
+                .set("fill", text_color),
+            );
+        }
+    }
+
+    if let Some((row, column)) = heatmap.minimum() {
+        let outline = Rectangle::new()
+            .set("x", margin + column as f64 * cell + 1.5)
+            .set("y", margin + row as f64 * cell + 1.5)
+            .set("width", cell - 3.0)
+            .set("height", cell - 3.0)
+            .set("fill", "none")
+            .set("stroke", "#D55E00")
+            .set("stroke-width", 3);
+        document = document.add(outline);
+    }
+    document
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    NonFinite { tick: usize, quantity: &'static str },
+    Svg { frame: usize, source: usvg::Error },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::NonFinite { tick, quantity } => {
+                write!(f, "cannot plot non-finite {} at tick {}", quantity, tick)
+            }
+            RenderError::Svg { frame, source } => {
+                write!(f, "frame {} produced an invalid SVG: {}", frame, source)
+            }
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::NonFinite { .. } => None,
+            RenderError::Svg { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Seconds `animate` spent rendering frames, split into its two halves.
+#[derive(Debug, Default, Serialize)]
+pub struct RenderTimings {
+    pub total: f64,
+    /// Building the frames, as SVG documents or, with `fast_render`, directly as pixels
+    pub draw: f64,
+    /// Parsing the SVG documents and rasterizing them
+    pub rasterize: f64,
+}
+
+pub struct AnimateOptions {
+    /// Side length of each pane in pixels
+    pub size: usize,
+    /// Add the error-over-time pane next to the trajectory
+    pub dual_pane: bool,
+    /// Also plot the measurement error in the error pane
+    pub measurement_error: bool,
+    /// Add a strip below the panes plotting the gain `k` and the covariance `p`
+    pub gain_panel: bool,
+    /// Add a strip below the panes plotting the innovations and their autocorrelation
+    pub innovation_panel: bool,
+    /// Only make every this many ticks a frame, before `max_frames` caps them
+    pub frame_stride: usize,
+    /// Render at most this many frames, spread evenly over the run
+    pub max_frames: Option<usize>,
+    /// Pacing of the reveal over the frames
+    pub easing: Easing,
+    /// Pixels kept free of data around each pane
+    pub margin: f64,
+    /// What the main pane shows
+    pub plot: PlotKind,
+    /// Draw the frames straight onto the pixmap, without text, instead of through SVG
+    pub fast_render: bool,
+    pub show_progress: bool,
+}
+
+impl AnimateOptions {
+    /// Number of strips stacked below the panes, out of `gain_panel` and `innovation_panel`.
+    pub fn strips(&self) -> usize {
+        usize::from(self.gain_panel) + usize::from(self.innovation_panel)
+    }
+}
+
+/// Width and height of the frames of panes of `size` pixels, with the error pane of
+/// `dual_pane` and `strips` strips below the panes.
+pub fn frame_size(size: usize, dual_pane: bool, strips: usize) -> (usize, usize) {
+    let width = if dual_pane { size * 2 } else { size };
+    (width, size + strips * strip_height(size))
+}
+
+/// Height of each strip below panes of `size` pixels.
+fn strip_height(size: usize) -> usize {
+    size * 2 / 5
+}
+
+/// Indices of the ticks that become frames when every `frame_stride`th of `tick_count` ticks,
+/// and at most `max_frames` of those, are shown. The picks are spread over the run by `easing`
+/// and always include the last tick, so the final frame shows the whole run. Where the reveal
+/// is slow, consecutive frames may show the same tick.
+pub fn frame_ticks(
+    tick_count: usize,
+    frame_stride: usize,
+    max_frames: Option<usize>,
+    easing: Easing,
+) -> Vec<usize> {
+    let mut strided: Vec<usize> = (0..tick_count).step_by(frame_stride).collect();
+    if let Some(last) = tick_count
+        .checked_sub(1)
+        .filter(|&last| strided.last() != Some(&last))
+    {
+        strided.push(last);
+    }
+    let candidates = strided.len();
+    let frame_count = max_frames.map_or(candidates, |max_frames| max_frames.min(candidates));
+    match frame_count {
+        0 => Vec::new(),
+        1 => vec![tick_count - 1],
+        _ => (0..frame_count)
+            .map(|frame| {
+                let progress = easing.apply(frame as f64 / (frame_count - 1) as f64);
+                strided[(progress * (candidates - 1) as f64).round() as usize]
+            })
+            .collect(),
+    }
+}
+
+/// Axes of every pane, fitted once to a whole run so they stay put over an animation.
+struct Viewports {
+    position: Viewport,
+    error: Viewport,
+    velocity: Viewport,
+    gain: Viewport,
+    /// Range of the covariance `p` in the `gain_panel` strip
+    covariance_range: (f64, f64),
+    /// Range of the innovations and their band in the `innovation_panel` strip
+    innovation_range: (f64, f64),
+    /// Range of the autocorrelation in the `innovation_panel` correlogram
+    correlation_range: (f64, f64),
+}
+
+impl Viewports {
+    fn fit(
+        fit_to: &SimulateResult,
+        render_options: &RenderOptions,
+        animate_options: &AnimateOptions,
+    ) -> Self {
+        let position = Viewport::fit(
+            fit_to,
+            animate_options.size,
+            render_options.log_y,
+            animate_options.margin,
+        );
+        // Keep the error range fixed over the whole animation so the pane doesn't jump around
+        let all_errors = fit_to.ticks.iter().flat_map(|tick| {
+            let measured = measured_error(tick).filter(|_| animate_options.measurement_error);
+            std::iter::once((tick.estimated_positions.1 - tick.true_positions.1).abs())
+                .chain(measured.map(|(_, error)| error))
+        });
+        let max_error = all_errors.clone().fold(0.0, f64::max);
+        let error_range = if render_options.log_y {
+            let min_error = all_errors
+                .filter(|&error| error > 0.0)
+                .fold(f64::INFINITY, f64::min);
+            padded_log((min_error, max_error), 0.05)
+        } else {
+            padded((0.0, max_error), 0.05)
+        };
+        let velocity_range =
+            fit_to
+                .ticks
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), tick| {
+                    (
+                        min.min(tick.true_velocity).min(tick.estimated_velocity),
+                        max.max(tick.true_velocity).max(tick.estimated_velocity),
+                    )
+                });
+        // Keep zero in view so the size of the gain reads in proportion, and fit the reference
+        // line too
+        let gain_range = fit_to
+            .ticks
+            .iter()
+            .map(|tick| tick.gain)
+            .chain(render_options.steady_state_gain)
+            .fold((0.0_f64, f64::NEG_INFINITY), |(min, max), gain| {
+                (min.min(gain), max.max(gain))
+            });
+        let max_covariance = fit_to
+            .ticks
+            .iter()
+            .map(|tick| tick.covariance)
+            .fold(0.0, f64::max);
+        let max_innovation = fit_to
+            .ticks
+            .iter()
+            .filter_map(|tick| tick.innovation)
+            .map(|(innovation, variance)| {
+                innovation
+                    .abs()
+                    .max(INNOVATION_BAND_SIGMAS * variance.sqrt())
+            })
+            .fold(0.0, f64::max);
+        // Scaled to the whole run's bound and correlations, which earlier frames with fewer
+        // innovations may overshoot
+        let max_correlation =
+            metrics::autocorrelation(&metrics::normalized_innovations(fit_to), CORRELOGRAM_LAGS)
+                .map_or(1.0, |correlogram| {
+                    let peak = correlogram.peak().map_or(0.0, |(_, peak)| peak.abs());
+                    (peak.max(correlogram.bound) * 1.5).min(1.0)
+                });
+        Viewports {
+            position,
+            error: Viewport {
+                position_range: error_range,
+                ..position
+            },
+            velocity: Viewport {
+                position_range: padded(velocity_range, 0.05),
+                log_y: false,
+                ..position
+            },
+            gain: Viewport {
+                position_range: padded(gain_range, 0.05),
+                log_y: false,
+                ..position
+            },
+            covariance_range: padded((0.0, max_covariance), 0.05),
+            innovation_range: padded((-max_innovation, max_innovation), 0.05),
+            correlation_range: (-max_correlation, max_correlation),
+        }
+    }
+}
+
+/// Time and absolute error of the measured position of `tick`, if it has one.
+fn measured_error(tick: &SimulateTick) -> Option<(f64, f64)> {
+    tick.measured_positions
+        .map(|(time, measured)| (time, (measured - tick.true_positions.1).abs()))
+}
+
+/// Every series of a run up to the latest tick an animation has revealed.
+#[derive(Default)]
+struct Revealed {
+    true_positions: Vec<(f64, f64)>,
+    measured_positions: Vec<(f64, f64)>,
+    estimated_positions: Vec<(f64, f64)>,
+    estimate_variances: Vec<f64>,
+    true_velocities: Vec<(f64, f64)>,
+    estimated_velocities: Vec<(f64, f64)>,
+    gains: Vec<(f64, f64)>,
+    /// Time, innovation and innovation variance of every measurement
+    innovations: Vec<(f64, f64, f64)>,
+    errors: Vec<(f64, f64)>,
+    measured_errors: Vec<(f64, f64)>,
+}
+
+impl Revealed {
+    fn push(&mut self, tick: &SimulateTick) {
+        let time = tick.true_positions.0;
+        self.true_positions.push(tick.true_positions);
+        self.true_velocities.push((time, tick.true_velocity));
+        self.estimated_velocities
+            .push((time, tick.estimated_velocity));
+        self.gains.push((time, tick.gain));
+        self.innovations.extend(
+            tick.innovation
+                .map(|(innovation, variance)| (time, innovation, variance)),
+        );
+        self.measured_positions.extend(tick.measured_positions);
+        self.estimated_positions.push(tick.estimated_positions);
+        self.estimate_variances.push(tick.covariance);
+        self.errors.push((
+            tick.true_positions.0,
+            (tick.estimated_positions.1 - tick.true_positions.1).abs(),
+        ));
+        self.measured_errors.extend(measured_error(tick));
+    }
+}
+
+/// SVG document of a frame showing the `revealed` series, with markers at the `latest` tick,
+/// or the bare axes without one.
+fn frame_document(
+    revealed: &Revealed,
+    latest: Option<&SimulateTick>,
+    overlays: &[Overlay],
+    cloud: &[f64],
+    viewports: &Viewports,
+    render_options: &RenderOptions,
+    animate_options: &AnimateOptions,
+) -> Document {
+    let mut document = match animate_options.plot {
+        PlotKind::Position => render(
+            &revealed.true_positions,
+            &revealed.measured_positions,
+            &revealed.estimated_positions,
+            &revealed.estimate_variances,
+            overlays,
+            &viewports.position,
+            render_options,
+        ),
+        PlotKind::Velocity => render_velocity(
+            &revealed.true_velocities,
+            &revealed.estimated_velocities,
+            &viewports.velocity,
+            render_options,
+        ),
+        PlotKind::Gain => render_gain(&revealed.gains, &viewports.gain, render_options),
+    };
+    if let Some(tick) = latest {
+        let time = tick.true_positions.0;
+        document = match animate_options.plot {
+            PlotKind::Position => add_particle_cloud(document, time, cloud, &viewports.position)
+                .add(current_position_marker(
+                    tick.true_positions,
+                    "true-marker",
+                    &viewports.position,
+                ))
+                .add(current_position_marker(
+                    tick.estimated_positions,
+                    "estimated-marker",
+                    &viewports.position,
+                )),
+            PlotKind::Velocity => document
+                .add(current_position_marker(
+                    (time, tick.true_velocity),
+                    "true-marker",
+                    &viewports.velocity,
+                ))
+                .add(current_position_marker(
+                    (time, tick.estimated_velocity),
+                    "estimated-marker",
+                    &viewports.velocity,
+                )),
+            PlotKind::Gain => document.add(current_position_marker(
+                (time, tick.gain),
+                "estimated-marker",
+                &viewports.gain,
+            )),
+        };
+        if render_options.time_annotation {
+            document = document.add(time_annotation(
+                render_options.x_annotation(time),
+                animate_options.size,
+            ));
+        }
+    }
+    if animate_options.dual_pane {
+        let error_document = render_error(
+            &revealed.errors,
+            animate_options
+                .measurement_error
+                .then_some(&revealed.measured_errors[..]),
+            &viewports.error,
+            render_options,
+        );
+        document = render_composite(vec![document, error_document], animate_options.size);
+    }
+    let mut strips = Vec::new();
+    if animate_options.gain_panel {
+        strips.push(render_gain_panel(
+            revealed,
+            latest,
+            viewports,
+            render_options,
+            animate_options,
+        ));
+    }
+    if animate_options.innovation_panel {
+        strips.push(render_innovation_panel(
+            revealed,
+            viewports,
+            render_options,
+            animate_options,
+        ));
+    }
+    if !strips.is_empty() {
+        let size = animate_options.size;
+        let (width, height) = frame_size(size, animate_options.dual_pane, strips.len());
+        document = Document::new()
+            .set("viewBox", (0, 0, width, height))
+            .set("width", width)
+            .set("height", height)
+            .add(document.set("x", 0).set("y", 0));
+        for (i, strip) in strips.into_iter().enumerate() {
+            let y = size + i * strip_height(size);
+            document = document.add(strip.set("x", 0).set("y", y));
+        }
+    }
+    document
+}
+
+/// White background of a strip below the panes, ruled off from them at the top.
+fn strip_document(animate_options: &AnimateOptions, palette: &Palette) -> Document {
+    let (width, _) = frame_size(animate_options.size, animate_options.dual_pane, 0);
+    let height = strip_height(animate_options.size);
+    Document::new()
+        .set("viewBox", (0, 0, width, height))
+        .set("width", width)
+        .set("height", height)
+        .add(element::Style::new(palette.stylesheet()))
+        .add(
+            Rectangle::new()
+                .set("x", 0)
+                .set("y", 0)
+                .set("width", width)
+                .set("height", height)
+                .set("fill", "white"),
+        )
+        .add(
+            Line::new()
+                .set("x1", 0)
+                .set("y1", 0.5)
+                .set("x2", width)
+                .set("y2", 0.5)
+                .set("stroke", "#cccccc")
+                .set("stroke-width", 1),
+        )
+}
+
+/// Left edge, top and bottom of the plot of cell `i` of the two in a strip, with room above
+/// it for the label at `top`. The cells are stacked below a single pane, and side by side
+/// below the two of `dual_pane`.
+struct StripCell {
+    left: f64,
+    top: f64,
+    plot_top: f64,
+    plot_bottom: f64,
+}
+
+impl StripCell {
+    fn new(i: usize, animate_options: &AnimateOptions) -> Self {
+        let size = animate_options.size as f64;
+        let height = strip_height(animate_options.size) as f64;
+        let (left, top, cell_height) = if animate_options.dual_pane {
+            (size * i as f64, 0.0, height)
+        } else {
+            let cell_height = height / 2.0;
+            (0.0, cell_height * i as f64, cell_height)
+        };
+        StripCell {
+            left,
+            top,
+            plot_top: top + 26.0,
+            plot_bottom: top + cell_height - 6.0,
+        }
+    }
+
+    /// Vertical position of `value` in the plot scaled to `(min, max)`.
+    fn y(&self, value: f64, (min, max): (f64, f64)) -> f64 {
+        self.plot_bottom - (value - min) / (max - min) * (self.plot_bottom - self.plot_top)
+    }
+
+    fn label(&self, label: String, color: &str) -> Text {
+        Text::new(label)
+            .set("x", self.left + 8.0)
+            .set("y", self.top + 18.0)
+            .set("font-family", FONT_FAMILY)
+            .set("font-size", 12)
+            .set("fill", color)
+    }
+}
+
+/// Renders the `gain_panel` strip: the gain `k` and the covariance `p` of the `revealed`
+/// ticks, each in its own cell on the time axis of the pane above it and scaled to its own
+/// range, labeled with its value at the `latest` tick.
+fn render_gain_panel(
+    revealed: &Revealed,
+    latest: Option<&SimulateTick>,
+    viewports: &Viewports,
+    render_options: &RenderOptions,
+    animate_options: &AnimateOptions,
+) -> Document {
+    let covariances: Vec<(f64, f64)> = revealed
+        .estimated_positions
+        .iter()
+        .zip(&revealed.estimate_variances)
+        .map(|(&(time, _), &p)| (time, p))
+        .collect();
+    let palette = &render_options.palette;
+    let cells = [
+        (
+            &revealed.gains,
+            latest.map(|tick| tick.gain),
+            viewports.gain.position_range,
+            "gain k",
+            (
+                palette.estimate.class,
+                "estimated-marker",
+                palette.estimate.color,
+            ),
+        ),
+        (
+            &covariances,
+            latest.map(|tick| tick.covariance),
+            viewports.covariance_range,
+            "covariance p",
+            ("covariance-line", "covariance-marker", COVARIANCE_COLOR),
+        ),
+    ];
+
+    let mut document = strip_document(animate_options, palette);
+    for (i, (points, value, range, name, (class, marker_class, color))) in
+        cells.into_iter().enumerate()
+    {
+        let cell = StripCell::new(i, animate_options);
+        let x = |time: f64| strip_x(&cell, time, viewports);
+        document = document.add(strip_line(
+            points
+                .iter()
+                .map(|&(time, value)| (x(time), cell.y(value, range))),
+            class,
+            render_options,
+        ));
+
+        let label = match (value, points.last()) {
+            (Some(value), Some(&(time, _))) => {
+                let marker = Circle::new()
+                    .set("cx", x(time))
+                    .set("cy", cell.y(value, range))
+                    .set("r", 4.0)
+                    .set("class", marker_class);
+                document = document.add(marker);
+                format!("{} = {:.4}", name, value)
+            }
+            _ => name.to_string(),
+        };
+        document = document.add(cell.label(label, color));
+    }
+    document
+}
+
+/// Horizontal position of `time` in `cell`, on the time axis of the pane above it.
+fn strip_x(cell: &StripCell, time: f64, viewports: &Viewports) -> f64 {
+    let size = viewports.position.size as f64;
+    let margin = viewports.position.margin;
+    let (min_time, max_time) = viewports.position.time_range;
+    cell.left + margin + (time - min_time) / (max_time - min_time) * (size - 2.0 * margin)
+}
+
+/// Polyline through the pixel `points`, styled by the CSS `class`.
+fn strip_line(
+    points: impl Iterator<Item = (f64, f64)>,
+    class: &str,
+    render_options: &RenderOptions,
+) -> Polyline {
+    let coordinates = points
+        .map(|(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Polyline::new()
+        .set("points", coordinates)
+        .set("class", class)
+        .set("stroke-linejoin", render_options.line_join.svg_name())
+        .set("stroke-linecap", render_options.line_cap.svg_name())
+}
+
+/// Renders the `innovation_panel` strip from the `revealed` measurements: the innovation
+/// `measurement - prediction` over time inside its `±2√S` band, on the time axis of the pane
+/// above it, and a correlogram of the normalized innovations `ν / √S` at lags 1 to 10 with the
+/// `±1.96 / √n` bounds a white sequence stays in. Innovations that wander outside the band,
+/// or bars that poke through the bounds, point at a mistuned `q` or `r`, and the label gives
+/// the Ljung-Box verdict over all the lags.
+fn render_innovation_panel(
+    revealed: &Revealed,
+    viewports: &Viewports,
+    render_options: &RenderOptions,
+    animate_options: &AnimateOptions,
+) -> Document {
+    let palette = &render_options.palette;
+    let mut document = strip_document(animate_options, palette);
+
+    let cell = StripCell::new(0, animate_options);
+    let range = viewports.innovation_range;
+    let x = |time: f64| strip_x(&cell, time, viewports);
+    if revealed.innovations.len() >= 2 {
+        let upper = revealed
+            .innovations
+            .iter()
+            .map(|&(time, _, variance)| (time, INNOVATION_BAND_SIGMAS * variance.sqrt()));
+        let lower = upper
+            .clone()
+            .rev()
+            .map(|(time, half_width)| (time, -half_width));
+        let outline = upper
+            .chain(lower)
+            .map(|(time, y)| format!("{},{}", x(time), cell.y(y, range)))
+            .collect::<Vec<_>>()
+            .join(" L ");
+        let band = element::Path::new()
+            .set("d", format!("M {} Z", outline))
+            .set("class", "covariance-band");
+        document = document.add(band);
+    }
+    let zero = Line::new()
+        .set("x1", x(viewports.position.time_range.0))
+        .set("y1", cell.y(0.0, range))
+        .set("x2", x(viewports.position.time_range.1))
+        .set("y2", cell.y(0.0, range))
+        .set("stroke", "#cccccc")
+        .set("stroke-width", 1);
+    document = document.add(zero);
+    document = document.add(strip_line(
+        revealed
+            .innovations
+            .iter()
+            .map(|&(time, innovation, _)| (x(time), cell.y(innovation, range))),
+        palette.estimate.class,
+        render_options,
+    ));
+    let label = match revealed.innovations.last() {
+        Some(&(_, innovation, _)) => format!("innovation ν = {:.4}", innovation),
+        None => "innovation ν".to_string(),
+    };
+    document = document.add(cell.label(label, palette.estimate.color));
+
+    let cell = StripCell::new(1, animate_options);
+    let range = viewports.correlation_range;
+    let size = animate_options.size as f64;
+    let margin = viewports.position.margin;
+    let slot = (size - 2.0 * margin) / CORRELOGRAM_LAGS as f64;
+    let clamp = |correlation: f64| cell.y(correlation.clamp(range.0, range.1), range);
+    let normalized: Vec<f64> = revealed
+        .innovations
+        .iter()
+        .map(|&(_, innovation, variance)| innovation / variance.sqrt())
+        .collect();
+    let label = match metrics::ljung_box(&normalized, CORRELOGRAM_LAGS) {
+        Some(test) => {
+            let correlogram = &test.autocorrelation;
+            for (i, &correlation) in correlogram.correlations.iter().enumerate() {
+                let (top, bottom) = if correlation >= 0.0 {
+                    (clamp(correlation), clamp(0.0))
+                } else {
+                    (clamp(0.0), clamp(correlation))
+                };
+                let color = if correlation.abs() > correlogram.bound {
+                    palette.error.color
+                } else {
+                    palette.estimate.color
+                };
+                let bar = Rectangle::new()
+                    .set("x", cell.left + margin + slot * (i as f64 + 0.2))
+                    .set("y", top)
+                    .set("width", slot * 0.6)
+                    .set("height", bottom - top)
+                    .set("fill", color);
+                document = document.add(bar);
+            }
+            for bound in [correlogram.bound, -correlogram.bound] {
+                let line = Line::new()
+                    .set("x1", cell.left + margin)
+                    .set("y1", clamp(bound))
+                    .set("x2", cell.left + size - margin)
+                    .set("y2", clamp(bound))
+                    .set("stroke", COVARIANCE_COLOR)
+                    .set("stroke-width", 1)
+                    .set("stroke-dasharray", "4 3");
+                document = document.add(line);
+            }
+            let verdict = if test.passed() { "white" } else { "correlated" };
+            format!(
+                "autocorrelation, lags 1-{}: Ljung-Box Q = {:.1} vs {:.1}, {}",
+                CORRELOGRAM_LAGS, test.q, test.threshold, verdict
+            )
+        }
+        None => "autocorrelation".to_string(),
+    };
+    let zero = Line::new()
+        .set("x1", cell.left + margin)
+        .set("y1", clamp(0.0))
+        .set("x2", cell.left + size - margin)
+        .set("y2", clamp(0.0))
+        .set("stroke", "#cccccc")
+        .set("stroke-width", 1);
+    document.add(zero).add(cell.label(label, COVARIANCE_COLOR))
+}
+
+/// Fails on the first tick of `result` with a non-finite quantity, which would end up as
+/// `NaN` attributes in the SVG.
+fn check_finite(result: &SimulateResult) -> Result<(), RenderError> {
+    for (tick, data) in result.ticks.iter().enumerate() {
+        if let Some(quantity) = data.non_finite_quantity() {
+            return Err(RenderError::NonFinite { tick, quantity });
+        }
+    }
+    Ok(())
+}
+
+/// Options for rasterizing SVG documents, with the system fonts loaded for their text.
+fn svg_options() -> usvg::Options<'static> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    options
+}
+
+/// Rasterizes `document` onto a `width` by `height` pixmap.
+fn rasterize_svg(
+    document: &Document,
+    options: &usvg::Options,
+    width: usize,
+    height: usize,
+) -> Result<Pixmap, usvg::Error> {
+    let tree = usvg::Tree::from_str(&document.to_string(), options)?;
+    let mut pixmap = Pixmap::new(width as u32, height as u32).unwrap();
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+/// Renders the final frame of `result` on its own as an SVG document: the whole run, every
+/// overlay and the last particle cloud, on axes fitted to `result`. `frame_stride`,
+/// `max_frames`, `easing` and `fast_render` don't apply.
+pub fn render_final(
+    result: &SimulateResult,
+    overlays: &[Overlay],
+    particle_clouds: &[Vec<f64>],
+    render_options: &RenderOptions,
+    animate_options: &AnimateOptions,
+) -> Result<Document, RenderError> {
+    check_finite(result)?;
+    let viewports = Viewports::fit(result, render_options, animate_options);
+    let mut revealed = Revealed::default();
+    for tick in &result.ticks {
+        revealed.push(tick);
+    }
+    let cloud = particle_clouds.last().map_or(&[][..], Vec::as_slice);
+    Ok(frame_document(
+        &revealed,
+        result.ticks.last(),
+        overlays,
+        cloud,
+        &viewports,
+        render_options,
+        animate_options,
+    ))
+}
+
+/// Rasterizes `document`, such as one from `render_final`, into a `width` by `height` image.
+pub fn rasterize(
+    document: &Document,
+    width: usize,
+    height: usize,
+) -> Result<RgbaImage, RenderError> {
+    let pixmap = rasterize_svg(document, &svg_options(), width, height)
+        .map_err(|source| RenderError::Svg { frame: 0, source })?;
+    Ok(RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixmap.take()).unwrap())
+}
+
+/// Renders the frames of `result`, with the axes fitted to the ticks of `fit_to` so several
+/// animations can share a scale. Pass `result` itself to fit the axes to its own data. Each of
+/// the `overlays` has one point per tick of `result`, and `particle_clouds` either holds the
+/// particle positions at every tick, drawn at the latest tick of the position plot, or is
+/// empty. The time spent drawing and rasterizing the frames is added to `timings`.
+///
+/// SVG frames are rasterized in parallel on the current rayon thread pool, and come out in
+/// order whatever the thread count.
+pub fn animate(
+    result: &SimulateResult,
+    fit_to: &SimulateResult,
+    overlays: &[Overlay],
+    particle_clouds: &[Vec<f64>],
+    render_options: &RenderOptions,
+    animate_options: &AnimateOptions,
+    timings: &mut RenderTimings,
+) -> Result<Vec<Frame>, RenderError> {
+    let AnimateOptions {
+        size,
+        dual_pane,
+        frame_stride,
+        max_frames,
+        easing,
+        plot,
+        fast_render,
+        show_progress,
+        ..
+    } = *animate_options;
+
+    check_finite(result)?;
+
+    let mut frames = Vec::new();
+    let options = svg_options();
+
+    let (width, height) = frame_size(size, dual_pane, animate_options.strips());
+
+    let viewports = Viewports::fit(fit_to, render_options, animate_options);
+
+    let to_frame = |pixmap: Pixmap| {
+        let (width, height) = (pixmap.width(), pixmap.height());
+        Frame::new(RgbaImage::from_raw(width, height, pixmap.take()).unwrap())
+    };
+    // Rasterizes the `pending` frames in parallel, then appends them in order, each repeated
+    // as often as it was picked
+    let rasterize = |pending: &mut Vec<(usize, Document, usize)>,
+                     frames: &mut Vec<Frame>,
+                     timings: &mut RenderTimings|
+     -> Result<(), RenderError> {
+        let start = Instant::now();
+        let pixmaps = pending
+            .par_iter()
+            .map(|(frame, document, _)| {
+                rasterize_svg(document, &options, width, height).map_err(|source| {
+                    RenderError::Svg {
+                        frame: *frame,
+                        source,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        timings.rasterize += start.elapsed().as_secs_f64();
+        for ((_, _, repeats), pixmap) in pending.drain(..).zip(pixmaps) {
+            frames.extend(std::iter::repeat_n(to_frame(pixmap), repeats));
+        }
+        Ok(())
+    };
+
+    // Without ticks there is nothing to reveal, but the bare axes still make a valid
+    // single-frame animation, where no frames at all would leave the GIF empty
+    if result.ticks.is_empty() {
+        let document = frame_document(
+            &Revealed::default(),
+            None,
+            &[],
+            &[],
+            &viewports,
+            render_options,
+            animate_options,
+        );
+        rasterize(&mut vec![(0, document, 1)], &mut frames, timings)?;
+        return Ok(frames);
+    }
+
+    let frame_ticks = frame_ticks(result.ticks.len(), frame_stride, max_frames, easing);
+    let mut next_frame = frame_ticks.iter().peekable();
+
+    let mut revealed = Revealed::default();
+    // The fast renderer paints each frame's new stretch of plot onto the previous one
+    let mut canvas = fast_render.then(|| {
+        let viewport = match plot {
+            PlotKind::Position => &viewports.position,
+            PlotKind::Velocity => &viewports.velocity,
+            PlotKind::Gain => &viewports.gain,
+        };
+        Canvas::new(viewport, render_options)
+    });
+    let mut pending = Vec::with_capacity(RASTER_BATCH);
+    let mut frame_count = 0;
+    for (i, tick) in result.ticks.iter().enumerate() {
+        let time = tick.true_positions.0;
+        revealed.push(tick);
+
+        let mut repeats = 0;
+        while next_frame.next_if_eq(&&i).is_some() {
+            repeats += 1;
+        }
+        if repeats == 0 {
+            continue;
+        }
+        let frame = frame_count;
+        frame_count += repeats;
+        if show_progress && frame % 10 == 9 {
+            eprintln!("{}/{} frames", frame + 1, frame_ticks.len());
+        }
+
+        let revealed_overlays: Vec<_> = overlays
+            .iter()
+            .map(|overlay| Overlay {
+                points: &overlay.points[..=i],
+                ..*overlay
+            })
+            .collect();
+        let cloud = particle_clouds.get(i).map_or(&[][..], Vec::as_slice);
+        let start = Instant::now();
+        if let Some(canvas) = &mut canvas {
+            let palette = &render_options.palette;
+            let snapshot = match plot {
+                PlotKind::Position => {
+                    canvas.position_plot(
+                        &revealed.true_positions,
+                        &revealed.measured_positions,
+                        &revealed.estimated_positions,
+                        &revealed.estimate_variances,
+                        &revealed_overlays,
+                    );
+                    let mut snapshot = canvas.snapshot();
+                    snapshot.particle_cloud(time, cloud);
+                    snapshot.current_position_marker(tick.true_positions, palette.truth.color);
+                    snapshot
+                        .current_position_marker(tick.estimated_positions, palette.estimate.color);
+                    snapshot
+                }
+                PlotKind::Velocity => {
+                    canvas.velocity_plot(&revealed.true_velocities, &revealed.estimated_velocities);
+                    let mut snapshot = canvas.snapshot();
+                    snapshot
+                        .current_position_marker((time, tick.true_velocity), palette.truth.color);
+                    snapshot.current_position_marker(
+                        (time, tick.estimated_velocity),
+                        palette.estimate.color,
+                    );
+                    snapshot
+                }
+                PlotKind::Gain => {
+                    canvas.gain_plot(&revealed.gains);
+                    let mut snapshot = canvas.snapshot();
+                    snapshot.current_position_marker((time, tick.gain), palette.estimate.color);
+                    snapshot
+                }
+            };
+            frames.extend(std::iter::repeat_n(
+                to_frame(snapshot.into_pixmap()),
+                repeats,
+            ));
+            timings.draw += start.elapsed().as_secs_f64();
+            continue;
+        }
+
+        let document = frame_document(
+            &revealed,
+            Some(tick),
+            &revealed_overlays,
+            cloud,
+            &viewports,
+            render_options,
+            animate_options,
+        );
+        timings.draw += start.elapsed().as_secs_f64();
+
+        pending.push((frame, document, repeats));
+        if pending.len() == RASTER_BATCH {
+            rasterize(&mut pending, &mut frames, timings)?;
+        }
+    }
+    rasterize(&mut pending, &mut frames, timings)?;
+
+    Ok(frames)
+}
+
+/// Places each frame of `right` to the right of the matching frame of `left`.
+pub fn composite_frames(left: Vec<Frame>, right: Vec<Frame>) -> Vec<Frame> {
+    left.into_iter()
+        .zip(right)
+        .map(|(left, right)| {
+            let (left, right) = (left.into_buffer(), right.into_buffer());
+            let mut image = RgbaImage::new(
+                left.width() + right.width(),
+                left.height().max(right.height()),
+            );
+            image::imageops::replace(&mut image, &left, 0, 0);
+            image::imageops::replace(&mut image, &right, left.width() as i64, 0);
+            Frame::new(image)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+    use std::{env, fs};
+
+    use super::*;
+    use crate::sim::tests::{config, run};
+    use crate::sim::SimulateConfig;
+    use crate::trajectory::Trajectory;
+
+    /// Plain position plot of the built-in palette, without any overlay.
+    pub(crate) fn render_options() -> RenderOptions {
+        RenderOptions {
+            time_unit: "s".to_string(),
+            x_axis: XAxis::Time,
+            scale_bar: false,
+            log_y: false,
+            time_annotation: true,
+            regime_changes: Vec::new(),
+            marker_shape: MarkerShape::Circle,
+            passthrough: false,
+            error_bars: None,
+            noise_bands: None,
+            covariance_band: None,
+            steady_state_gain: None,
+            grid_lines: 0,
+            axes: false,
+            palette: Theme::OkabeIto.palette(),
+            line_join: LineJoin::Round,
+            line_cap: LineCap::Round,
+        }
+    }
+
+    /// Single pane of `size` pixels with one frame per tick.
+    pub(crate) fn animate_options(size: usize) -> AnimateOptions {
+        AnimateOptions {
+            size,
+            dual_pane: false,
+            measurement_error: false,
+            gain_panel: false,
+            innovation_panel: false,
+            frame_stride: 1,
+            max_frames: None,
+            easing: Easing::Linear,
+            margin: 0.0,
+            plot: PlotKind::Position,
+            fast_render: false,
+            show_progress: false,
+        }
+    }
+
+    /// Frames of `result` animated with `animate_options`.
+    fn frames(result: &SimulateResult, animate_options: &AnimateOptions) -> Vec<Frame> {
+        let mut timings = RenderTimings::default();
+        animate(
+            result,
+            result,
+            &[],
+            &[],
+            &render_options(),
+            animate_options,
+            &mut timings,
+        )
+        .unwrap()
+    }
+
+    /// SVG document of the frame at every tick of `result`, before rasterizing.
+    fn frame_documents(result: &SimulateResult, render_options: &RenderOptions) -> Vec<Document> {
+        let animate_options = animate_options(200);
+        let viewports = Viewports::fit(result, render_options, &animate_options);
+        let mut revealed = Revealed::default();
+        result
+            .ticks
+            .iter()
+            .map(|tick| {
+                revealed.push(tick);
+                frame_document(
+                    &revealed,
+                    Some(tick),
+                    &[],
+                    &[],
+                    &viewports,
+                    render_options,
+                    &animate_options,
+                )
+            })
+            .collect()
+    }
+
+    /// Compares `actual` with the checked-in file `testdata/<name>`, or rewrites that file
+    /// instead when `UPDATE_GOLDEN` is set.
+    fn assert_golden(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "{}: {} (run with UPDATE_GOLDEN=1 to create it)",
+                path.display(),
+                err
+            )
+        });
+        assert!(
+            actual == expected,
+            "{} differs from the output (run with UPDATE_GOLDEN=1 to accept it)",
+            path.display()
+        );
+    }
+
+    /// 64-bit FNV-1a hash of the dimensions and pixels of every frame of `result` animated
+    /// with `animate_options`, one hexadecimal hash per line.
+    fn frame_hashes(result: &SimulateResult, animate_options: &AnimateOptions) -> String {
+        frames(result, animate_options)
+            .iter()
+            .map(|frame| {
+                let buffer = frame.buffer();
+                let (width, height) = buffer.dimensions();
+                let bytes = width.to_le_bytes().into_iter().chain(height.to_le_bytes());
+                let hash = bytes
+                    .chain(buffer.as_raw().iter().copied())
+                    .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+                    });
+                format!("{:016x}\n", hash)
+            })
+            .collect()
+    }
+
+    /// Number of elements of `document` with the CSS class `class`.
+    fn count_class(document: &Document, class: &str) -> usize {
+        document
+            .to_string()
+            .matches(&format!("class=\"{}\"", class))
+            .count()
+    }
+
+    /// Pixel coordinates of the polyline of `svg` with the CSS class `class`.
+    fn polyline_points(svg: &str, class: &str) -> Vec<(f64, f64)> {
+        let line = svg
+            .lines()
+            .find(|line| line.contains(&format!("class=\"{}\"", class)))
+            .unwrap_or_else(|| panic!("no {} in the document", class));
+        let (_, points) = line.split_once("points=\"").unwrap();
+        let (points, _) = points.split_once('"').unwrap();
+        points
+            .split(' ')
+            .map(|point| {
+                let (x, y) = point.split_once(',').unwrap();
+                (x.parse().unwrap(), y.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn estimate_error_is_drawn_below_the_measurement_error() {
+        let result = run(&config(), 1);
+        let errors: Vec<(f64, f64)> = result
+            .ticks
+            .iter()
+            .map(|tick| {
+                let (time, truth) = tick.true_positions;
+                (time, (tick.estimated_positions.1 - truth).abs())
+            })
+            .collect();
+        let measured_errors: Vec<(f64, f64)> = result
+            .ticks
+            .iter()
+            .filter_map(|tick| {
+                let (time, measured) = tick.measured_positions?;
+                Some((time, (measured - tick.true_positions.1).abs()))
+            })
+            .collect();
+        let max_error = measured_errors
+            .iter()
+            .map(|&(_, error)| error)
+            .fold(0.0, f64::max);
+        let viewport = Viewport {
+            size: 200,
+            time_range: (0.0, 10.0),
+            position_range: (0.0, max_error),
+            log_y: false,
+            margin: 10.0,
+        };
+        let svg = render_error(
+            &errors,
+            Some(&measured_errors),
+            &viewport,
+            &render_options(),
+        )
+        .to_string();
+
+        let estimated = polyline_points(&svg, "error-line");
+        let measured = polyline_points(&svg, "measured-line");
+        assert_eq!(estimated.len(), errors.len());
+        assert_eq!(measured.len(), measured_errors.len());
+        // SVG y grows downwards, so a smaller error sits lower in the pane
+        let mean_y = |points: &[(f64, f64)]| {
+            points.iter().map(|&(_, y)| y).sum::<f64>() / points.len() as f64
+        };
+        assert!(mean_y(&estimated) > mean_y(&measured));
+        let below = estimated
+            .iter()
+            .zip(&measured)
+            .filter(|(estimated, measured)| estimated.1 >= measured.1)
+            .count();
+        assert!(
+            below * 3 > estimated.len() * 2,
+            "{} of {}",
+            below,
+            estimated.len()
+        );
+    }
+
+    #[test]
+    fn error_bars_span_one_sigma_either_side() {
+        let result = run(&config(), 1);
+        let render_options = RenderOptions {
+            error_bars: Some((SensorModel::Additive, 4.0)),
+            ..render_options()
+        };
+        let viewport = Viewports::fit(&result, &render_options, &animate_options(200)).position;
+        let pixels_per_unit = viewport.y(0.0) - viewport.y(1.0);
+        let svg = frame_documents(&result, &render_options)
+            .pop()
+            .unwrap()
+            .to_string();
+
+        let bars: Vec<&str> = svg
+            .lines()
+            .filter(|line| line.contains("class=\"measured-error-bar\""))
+            .collect();
+        assert_eq!(bars.len(), result.ticks.len());
+        for bar in bars {
+            let (_, path) = bar.split_once("d=\"").unwrap();
+            let (path, _) = path.split_once('"').unwrap();
+            let numbers: Vec<f64> = path
+                .split(' ')
+                .filter_map(|token| token.parse().ok())
+                .collect();
+            // M x0 low H x1 M x low V high M x0 high H x1
+            let (low, high) = (numbers[4], numbers[5]);
+            let half_length = (low - high) / 2.0 / pixels_per_unit;
+            assert!((half_length - 2.0).abs() < 1e-9, "{}", half_length);
+        }
+    }
+
+    #[test]
+    fn a_time_range_draws_only_its_ticks_across_the_whole_pane() {
+        let full = run(&config(), 1);
+        let range = full.within(3.0, 5.0);
+        let times: Vec<f64> = range
+            .ticks
+            .iter()
+            .map(|tick| tick.true_positions.0)
+            .collect();
+        assert_eq!(times.len(), 21);
+        assert!(times.iter().all(|time| (3.0..=5.0).contains(time)));
+
+        let document =
+            render_final(&range, &[], &[], &render_options(), &animate_options(200)).unwrap();
+        assert_eq!(count_class(&document, "measured-dot"), 21);
+        let points = polyline_points(&document.to_string(), "true-line");
+        assert_eq!(points.len(), 21);
+        // The axes fit the range, so its ends sit at the padded edges of the pane
+        let viewport = Viewport::fit(&range, 200, false, 0.0);
+        assert_eq!(viewport.time_range, (2.96, 5.04));
+        assert!((points[0].0 - 200.0 * 0.04 / 2.08).abs() < 1e-9);
+        assert!((points[20].0 - 200.0 * 2.04 / 2.08).abs() < 1e-9);
+    }
+
+    #[test]
+    fn noise_bands_emit_all_three_bands_widest_first() {
+        let result = run(&config(), 1);
+        let noise_bands = NoiseBands {
+            sensor: SensorModel::Additive,
+            q: 0.01,
+            r: 4.0,
+        };
+        let render_options = RenderOptions {
+            noise_bands: Some(noise_bands),
+            ..render_options()
+        };
+        let document = frame_documents(&result, &render_options).pop().unwrap();
+        for class in ["p-band", "q-band", "r-band"] {
+            assert_eq!(count_class(&document, class), 1, "{}", class);
+        }
+
+        // With r far above q, ±√r is widest and ±√q narrowest
+        let svg = document.to_string();
+        let position = |class: &str| svg.find(&format!("class=\"{}\"", class)).unwrap();
+        assert!(position("r-band") < position("p-band"));
+        assert!(position("p-band") < position("q-band"));
+    }
+
+    #[test]
+    fn steady_state_line_sits_at_the_computed_gain() {
+        let config = config();
+        let gain = metrics::steady_state_gain(&config).unwrap();
+        let result = run(&config, 1);
+        let gains: Vec<(f64, f64)> = result
+            .ticks
+            .iter()
+            .map(|tick| (tick.true_positions.0, tick.gain))
+            .collect();
+        let viewport = Viewport {
+            size: 200,
+            time_range: (0.0, 10.0),
+            position_range: (0.0, 0.5),
+            log_y: false,
+            margin: 20.0,
+        };
+        let render_options = RenderOptions {
+            steady_state_gain: Some(gain),
+            ..render_options()
+        };
+        let svg = render_gain(&gains, &viewport, &render_options).to_string();
+
+        let line = svg
+            .lines()
+            .find(|line| line.starts_with("<line") && line.contains("stroke-dasharray"))
+            .unwrap();
+        let attribute = |name: &str| -> f64 {
+            let (_, value) = line.split_once(&format!(" {}=\"", name)).unwrap();
+            value.split_once('"').unwrap().0.parse().unwrap()
+        };
+        // 20 px of margin, then 160 px for gains from 0.5 at the top down to 0
+        let expected = 20.0 + (0.5 - gain) / 0.5 * 160.0;
+        assert!((attribute("y1") - expected).abs() < 1e-9);
+        assert_eq!(attribute("y1"), attribute("y2"));
+        assert!(svg.contains(&format!("steady state k = {:.4}", gain)));
+        // The filter's gain does converge onto the line
+        assert!((gains.last().unwrap().1 - gain).abs() < 1e-3);
+    }
+
+    #[test]
+    fn animation_frames_match_the_golden_hashes() {
+        let result = run(
+            &SimulateConfig {
+                total_time: 1.0,
+                ..config()
+            },
+            1,
+        );
+        // The direct raster draws no text, so the pixels don't depend on the installed fonts
+        let animate_options = AnimateOptions {
+            fast_render: true,
+            ..animate_options(48)
+        };
+        let hashes = frame_hashes(&result, &animate_options);
+        assert_eq!(hashes.lines().count(), 10);
+        assert_golden("animate_frames.hashes", &hashes);
+    }
+
+    #[test]
+    fn dual_pane_frames_are_two_panes_wide() {
+        let result = run(&config(), 1);
+        let single = AnimateOptions {
+            max_frames: Some(2),
+            ..animate_options(64)
+        };
+        let dual = AnimateOptions {
+            dual_pane: true,
+            ..single
+        };
+        assert_eq!(frame_size(64, true, 0), (128, 64));
+        for frame in frames(&result, &single) {
+            assert_eq!(frame.buffer().dimensions(), (64, 64));
+        }
+        for frame in frames(&result, &dual) {
+            assert_eq!(frame.buffer().dimensions(), (128, 64));
+        }
+    }
+
+    #[test]
+    fn every_frame_marks_the_current_positions_once() {
+        let result = run(&config(), 1);
+        for document in frame_documents(&result, &render_options()) {
+            assert_eq!(count_class(&document, "true-marker"), 1);
+            assert_eq!(count_class(&document, "estimated-marker"), 1);
+        }
+    }
+
+    #[test]
+    fn viewport_fits_a_negative_start() {
+        let result = run(
+            &SimulateConfig {
+                initial_position: -50.0,
+                ..config()
+            },
+            1,
+        );
+        let viewport = Viewport::fit(&result, 200, false, 0.0);
+        for tick in &result.ticks {
+            let (time, position) = tick.true_positions;
+            assert!((0.0..=200.0).contains(&viewport.x(time)));
+            assert!((0.0..=200.0).contains(&viewport.y(position)));
+        }
+        assert!(viewport.position_range.0 < -50.0);
+    }
+
+    #[test]
+    fn axis_label_names_the_time_unit() {
+        let result = run(&config(), 1);
+        let render_options = RenderOptions {
+            time_unit: "ms".to_string(),
+            ..render_options()
+        };
+        let documents = frame_documents(&result, &render_options);
+        assert!(documents[0].to_string().contains("time [ms]"));
+        assert_eq!(XAxis::Time.column_name("ms"), "time[ms]");
+    }
+
+    #[test]
+    fn render_final_matches_the_golden_svg() {
+        let result = run(&config(), 1);
+        let document =
+            render_final(&result, &[], &[], &render_options(), &animate_options(200)).unwrap();
+        assert_golden("render_final.svg", &document.to_string());
+    }
+
+    #[test]
+    fn scale_bar_labels_its_round_length() {
+        assert_eq!(round_length(3.7), 2.0);
+        assert_eq!(round_length(73.0), 50.0);
+        assert_eq!(round_length(10.0), 10.0);
+
+        let result = run(&config(), 1);
+        let viewport = Viewport::fit(&result, 200, false, 0.0);
+        let time_length = round_length(viewport.plot_size() / 5.0 / viewport.time_scale());
+        let position_length = round_length(viewport.plot_size() / 5.0 / viewport.position_scale());
+        let svg = add_scale_bar(Document::new(), &viewport, "s").to_string();
+        assert!(svg.contains(&format!("\n{} s\n", time_length)));
+        assert!(svg.contains(&format!("\n{} units\n", position_length)));
+    }
+
+    #[test]
+    fn non_finite_ticks_fail_to_render() {
+        let mut result = run(&config(), 1);
+        result.ticks[3].estimated_positions.1 = f64::NAN;
+        let error = render_final(&result, &[], &[], &render_options(), &animate_options(200));
+        assert!(matches!(
+            error.err(),
+            Some(RenderError::NonFinite { tick: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn log_axis_is_monotonic_with_even_decades() {
+        let viewport = Viewport {
+            size: 200,
+            time_range: (0.0, 10.0),
+            position_range: (0.01, 1000.0),
+            log_y: true,
+            margin: 10.0,
+        };
+        let ys: Vec<f64> = (-20..=30)
+            .map(|exponent| viewport.y(10f64.powf(exponent as f64 / 10.0)))
+            .collect();
+        assert!(ys.windows(2).all(|pair| pair[1] < pair[0]));
+        let decade = viewport.y(0.1) - viewport.y(1.0);
+        for position in [1.0, 10.0, 100.0] {
+            let pixels = viewport.y(position) - viewport.y(position * 10.0);
+            assert!((pixels - decade).abs() < 1e-9, "{} vs {}", pixels, decade);
+        }
+        assert!((decade - viewport.plot_size() / 5.0).abs() < 1e-9);
+        assert_eq!(viewport.y(-1.0), viewport.y(0.01));
+    }
+
+    #[test]
+    fn time_annotation_names_the_latest_tick() {
+        let result = run(&config(), 1);
+        let documents = frame_documents(&result, &render_options());
+        for index in [0, 34, result.ticks.len() - 1] {
+            let label = format!("t = {:.2} s", result.ticks[index].true_positions.0);
+            assert!(documents[index].to_string().contains(&label), "{}", label);
+        }
+        assert!(documents[34].to_string().contains("t = 3.40 s"));
+
+        let untimed = RenderOptions {
+            time_annotation: false,
+            ..render_options()
+        };
+        assert!(!frame_documents(&result, &untimed)[34]
+            .to_string()
+            .contains("t = "));
+    }
+
+    #[test]
+    fn every_easing_reveals_the_whole_run_by_the_last_frame() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            for (stride, max_frames) in [(1, None), (3, None), (1, Some(40)), (7, Some(9))] {
+                let ticks = frame_ticks(100, stride, max_frames, easing);
+                assert_eq!(ticks.first(), Some(&0), "{:?}", easing);
+                assert_eq!(ticks.last(), Some(&99), "{:?}", easing);
+                assert!(ticks.windows(2).all(|pair| pair[0] <= pair[1]));
+            }
+        }
+        // Easing in spends the first half of the frames on under half of the run
+        let eased = frame_ticks(100, 1, Some(50), Easing::EaseIn);
+        assert!(eased[25] < 30, "{}", eased[25]);
+    }
+
+    #[test]
+    fn empty_and_one_step_runs_render_valid_plots() {
+        let empty = SimulateResult::default();
+        let one_step = run(
+            &SimulateConfig {
+                total_time: 0.1,
+                ..config()
+            },
+            1,
+        );
+        assert_eq!(one_step.ticks.len(), 1);
+        for (result, dots) in [(&empty, 0), (&one_step, 1)] {
+            for dual_pane in [false, true] {
+                let options = AnimateOptions {
+                    dual_pane,
+                    ..animate_options(32)
+                };
+                let frames = frames(result, &options);
+                assert_eq!(frames.len(), 1);
+                let width = if dual_pane { 64 } else { 32 };
+                assert_eq!(frames[0].buffer().dimensions(), (width, 32));
+            }
+
+            let document =
+                render_final(result, &[], &[], &render_options(), &animate_options(200)).unwrap();
+            let svg = document.to_string();
+            assert!(svg.contains("position"), "axes missing from {}", svg);
+            assert!(svg.contains("<circle") == (dots > 0));
+            assert_eq!(count_class(&document, "true-line"), dots);
+            assert_eq!(count_class(&document, "estimated-line"), dots);
+        }
+    }
+
+    #[test]
+    fn max_frames_subsamples_the_whole_run() {
+        let ticks = frame_ticks(1000, 1, Some(50), Easing::Linear);
+        assert_eq!(ticks.len(), 50);
+        assert_eq!((ticks[0], ticks[49]), (0, 999));
+        assert!(ticks
+            .windows(2)
+            .all(|pair| (20..=21).contains(&(pair[1] - pair[0]))));
+
+        let result = run(
+            &SimulateConfig {
+                total_time: 100.0,
+                ..config()
+            },
+            1,
+        );
+        assert_eq!(result.ticks.len(), 1000);
+        let animate_options = AnimateOptions {
+            max_frames: Some(50),
+            ..animate_options(32)
+        };
+        assert_eq!(frames(&result, &animate_options).len(), 50);
+    }
+
+    #[test]
+    fn marker_shapes_emit_their_elements() {
+        let result = run(&config(), 1);
+        for (shape, element) in [
+            (MarkerShape::Circle, "<circle"),
+            (MarkerShape::Square, "<rect"),
+            (MarkerShape::Triangle, "<polygon"),
+            (MarkerShape::Cross, "<path"),
+        ] {
+            assert!(shape
+                .marker(10.0, 10.0, 3.0)
+                .to_string()
+                .starts_with(element));
+            let render_options = RenderOptions {
+                marker_shape: shape,
+                ..render_options()
+            };
+            let svg = frame_documents(&result, &render_options)
+                .pop()
+                .unwrap()
+                .to_string();
+            let class = if shape == MarkerShape::Cross {
+                "measured-cross"
+            } else {
+                "measured-dot"
+            };
+            let markers = svg
+                .lines()
+                .filter(|line| line.starts_with(element) && line.contains(class))
+                .count();
+            assert_eq!(markers, result.ticks.len(), "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn plotted_elements_are_styled_by_class() {
+        let result = run(&config(), 1);
+        let document = frame_documents(&result, &render_options()).pop().unwrap();
+        assert_eq!(count_class(&document, "true-line"), 1);
+        assert_eq!(count_class(&document, "estimated-line"), 1);
+        assert_eq!(count_class(&document, "measured-dot"), result.ticks.len());
+
+        let svg = document.to_string();
+        let (_, stylesheet) = svg.split_once("<style>").unwrap();
+        let (stylesheet, _) = stylesheet.split_once("</style>").unwrap();
+        for class in ["true-line", "estimated-line", "measured-dot"] {
+            assert!(stylesheet.contains(&format!(".{} {{", class)), "{}", class);
+        }
+        // The colors live only in the stylesheet, so restyling it restyles the plot
+        let styled = svg
+            .lines()
+            .filter(|line| line.contains("class=\""))
+            .filter(|line| line.contains(" fill=\"") || line.contains(" stroke=\""));
+        assert_eq!(styled.count(), 0);
+    }
+
+    #[test]
+    fn points_stay_inside_the_margin() {
+        let result = run(&config(), 1);
+        let viewport = Viewport::fit(&result, 200, false, 30.0);
+        assert_eq!(viewport.plot_size(), 140.0);
+        let inset = 30.0..=170.0;
+        for tick in &result.ticks {
+            let points = [
+                Some(tick.true_positions),
+                tick.measured_positions,
+                Some(tick.estimated_positions),
+            ];
+            for (time, position) in points.into_iter().flatten() {
+                assert!(inset.contains(&viewport.x(time)));
+                assert!(inset.contains(&viewport.y(position)));
+            }
+        }
+    }
+
+    #[test]
+    fn palette_colors_style_the_series() {
+        let result = run(&config(), 1);
+        for theme in [Theme::Classic, Theme::OkabeIto] {
+            let palette = theme.palette();
+            let render_options = RenderOptions {
+                palette,
+                ..render_options()
+            };
+            let document = frame_documents(&result, &render_options).pop().unwrap();
+            let svg = document.to_string();
+            for style in [palette.truth, palette.estimate] {
+                let rule = format!(".{} {{ fill: none; stroke: {};", style.class, style.color);
+                assert!(svg.contains(&rule), "{:?}: {}", theme, rule);
+                assert_eq!(count_class(&document, style.class), 1, "{:?}", theme);
+            }
+            assert!(svg.contains(palette.measurement), "{:?}", theme);
+        }
+        assert!(Theme::OkabeIto.palette().truth.dash.is_some());
+        assert!(Theme::OkabeIto.palette().estimate.dash.is_none());
+    }
+
+    #[test]
+    fn index_axis_places_samples_by_index() {
+        let result = run(
+            &SimulateConfig {
+                timestamp_jitter: 0.03,
+                ..config()
+            },
+            1,
+        )
+        .by_index();
+        let viewport = Viewport::fit(&result, 200, false, 0.0);
+        // 100 samples span indices 0 to 99, padded by 2% of that on either side
+        for i in [0, 50, 99] {
+            let (x, _) = result.ticks[i].true_positions;
+            assert_eq!(x, i as f64);
+            let expected = (i as f64 + 1.98) / 102.96 * 200.0;
+            assert!((viewport.x(x) - expected).abs() < 1e-9, "sample {}", i);
+        }
+    }
+
+    #[test]
+    fn each_series_is_one_polyline() {
+        let result = run(&config(), 1);
+        let render_options = RenderOptions {
+            line_join: LineJoin::Bevel,
+            line_cap: LineCap::Square,
+            ..render_options()
+        };
+        let svg = frame_documents(&result, &render_options)
+            .pop()
+            .unwrap()
+            .to_string();
+        for class in ["true-line", "estimated-line"] {
+            let lines: Vec<&str> = svg
+                .lines()
+                .filter(|line| line.contains(&format!("class=\"{}\"", class)))
+                .collect();
+            assert_eq!(lines.len(), 1, "{}", class);
+            assert!(lines[0].starts_with("<polyline"));
+            assert_eq!(lines[0].matches(',').count(), result.ticks.len());
+            assert!(lines[0].contains("stroke-linecap=\"square\" stroke-linejoin=\"bevel\""));
+        }
+        assert!(!svg.contains("<line"));
+    }
+
+    #[test]
+    fn composited_frames_are_as_wide_as_both_panes() {
+        let result = run(&config(), 1);
+        let options = AnimateOptions {
+            max_frames: Some(3),
+            ..animate_options(48)
+        };
+        let composite = composite_frames(frames(&result, &options), frames(&result, &options));
+        assert_eq!(composite.len(), 3);
+        for frame in composite {
+            assert_eq!(frame.buffer().dimensions(), (96, 48));
+        }
+    }
+
+    #[test]
+    fn ramp_hold_is_annotated_once_at_the_stop() {
+        let stop_time = 4.0;
+        let result = run(
+            &SimulateConfig {
+                trajectory: Trajectory::RampHold { stop_time },
+                ..config()
+            },
+            1,
+        );
+        let regime_changes = metrics::regime_changes(&result);
+        assert_eq!(regime_changes.len(), 1);
+        assert!((regime_changes[0].time - stop_time).abs() <= 0.1);
+
+        let render_options = RenderOptions {
+            regime_changes,
+            ..render_options()
+        };
+        let svg = frame_documents(&result, &render_options)
+            .pop()
+            .unwrap()
+            .to_string();
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert_eq!(svg.matches("v = 0.00").count(), 1);
+    }
+}
@@ -0,0 +1,122 @@
+/// Puts measurements that arrive out of timestamp order back in order before the filter sees
+/// them.
+///
+/// Each measurement is held until `window` has passed since it was taken, so one overtaken in
+/// transit by a later measurement can still slot in ahead of it. A measurement delayed by more
+/// than `window` arrives after later ones were released, when the filter can no longer go back
+/// to it, and is dropped.
+///
+/// The window trades freshness for completeness: a wider one drops fewer late measurements,
+/// but delays every estimate by the whole window.
+pub struct ReorderBuffer<T> {
+    window: f64,
+    /// Held measurements, sorted by timestamp
+    pending: Vec<(f64, T)>,
+    /// Timestamp of the latest measurement released so far
+    released_until: f64,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(window: f64) -> Self {
+        ReorderBuffer {
+            window,
+            pending: Vec::new(),
+            released_until: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Holds `measurement`, taken at `timestamp`, until it is due. Returns `false` and drops it
+    /// when a later measurement was already released.
+    pub fn push(&mut self, timestamp: f64, measurement: T) -> bool {
+        if timestamp < self.released_until {
+            return false;
+        }
+        // After any held measurements with the same timestamp, so those keep their arrival order
+        let index = self
+            .pending
+            .partition_point(|&(pending, _)| pending <= timestamp);
+        self.pending.insert(index, (timestamp, measurement));
+        true
+    }
+
+    /// Releases, in timestamp order, every held measurement taken at least `window` before
+    /// `now`.
+    pub fn release(&mut self, now: f64) -> Vec<(f64, T)> {
+        let due = self
+            .pending
+            .partition_point(|&(timestamp, _)| timestamp <= now - self.window);
+        let released: Vec<_> = self.pending.drain(..due).collect();
+        if let Some(&(timestamp, _)) = released.last() {
+            self.released_until = timestamp;
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::filter::{Filter, KalmanFilter};
+    use crate::sim::tests::{config, run};
+
+    /// Estimates of the built-in filter after each of `measurements`, given in the order it
+    /// sees them as `(timestamp, position)`.
+    fn estimates(measurements: &[(f64, f64)]) -> Vec<f64> {
+        let mut filter = KalmanFilter::from_config(&config());
+        let mut previous = measurements[0].0 - config().dt;
+        measurements
+            .iter()
+            .map(|&(timestamp, position)| {
+                filter.step(timestamp - previous, position);
+                previous = timestamp;
+                filter.position()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shuffled_arrivals_within_the_window_give_the_in_order_estimates() {
+        let measurements: Vec<(f64, f64)> = run(&config(), 1)
+            .ticks
+            .iter()
+            .filter_map(|tick| tick.measured_positions)
+            .collect();
+
+        // Every three consecutive measurements arrive together, in random order, once the
+        // latest of them is taken, so none is late by more than 0.2 s
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut buffer = ReorderBuffer::new(0.3);
+        let mut released = Vec::new();
+        for batch in measurements.chunks(3) {
+            let mut arrivals = batch.to_vec();
+            arrivals.shuffle(&mut rng);
+            for (timestamp, position) in arrivals {
+                assert!(buffer.push(timestamp, position));
+            }
+            let now = batch.last().unwrap().0;
+            released.extend(buffer.release(now));
+        }
+        released.extend(buffer.release(f64::INFINITY));
+
+        assert_eq!(released, measurements);
+        assert_eq!(estimates(&released), estimates(&measurements));
+    }
+
+    #[test]
+    fn measurements_later_than_the_window_are_dropped() {
+        let mut buffer = ReorderBuffer::new(0.1);
+        assert!(buffer.push(1.0, "a"));
+        assert!(buffer.push(1.2, "b"));
+        assert_eq!(buffer.release(1.15), [(1.0, "a")]);
+        assert!(!buffer.push(0.9, "late"));
+        assert!(buffer.push(1.1, "in time"));
+        assert_eq!(
+            buffer.release(f64::INFINITY),
+            [(1.1, "in time"), (1.2, "b")]
+        );
+    }
+}
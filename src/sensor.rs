@@ -0,0 +1,122 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::noise::NoiseModel;
+
+/// What the sensor reports about the true position, and how it corrupts it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum SensorModel {
+    /// Adds noise of standard deviation `sensor_noise_stddev`.
+    Additive,
+    /// Adds noise proportional to the true position, with standard deviation
+    /// `position * relative_stddev`.
+    Multiplicative { relative_stddev: f64 },
+    /// Reports the distance to a station at `station` along the track and `offset` beside it
+    /// instead of the position, with noise of standard deviation `sensor_noise_stddev`. The
+    /// distance is nonlinear in the position, so only the `extended`, `unscented` and
+    /// `particle` filter models can use it.
+    Range { station: f64, offset: f64 },
+}
+
+impl SensorModel {
+    /// Reading of the sensor at `true_position`, corrupted by `noise` drawn from `rng`.
+    pub fn measure(
+        &self,
+        true_position: f64,
+        noise_stddev: f64,
+        noise: &impl NoiseModel,
+        rng: &mut impl Rng,
+    ) -> f64 {
+        match *self {
+            SensorModel::Additive => true_position + noise.sample(noise_stddev, rng),
+            SensorModel::Multiplicative { relative_stddev } => {
+                true_position + true_position * noise.sample(relative_stddev, rng)
+            }
+            SensorModel::Range { .. } => {
+                self.expected(true_position) + noise.sample(noise_stddev, rng)
+            }
+        }
+    }
+
+    /// Noise-free reading of the sensor at `position`.
+    pub fn expected(&self, position: f64) -> f64 {
+        match *self {
+            SensorModel::Additive | SensorModel::Multiplicative { .. } => position,
+            SensorModel::Range { station, offset } => (position - station).hypot(offset),
+        }
+    }
+
+    /// Derivative of `expected` at `position`, which linearizes the sensor there.
+    pub fn slope(&self, position: f64) -> f64 {
+        match *self {
+            SensorModel::Additive | SensorModel::Multiplicative { .. } => 1.0,
+            SensorModel::Range { station, .. } => {
+                let range = self.expected(position);
+                if range > 0.0 {
+                    (position - station) / range
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Position that reads as `measurement`, for plotting it. A range matches a position on
+    /// either side of the station, so this picks the side of `near`, and a range shorter than
+    /// the offset maps to the station itself.
+    pub fn position_of(&self, measurement: f64, near: f64) -> f64 {
+        match *self {
+            SensorModel::Additive | SensorModel::Multiplicative { .. } => measurement,
+            SensorModel::Range { station, offset } => {
+                let along = (measurement.powi(2) - offset.powi(2)).max(0.0).sqrt();
+                if near < station {
+                    station - along
+                } else {
+                    station + along
+                }
+            }
+        }
+    }
+
+    /// Measurement variance the filter should assume for the next update.
+    ///
+    /// The true position is unknown to the filter, so the multiplicative model scales with the
+    /// predicted position instead.
+    pub fn measurement_variance(&self, predicted_position: f64, r: f64) -> f64 {
+        match *self {
+            SensorModel::Additive | SensorModel::Range { .. } => r,
+            SensorModel::Multiplicative { relative_stddev } => {
+                (relative_stddev * predicted_position).powi(2)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::noise::SensorNoise;
+
+    #[test]
+    fn multiplicative_noise_scales_with_position() {
+        let sensor = SensorModel::Multiplicative {
+            relative_stddev: 0.05,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut stddev = |position: f64| {
+            let errors: Vec<f64> = (0..4000)
+                .map(|_| sensor.measure(position, 2.0, &SensorNoise::Gaussian, &mut rng) - position)
+                .collect();
+            (errors.iter().map(|error| error * error).sum::<f64>() / errors.len() as f64).sqrt()
+        };
+        let near = stddev(10.0);
+        let far = stddev(1000.0);
+        assert!((near - 0.5).abs() < 0.05, "stddev {} at 10", near);
+        assert!((far / near - 100.0).abs() < 10.0, "{} vs {}", far, near);
+        assert_eq!(sensor.measurement_variance(1000.0, 4.0), 2500.0);
+    }
+}
@@ -0,0 +1,1044 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive::AdaptiveQ;
+use crate::dynamics::{ConstantAcceleration, ConstantVelocity, FilterModel, ModelFilter};
+use crate::ekf::ExtendedKalmanFilter;
+use crate::filter::{Filter, KalmanFilter};
+use crate::noise::SensorNoise;
+use crate::particle::{ParticleFilter, ParticleParameters};
+use crate::reorder::ReorderBuffer;
+use crate::sensor::SensorModel;
+use crate::trajectory::{Trajectory, TrajectoryModel, TrueState};
+use crate::ukf::{UnscentedKalmanFilter, UnscentedParameters};
+
+/// What happened during one tick of a simulation: the truth, the measurement if there was
+/// one, and the filter's estimate after seeing it.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct SimulateTick {
+    /// Time and true position
+    pub true_positions: (f64, f64),
+    /// Time and measured position, `None` on ticks where the sensor didn't report
+    pub measured_positions: Option<(f64, f64)>,
+    /// What the sensor reported, which is the measured position unless it measures something
+    /// else, like a range
+    pub measurement: Option<f64>,
+    /// Time and estimated position
+    pub estimated_positions: (f64, f64),
+    pub true_velocity: f64,
+    pub estimated_velocity: f64,
+    /// Variance `p` of the position estimate after this tick
+    pub covariance: f64,
+    /// Gain `k` the filter applied on this tick
+    pub gain: f64,
+    /// Measurement minus predicted position, and its predicted variance `p + r`, on ticks
+    /// with a measurement
+    pub innovation: Option<(f64, f64)>,
+}
+
+impl SimulateTick {
+    /// Name of the first recorded quantity that is NaN or infinite, if any.
+    pub fn non_finite_quantity(&self) -> Option<&'static str> {
+        let quantities = [
+            ("time", Some(self.true_positions.0)),
+            ("true position", Some(self.true_positions.1)),
+            ("measured position", self.measured_positions.map(|(_, y)| y)),
+            ("estimated position", Some(self.estimated_positions.1)),
+            ("true velocity", Some(self.true_velocity)),
+            ("estimated velocity", Some(self.estimated_velocity)),
+            ("covariance", Some(self.covariance)),
+            ("gain", Some(self.gain)),
+            (
+                "innovation",
+                self.innovation.map(|(innovation, _)| innovation),
+            ),
+            (
+                "innovation variance",
+                self.innovation.map(|(_, variance)| variance),
+            ),
+        ];
+        quantities
+            .into_iter()
+            .find(|(_, value)| value.is_some_and(|value| !value.is_finite()))
+            .map(|(name, _)| name)
+    }
+}
+
+/// One line of logged sensor data, to run a filter over instead of simulated measurements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedMeasurement {
+    pub time: f64,
+    /// What the sensor reported, `None` on ticks where it didn't
+    pub measurement: Option<f64>,
+    /// Where the tracked object really was, when that was logged too
+    pub true_position: Option<f64>,
+}
+
+/// Every tick of a simulated run, in time order.
+#[derive(Default, Serialize, Deserialize)]
+pub struct SimulateResult {
+    pub ticks: Vec<SimulateTick>,
+}
+
+impl SimulateResult {
+    /// Returns a copy with every tick's time replaced by its sample index.
+    pub fn by_index(&self) -> SimulateResult {
+        let ticks = self
+            .ticks
+            .iter()
+            .enumerate()
+            .map(|(i, tick)| {
+                let index = i as f64;
+                SimulateTick {
+                    true_positions: (index, tick.true_positions.1),
+                    measured_positions: tick.measured_positions.map(|(_, m)| (index, m)),
+                    estimated_positions: (index, tick.estimated_positions.1),
+                    ..*tick
+                }
+            })
+            .collect();
+        SimulateResult { ticks }
+    }
+
+    /// Returns a copy without the ticks before `time`.
+    pub fn since(&self, time: f64) -> SimulateResult {
+        SimulateResult {
+            ticks: self
+                .ticks
+                .iter()
+                .filter(|tick| tick.true_positions.0 >= time)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns a copy with only the ticks from `start` to `end`, both included.
+    pub fn within(&self, start: f64, end: f64) -> SimulateResult {
+        SimulateResult {
+            ticks: self
+                .ticks
+                .iter()
+                .filter(|tick| (start..=end).contains(&tick.true_positions.0))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Writes one CSV row per tick to `writer`, under a header whose first column is named
+    /// `x_column`, such as `time[s]`. Ticks without a measurement leave `measured_position`
+    /// empty.
+    ///
+    /// `covariance` and `gain` come last so readers that only know the position columns keep
+    /// working.
+    pub fn to_csv(&self, writer: &mut impl Write, x_column: &str) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{},true_position,measured_position,estimated_position,covariance,gain",
+            x_column
+        )?;
+        for tick in &self.ticks {
+            let (time, true_position) = tick.true_positions;
+            let measured_position = tick
+                .measured_positions
+                .map(|(_, position)| position.to_string())
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                time,
+                true_position,
+                measured_position,
+                tick.estimated_positions.1,
+                tick.covariance,
+                tick.gain
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns a copy with only every `n`th tick, starting from the first.
+    pub fn every(&self, n: usize) -> SimulateResult {
+        SimulateResult {
+            ticks: self.ticks.iter().step_by(n).cloned().collect(),
+        }
+    }
+}
+
+/// Parameters of a simulated run: the true motion, the sensor and the filter tracking it.
+///
+/// `r` and `q` are what the filter assumes for the measurement variance and process noise;
+/// the real measurement noise is `sensor_noise_stddev`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SimulateConfig {
+    pub total_time: f64,
+    pub dt: f64,
+    pub velocity: f64,
+    pub sensor_noise_stddev: f64,
+    pub r: f64,
+    pub q: f64,
+    /// Shape of the true motion, with `velocity` as its nominal speed
+    pub trajectory: Trajectory,
+    pub sensor: SensorModel,
+    /// Shape of the sensor noise, scaled to `sensor_noise_stddev`
+    pub noise: SensorNoise,
+    /// Where the true trajectory starts
+    pub initial_position: f64,
+    /// Error added to the filter's initial position guess
+    pub initial_position_offset: f64,
+    /// Error added to the filter's initial velocity guess. The truth always moves at `velocity`.
+    pub initial_velocity_offset: f64,
+    /// Number of ticks between measurements. The filter still predicts every tick.
+    pub measurement_interval: usize,
+    /// Largest random offset of each tick's timestamp from its nominal `step * dt`, so the
+    /// filter sees irregular gaps between ticks
+    pub timestamp_jitter: f64,
+    /// Longest random delay between taking a measurement and it reaching the filter, so
+    /// measurements can arrive out of order
+    pub arrival_delay: f64,
+    /// How long measurements are held to be put back in order before the filter sees them
+    pub reorder_window: f64,
+    /// State the filter estimates and how it assumes that state evolves
+    pub model: FilterModel,
+    /// Raise `q` while the innovations are surprising, when set
+    pub adaptive_q: Option<AdaptiveQ>,
+    /// Sigma points of the `unscented` model
+    pub unscented: UnscentedParameters,
+    /// Particles of the `particle` model
+    pub particle: ParticleParameters,
+}
+
+/// Why `simulate` refused a configuration or gave up on a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimulateError {
+    InvalidDt(f64),
+    InvalidTotalTime(f64),
+    TotalTimeShorterThanDt { total_time: f64, dt: f64 },
+    InvalidMeasurementInterval,
+    InvalidTimestampJitter { jitter: f64, dt: f64 },
+    InvalidArrivalDelay(f64),
+    InvalidReorderWindow(f64),
+    InvalidAdaptiveQ(AdaptiveQ),
+    InvalidUnscented(UnscentedParameters),
+    InvalidParticleCount,
+    InvalidDegreesOfFreedom(f64),
+    InvalidPeriod(f64),
+    InvalidWalkDensity(f64),
+    /// The sensor reports something other than the position, which `model` cannot use
+    SensorNeedsNonlinearModel(FilterModel),
+    NonFiniteParameter { name: &'static str, value: f64 },
+    NonFinite { step: usize, quantity: &'static str },
+}
+
+impl fmt::Display for SimulateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulateError::InvalidDt(dt) => {
+                write!(f, "dt must be a positive finite number, got {}", dt)
+            }
+            SimulateError::InvalidTotalTime(total_time) => write!(
+                f,
+                "total_time must be a positive finite number, got {}",
+                total_time
+            ),
+            SimulateError::TotalTimeShorterThanDt { total_time, dt } => write!(
+                f,
+                "total_time ({}) is shorter than dt ({}), so no step would be simulated",
+                total_time, dt
+            ),
+            SimulateError::InvalidMeasurementInterval => {
+                write!(f, "measurement_interval must be at least 1")
+            }
+            SimulateError::InvalidTimestampJitter { jitter, dt } => write!(
+                f,
+                "timestamp_jitter must be at least 0 and below dt / 2 ({}) to keep the ticks in order, got {}",
+                dt / 2.0,
+                jitter
+            ),
+            SimulateError::InvalidArrivalDelay(delay) => write!(
+                f,
+                "arrival_delay must be a non-negative finite number, got {}",
+                delay
+            ),
+            SimulateError::InvalidReorderWindow(window) => write!(
+                f,
+                "reorder_window must be a non-negative finite number, got {}",
+                window
+            ),
+            SimulateError::InvalidAdaptiveQ(adaptive) => write!(
+                f,
+                "adaptive q needs a positive sensitivity and a decay in [0, 1), got {} and {}",
+                adaptive.sensitivity, adaptive.decay
+            ),
+            SimulateError::InvalidUnscented(unscented) => write!(
+                f,
+                "the unscented filter needs a positive alpha, a finite beta and a kappa above -2, got {}, {} and {}",
+                unscented.alpha, unscented.beta, unscented.kappa
+            ),
+            SimulateError::InvalidParticleCount => {
+                write!(f, "the particle filter needs at least 1 particle")
+            }
+            SimulateError::InvalidDegreesOfFreedom(degrees_of_freedom) => write!(
+                f,
+                "student-t noise needs more than 2 degrees of freedom to have a variance, got {}",
+                degrees_of_freedom
+            ),
+            SimulateError::InvalidPeriod(period) => write!(
+                f,
+                "the sinusoidal trajectory needs a positive finite period, got {}",
+                period
+            ),
+            SimulateError::InvalidWalkDensity(density) => write!(
+                f,
+                "the random-walk trajectory needs a non-negative finite density, got {}",
+                density
+            ),
+            SimulateError::SensorNeedsNonlinearModel(model) => write!(
+                f,
+                "the range sensor needs the extended, unscented or particle filter model, got {}",
+                model.name()
+            ),
+            SimulateError::NonFiniteParameter { name, value } => {
+                write!(f, "{} must be finite, got {}", name, value)
+            }
+            SimulateError::NonFinite { step, quantity } => write!(
+                f,
+                "{} became non-finite at step {}; the parameters are too extreme",
+                quantity, step
+            ),
+        }
+    }
+}
+
+impl Error for SimulateError {}
+
+impl SimulateConfig {
+    /// Number of ticks the simulation runs for.
+    pub fn steps(&self) -> usize {
+        (self.total_time / self.dt) as usize
+    }
+
+    /// Checks that `simulate` can run this configuration.
+    pub fn validate(&self) -> Result<(), SimulateError> {
+        if !(self.dt.is_finite() && self.dt > 0.0) {
+            return Err(SimulateError::InvalidDt(self.dt));
+        }
+        if !(self.total_time.is_finite() && self.total_time > 0.0) {
+            return Err(SimulateError::InvalidTotalTime(self.total_time));
+        }
+        if self.total_time < self.dt {
+            return Err(SimulateError::TotalTimeShorterThanDt {
+                total_time: self.total_time,
+                dt: self.dt,
+            });
+        }
+        if self.measurement_interval == 0 {
+            return Err(SimulateError::InvalidMeasurementInterval);
+        }
+        if !(self.timestamp_jitter >= 0.0 && self.timestamp_jitter < self.dt / 2.0) {
+            return Err(SimulateError::InvalidTimestampJitter {
+                jitter: self.timestamp_jitter,
+                dt: self.dt,
+            });
+        }
+        if !(self.arrival_delay.is_finite() && self.arrival_delay >= 0.0) {
+            return Err(SimulateError::InvalidArrivalDelay(self.arrival_delay));
+        }
+        if !(self.reorder_window.is_finite() && self.reorder_window >= 0.0) {
+            return Err(SimulateError::InvalidReorderWindow(self.reorder_window));
+        }
+        if let Some(adaptive) = self.adaptive_q {
+            if !adaptive.is_valid() {
+                return Err(SimulateError::InvalidAdaptiveQ(adaptive));
+            }
+        }
+        if self.model == FilterModel::Unscented && !self.unscented.is_valid(2) {
+            return Err(SimulateError::InvalidUnscented(self.unscented));
+        }
+        if let SensorNoise::StudentT { degrees_of_freedom } = self.noise {
+            if !self.noise.is_valid() {
+                return Err(SimulateError::InvalidDegreesOfFreedom(degrees_of_freedom));
+            }
+        }
+        match self.trajectory {
+            Trajectory::Sinusoidal { period, .. } if !(period.is_finite() && period > 0.0) => {
+                return Err(SimulateError::InvalidPeriod(period));
+            }
+            Trajectory::RandomWalk { density } if !(density.is_finite() && density >= 0.0) => {
+                return Err(SimulateError::InvalidWalkDensity(density));
+            }
+            _ => {}
+        }
+        if self.model == FilterModel::Particle && self.particle.particles == 0 {
+            return Err(SimulateError::InvalidParticleCount);
+        }
+        let parameters = [
+            ("velocity", self.velocity),
+            ("sensor_noise_stddev", self.sensor_noise_stddev),
+            ("r", self.r),
+            ("q", self.q),
+            ("initial_position", self.initial_position),
+            ("initial_position_offset", self.initial_position_offset),
+            ("initial_velocity_offset", self.initial_velocity_offset),
+        ];
+        for (name, value) in parameters.into_iter().chain(self.trajectory.parameters()) {
+            if !value.is_finite() {
+                return Err(SimulateError::NonFiniteParameter { name, value });
+            }
+        }
+        let sensor_parameters = match self.sensor {
+            SensorModel::Additive => vec![],
+            SensorModel::Multiplicative { relative_stddev } => {
+                vec![("relative_stddev", relative_stddev)]
+            }
+            SensorModel::Range { station, offset } => {
+                if !self.model.is_nonlinear() {
+                    return Err(SimulateError::SensorNeedsNonlinearModel(self.model));
+                }
+                vec![("station", station), ("offset", offset)]
+            }
+        };
+        for (name, value) in sensor_parameters {
+            if !value.is_finite() {
+                return Err(SimulateError::NonFiniteParameter { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Advances `filter` by a tick `dt` after the previous one, correcting it with
+/// `measured_position` when the sensor reported, and adapts its `q` to the innovation when
+/// `config` asks for it.
+pub(crate) fn filter_tick(
+    filter: &mut (impl Filter + ?Sized),
+    config: &SimulateConfig,
+    dt: f64,
+    measured_position: Option<f64>,
+) {
+    match (measured_position, config.sensor) {
+        (None, _) => {
+            filter.predict(dt);
+            return;
+        }
+        (Some(measured_position), SensorModel::Additive) => {
+            filter.step(dt, measured_position);
+        }
+        (Some(measured_position), sensor) => {
+            filter.predict(dt);
+            let r = sensor.measurement_variance(filter.position(), config.r);
+            filter.update_with_variance(measured_position, r);
+        }
+    }
+    if let Some(adaptive) = config.adaptive_q {
+        let q = adaptive.next_q(filter.process_noise(), config.q, filter.innovation());
+        filter.set_process_noise(q);
+    }
+}
+
+/// Simulates `config` with the filter model it selects, drawing the sensor noise from `rng`.
+pub fn simulate_model(
+    config: &SimulateConfig,
+    rng: &mut impl Rng,
+) -> Result<SimulateResult, SimulateError> {
+    match config.model {
+        FilterModel::KnownVelocity => simulate(config, &mut KalmanFilter::from_config(config), rng),
+        FilterModel::ConstantVelocity => simulate(
+            config,
+            &mut ModelFilter::from_config(ConstantVelocity, config),
+            rng,
+        ),
+        FilterModel::ConstantAcceleration => simulate(
+            config,
+            &mut ModelFilter::from_config(ConstantAcceleration, config),
+            rng,
+        ),
+        FilterModel::Extended => {
+            simulate(config, &mut ExtendedKalmanFilter::from_config(config), rng)
+        }
+        FilterModel::Unscented => {
+            simulate(config, &mut UnscentedKalmanFilter::from_config(config), rng)
+        }
+        FilterModel::Particle => simulate(config, &mut ParticleFilter::from_config(config), rng),
+    }
+}
+
+/// Runs `filter` over the measurements recorded in `result` and returns its position estimate
+/// at every tick.
+pub fn replay(result: &SimulateResult, config: &SimulateConfig, filter: impl Filter) -> Vec<f64> {
+    replay_with(result, config, filter, |filter| filter.position())
+}
+
+/// Like `replay`, but returns what `observe` reads off the filter at every tick.
+pub fn replay_with<F: Filter, T>(
+    result: &SimulateResult,
+    config: &SimulateConfig,
+    mut filter: F,
+    mut observe: impl FnMut(&F) -> T,
+) -> Vec<T> {
+    // Same convention as `simulate`: the first tick comes a nominal `dt` after the start
+    let mut previous_time = -config.dt;
+    result
+        .ticks
+        .iter()
+        .map(|tick| {
+            let time = tick.true_positions.0;
+            filter_tick(&mut filter, config, time - previous_time, tick.measurement);
+            previous_time = time;
+            observe(&filter)
+        })
+        .collect()
+}
+
+/// Position estimates of the filter model `config` selects over the measurements recorded in
+/// `result`, so several models can be compared on the same noise.
+pub fn refilter(result: &SimulateResult, config: &SimulateConfig) -> Vec<f64> {
+    match config.model {
+        FilterModel::KnownVelocity => replay(result, config, KalmanFilter::from_config(config)),
+        FilterModel::ConstantVelocity => replay(
+            result,
+            config,
+            ModelFilter::from_config(ConstantVelocity, config),
+        ),
+        FilterModel::ConstantAcceleration => replay(
+            result,
+            config,
+            ModelFilter::from_config(ConstantAcceleration, config),
+        ),
+        FilterModel::Extended => replay(result, config, ExtendedKalmanFilter::from_config(config)),
+        FilterModel::Unscented => {
+            replay(result, config, UnscentedKalmanFilter::from_config(config))
+        }
+        FilterModel::Particle => replay(result, config, ParticleFilter::from_config(config)),
+    }
+}
+
+/// Runs `filter` over a simulated run of `config`. All of the randomness, the sensor noise,
+/// comes from `rng`, so a seeded or mock generator makes the run reproducible.
+pub fn simulate(
+    config: &SimulateConfig,
+    filter: &mut impl Filter,
+    rng: &mut impl Rng,
+) -> Result<SimulateResult, SimulateError> {
+    config.validate()?;
+    let run = draw_run(config, rng);
+    let mut result = SimulateResult::default();
+    for step in 0..run.truth.len() {
+        result.ticks.push(filter_step(config, filter, &run, step)?);
+    }
+    Ok(result)
+}
+
+/// Progress of a seeded run part way through, from which `simulate_checkpointed` carries on.
+/// The step index is the number of ticks filtered so far.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint<F> {
+    /// Seed of the `StdRng` all of the run's noise comes from
+    pub seed: u64,
+    pub result: SimulateResult,
+    pub filter: F,
+}
+
+impl<F: Filter> Checkpoint<F> {
+    /// Start of a run seeded with `seed`, before `filter` has seen any tick.
+    pub fn new(seed: u64, filter: F) -> Self {
+        Checkpoint {
+            seed,
+            result: SimulateResult::default(),
+            filter,
+        }
+    }
+}
+
+/// Carries on the run of `config` that `checkpoint` got part way through to its end, handing
+/// the checkpoint to `save` after every `every` ticks along with the ticks filtered since the
+/// previous save, so that `save` can append those rather than write the whole result again.
+/// The noise of the whole run is drawn again from the seed, so a run resumed with the same
+/// `config` has the ticks it would have had uninterrupted.
+pub fn simulate_checkpointed<F: Filter, E: From<SimulateError>>(
+    config: &SimulateConfig,
+    checkpoint: &mut Checkpoint<F>,
+    every: usize,
+    mut save: impl FnMut(&Checkpoint<F>, &[SimulateTick]) -> Result<(), E>,
+) -> Result<(), E> {
+    config.validate()?;
+    let run = draw_run(config, &mut StdRng::seed_from_u64(checkpoint.seed));
+    let start = checkpoint.result.ticks.len();
+    let mut saved = start;
+    for step in start..run.truth.len() {
+        let tick = filter_step(config, &mut checkpoint.filter, &run, step)?;
+        checkpoint.result.ticks.push(tick);
+        if (step + 1).is_multiple_of(every) {
+            save(checkpoint, &checkpoint.result.ticks[saved..])?;
+            saved = step + 1;
+        }
+    }
+    Ok(())
+}
+
+/// Truth and measurements of a simulated run, before any filtering.
+struct DrawnRun {
+    /// Time, time since the previous tick, true position and true velocity of every tick
+    truth: Vec<(f64, f64, f64, f64)>,
+    /// Measurement that reaches the filter on every tick
+    received: Vec<Option<f64>>,
+}
+
+/// Draws the truth and the measurements of a run of `config` from `rng`.
+fn draw_run(config: &SimulateConfig, rng: &mut impl Rng) -> DrawnRun {
+    let SimulateConfig {
+        dt,
+        velocity,
+        sensor_noise_stddev,
+        trajectory,
+        sensor,
+        noise,
+        initial_position,
+        measurement_interval,
+        timestamp_jitter,
+        arrival_delay,
+        reorder_window,
+        ..
+    } = *config;
+
+    let steps = config.steps();
+
+    let mut true_state = TrueState {
+        position: initial_position,
+        velocity,
+    };
+    let mut previous_time = -dt;
+
+    // The truth at every tick, and the measurements with the time each reaches the filter
+    let mut truth = Vec::with_capacity(steps);
+    let mut in_transit = Vec::new();
+    for step in 0..steps {
+        let mut time = step as f64 * dt;
+        // Only draw the jitter and delays when asked, so runs without them keep their noise
+        // sequence
+        if timestamp_jitter > 0.0 {
+            time += rng.gen_range(-timestamp_jitter..timestamp_jitter);
+        }
+        let tick_dt = time - previous_time;
+        previous_time = time;
+
+        true_state = trajectory.step(true_state, time, tick_dt, velocity, rng);
+        let TrueState {
+            position: true_position,
+            velocity: true_velocity,
+        } = true_state;
+        truth.push((time, tick_dt, true_position, true_velocity));
+
+        if step % measurement_interval == 0 {
+            let measured = sensor.measure(true_position, sensor_noise_stddev, &noise, rng);
+            let delay = if arrival_delay > 0.0 {
+                rng.gen_range(0.0..arrival_delay)
+            } else {
+                0.0
+            };
+            in_transit.push((time + delay, time, step, measured));
+        }
+    }
+
+    // Hand the measurements to the reorder buffer as they arrive, with the clock advancing
+    // tick by tick, and wait out the window once the run is over
+    in_transit.sort_by(|(a, ..), (b, ..)| a.total_cmp(b));
+    let mut buffer = ReorderBuffer::new(reorder_window);
+    let mut received = vec![None; steps];
+    let mut arrivals = in_transit.into_iter().peekable();
+    let clock = truth.iter().map(|&(time, ..)| time);
+    for now in clock.chain(std::iter::once(f64::INFINITY)) {
+        while let Some((_, timestamp, step, measured)) =
+            arrivals.next_if(|&(arrival, ..)| arrival <= now)
+        {
+            // A late measurement is dropped, leaving its tick without one
+            buffer.push(timestamp, (step, measured));
+        }
+        for (_, (step, measured)) in buffer.release(now) {
+            received[step] = Some(measured);
+        }
+    }
+
+    DrawnRun { truth, received }
+}
+
+/// Runs `filter` over tick `step` of `run` and records the tick.
+fn filter_step(
+    config: &SimulateConfig,
+    filter: &mut impl Filter,
+    run: &DrawnRun,
+    step: usize,
+) -> Result<SimulateTick, SimulateError> {
+    let (time, tick_dt, true_position, true_velocity) = run.truth[step];
+    let measured_position = run.received[step];
+    filter_tick(filter, config, tick_dt, measured_position);
+    let tick = record_tick(
+        filter,
+        config.sensor,
+        time,
+        (true_position, true_velocity),
+        measured_position,
+    );
+    match tick.non_finite_quantity() {
+        Some(quantity) => Err(SimulateError::NonFinite { step, quantity }),
+        None => Ok(tick),
+    }
+}
+
+/// What `filter` estimates at `time`, after a tick with `measurement` and the true position
+/// and velocity `truth`.
+fn record_tick(
+    filter: &impl Filter,
+    sensor: SensorModel,
+    time: f64,
+    truth: (f64, f64),
+    measurement: Option<f64>,
+) -> SimulateTick {
+    SimulateTick {
+        true_positions: (time, truth.0),
+        measured_positions: measurement
+            .map(|measured| (time, sensor.position_of(measured, filter.position()))),
+        measurement,
+        estimated_positions: (time, filter.position()),
+        true_velocity: truth.1,
+        estimated_velocity: filter.velocity(),
+        covariance: filter.uncertainty(),
+        gain: filter.gain(),
+        innovation: measurement.map(|_| filter.innovation()),
+    }
+}
+
+/// Runs `filter` over logged `measurements`, in time order, instead of a simulated run. Only
+/// the sensor and filter parameters of `config` apply; the ticks are the logged ones, the
+/// first a nominal `dt` after the start.
+///
+/// Where no true position was logged, the latest measured position stands in for it, or the
+/// initial position before the first measurement, so the error metrics measure the distance
+/// from the data. The true velocity is how fast that truth changes.
+pub fn filter_recorded(
+    config: &SimulateConfig,
+    filter: &mut impl Filter,
+    measurements: &[RecordedMeasurement],
+) -> Result<SimulateResult, SimulateError> {
+    config.validate()?;
+
+    let mut result = SimulateResult::default();
+    let mut previous_time = measurements
+        .first()
+        .map_or(0.0, |first| first.time - config.dt);
+    let mut previous_position = config.initial_position;
+    let mut latest_measured = config.initial_position;
+    for (step, recorded) in measurements.iter().enumerate() {
+        let tick_dt = recorded.time - previous_time;
+        filter_tick(filter, config, tick_dt, recorded.measurement);
+
+        if let Some(measured) = recorded.measurement {
+            latest_measured = config.sensor.position_of(measured, filter.position());
+        }
+        let true_position = recorded.true_position.unwrap_or(latest_measured);
+        let true_velocity = if tick_dt > 0.0 {
+            (true_position - previous_position) / tick_dt
+        } else {
+            0.0
+        };
+        previous_time = recorded.time;
+        previous_position = true_position;
+
+        let tick = record_tick(
+            filter,
+            config.sensor,
+            recorded.time,
+            (true_position, true_velocity),
+            recorded.measurement,
+        );
+        if let Some(quantity) = tick.non_finite_quantity() {
+            return Err(SimulateError::NonFinite { step, quantity });
+        }
+        result.ticks.push(tick);
+    }
+
+    Ok(result)
+}
+
+/// Runs the filter model `config` selects over logged `measurements`, like `filter_recorded`.
+pub fn filter_recorded_model(
+    config: &SimulateConfig,
+    measurements: &[RecordedMeasurement],
+) -> Result<SimulateResult, SimulateError> {
+    match config.model {
+        FilterModel::KnownVelocity => {
+            filter_recorded(config, &mut KalmanFilter::from_config(config), measurements)
+        }
+        FilterModel::ConstantVelocity => filter_recorded(
+            config,
+            &mut ModelFilter::from_config(ConstantVelocity, config),
+            measurements,
+        ),
+        FilterModel::ConstantAcceleration => filter_recorded(
+            config,
+            &mut ModelFilter::from_config(ConstantAcceleration, config),
+            measurements,
+        ),
+        FilterModel::Extended => filter_recorded(
+            config,
+            &mut ExtendedKalmanFilter::from_config(config),
+            measurements,
+        ),
+        FilterModel::Unscented => filter_recorded(
+            config,
+            &mut UnscentedKalmanFilter::from_config(config),
+            measurements,
+        ),
+        FilterModel::Particle => filter_recorded(
+            config,
+            &mut ParticleFilter::from_config(config),
+            measurements,
+        ),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::variants::{FilterVariant, VariantFilter};
+
+    /// The known-velocity filter following a target at unit speed for 10 s, measured every
+    /// 0.1 s with noise of standard deviation 2, like the binary's built-in scenario.
+    pub(crate) fn config() -> SimulateConfig {
+        SimulateConfig {
+            total_time: 10.0,
+            dt: 0.1,
+            velocity: 1.0,
+            sensor_noise_stddev: 2.0,
+            r: 4.0,
+            q: 0.01,
+            trajectory: Trajectory::ConstantVelocity,
+            sensor: SensorModel::Additive,
+            noise: SensorNoise::Gaussian,
+            initial_position: 0.0,
+            initial_position_offset: 0.0,
+            initial_velocity_offset: 0.0,
+            measurement_interval: 1,
+            timestamp_jitter: 0.0,
+            arrival_delay: 0.0,
+            reorder_window: 0.0,
+            model: FilterModel::KnownVelocity,
+            adaptive_q: None,
+            unscented: UnscentedParameters::default(),
+            particle: ParticleParameters::default(),
+        }
+    }
+
+    /// Run of `config` with its noise drawn from a generator seeded with `seed`.
+    pub(crate) fn run(config: &SimulateConfig, seed: u64) -> SimulateResult {
+        simulate_model(config, &mut StdRng::seed_from_u64(seed)).unwrap()
+    }
+
+    /// Mean absolute estimate error over `ticks`.
+    fn mean_error(ticks: &[SimulateTick]) -> f64 {
+        ticks
+            .iter()
+            .map(|tick| (tick.estimated_positions.1 - tick.true_positions.1).abs())
+            .sum::<f64>()
+            / ticks.len() as f64
+    }
+
+    #[test]
+    fn initial_offset_error_shrinks() {
+        let result = run(
+            &SimulateConfig {
+                initial_position_offset: 20.0,
+                ..config()
+            },
+            1,
+        );
+        let early = mean_error(&result.ticks[..5]);
+        let late = mean_error(&result.ticks[result.ticks.len() - 20..]);
+        assert!(early > 10.0, "early error {}", early);
+        assert!(late < early / 5.0, "late error {} vs early {}", late, early);
+    }
+
+    #[test]
+    fn rejects_non_positive_dt_and_short_runs() {
+        let with = |dt, total_time| {
+            SimulateConfig {
+                dt,
+                total_time,
+                ..config()
+            }
+            .validate()
+        };
+        assert_eq!(with(0.0, 10.0), Err(SimulateError::InvalidDt(0.0)));
+        assert_eq!(with(-0.1, 10.0), Err(SimulateError::InvalidDt(-0.1)));
+        assert_eq!(
+            with(0.1, 0.05),
+            Err(SimulateError::TotalTimeShorterThanDt {
+                total_time: 0.05,
+                dt: 0.1
+            })
+        );
+        assert_eq!(with(0.1, 10.0), Ok(()));
+    }
+
+    /// Estimated positions of `filter` over the run of `config` seeded with 1.
+    fn estimates(config: &SimulateConfig, filter: &mut impl Filter) -> Vec<f64> {
+        simulate(config, filter, &mut StdRng::seed_from_u64(1))
+            .unwrap()
+            .ticks
+            .iter()
+            .map(|tick| tick.estimated_positions.1)
+            .collect()
+    }
+
+    #[test]
+    fn simulates_any_filter() {
+        let config = config();
+        let kalman = estimates(&config, &mut KalmanFilter::from_config(&config));
+        let standard = estimates(
+            &config,
+            &mut VariantFilter::from_config(FilterVariant::Standard, &config),
+        );
+        assert_eq!(kalman.len(), config.steps());
+        // The same algebra, but the known-velocity filter solves for its gain through the
+        // linear filter's Cholesky factor, which rounds differently
+        for (kalman, standard) in kalman.iter().zip(&standard) {
+            assert!(
+                (kalman - standard).abs() < 1e-12,
+                "{} vs {}",
+                kalman,
+                standard
+            );
+        }
+    }
+
+    #[test]
+    fn a_step_rng_gives_the_same_run_every_time() {
+        let config = config();
+        let serialized = |increment| {
+            let mut rng = StepRng::new(1, increment);
+            let result = simulate(&config, &mut KalmanFilter::from_config(&config), &mut rng);
+            serde_json::to_string(&result.unwrap()).unwrap()
+        };
+        let first = serialized(0x9E37_79B9_7F4A_7C15);
+        assert_eq!(serialized(0x9E37_79B9_7F4A_7C15), first);
+        assert_ne!(serialized(0x2545_F491_4F6C_DD1D), first);
+    }
+
+    #[test]
+    fn jittered_gaps_vary_around_the_nominal_dt() {
+        let config = SimulateConfig {
+            total_time: 100.0,
+            timestamp_jitter: 0.03,
+            ..config()
+        };
+        let times: Vec<f64> = run(&config, 1)
+            .ticks
+            .iter()
+            .map(|tick| tick.true_positions.0)
+            .collect();
+        let gaps: Vec<f64> = times.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        let mean_gap = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        assert!((mean_gap - config.dt).abs() < 1e-4, "{}", mean_gap);
+        assert!(gaps
+            .iter()
+            .all(|&gap| gap > 0.0 && (gap - config.dt).abs() < 0.06));
+        let (min, max) = gaps
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &gap| {
+                (min.min(gap), max.max(gap))
+            });
+        assert!(max - min > 0.08, "gaps from {} to {}", min, max);
+    }
+
+    #[test]
+    fn overflowing_parameters_are_reported_not_propagated() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let error = simulate_model(
+            &SimulateConfig {
+                velocity: 1e308,
+                ..config()
+            },
+            &mut rng,
+        )
+        .err();
+        assert!(matches!(error, Some(SimulateError::NonFinite { .. })));
+        assert!(error.unwrap().to_string().contains("non-finite at step"));
+
+        let error = simulate_model(
+            &SimulateConfig {
+                q: f64::INFINITY,
+                ..config()
+            },
+            &mut rng,
+        )
+        .err();
+        assert_eq!(
+            error,
+            Some(SimulateError::NonFiniteParameter {
+                name: "q",
+                value: f64::INFINITY
+            })
+        );
+    }
+
+    #[test]
+    fn velocity_error_shrinks() {
+        let result = run(
+            &SimulateConfig {
+                total_time: 30.0,
+                initial_velocity_offset: 2.0,
+                model: FilterModel::ConstantVelocity,
+                ..config()
+            },
+            1,
+        );
+        let velocity_error = |ticks: &[SimulateTick]| {
+            ticks
+                .iter()
+                .map(|tick| (tick.estimated_velocity - tick.true_velocity).abs())
+                .sum::<f64>()
+                / ticks.len() as f64
+        };
+        let early = velocity_error(&result.ticks[..10]);
+        let late = velocity_error(&result.ticks[result.ticks.len() - 100..]);
+        assert!(early > 1.0, "early error {}", early);
+        assert!(late < early / 4.0, "late error {} vs early {}", late, early);
+    }
+
+    #[test]
+    fn resuming_at_60_matches_100_steps_straight() {
+        let config = config();
+        assert_eq!(config.steps(), 100);
+        let straight = serde_json::to_string(&run(&config, 7)).unwrap();
+
+        let mut saved = None;
+        let mut checkpoint = Checkpoint::new(7, KalmanFilter::from_config(&config));
+        simulate_checkpointed(&config, &mut checkpoint, 30, |checkpoint, ticks| {
+            // Each save gets only the ticks since the previous one
+            assert_eq!(ticks.len(), 30);
+            if checkpoint.result.ticks.len() == 60 {
+                saved = Some(serde_json::to_string(checkpoint).unwrap());
+            }
+            Ok::<_, SimulateError>(())
+        })
+        .unwrap();
+        assert_eq!(serde_json::to_string(&checkpoint.result).unwrap(), straight);
+
+        let mut resumed: Checkpoint<KalmanFilter> = serde_json::from_str(&saved.unwrap()).unwrap();
+        assert_eq!(resumed.result.ticks.len(), 60);
+        simulate_checkpointed(&config, &mut resumed, 30, |_, ticks| {
+            assert_eq!(ticks.len(), 30);
+            Ok::<_, SimulateError>(())
+        })
+        .unwrap();
+        assert_eq!(serde_json::to_string(&resumed.result).unwrap(), straight);
+    }
+}
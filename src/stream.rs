@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+use kalman_filter_simulator::online::OnlineFilter;
+use kalman_filter_simulator::sim::SimulateConfig;
+
+/// Reads `time,measurement` lines from stdin as they arrive, with an empty measurement for a
+/// prediction only, and writes the filter's estimate after each to stdout straight away.
+///
+/// A header line starting with `time` and blank lines are skipped. Lines that don't parse or
+/// go back in time are reported on stderr and skipped, so one bad reading doesn't end the
+/// stream. An estimate that becomes NaN or infinite ends it with an error.
+pub fn run_stream(config: &SimulateConfig) -> Result<(), Box<dyn Error>> {
+    filter_stream(
+        config,
+        io::stdin().lock(),
+        io::stdout().lock(),
+        io::stderr().lock(),
+    )
+}
+
+/// `run_stream` over any `input`, writing the estimates to `output` and the skipped lines to
+/// `errors`.
+fn filter_stream(
+    config: &SimulateConfig,
+    input: impl BufRead,
+    mut output: impl Write,
+    mut errors: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut filter = OnlineFilter::from_config(config)?;
+    writeln!(
+        output,
+        "time,estimated_position,estimated_velocity,covariance,gain"
+    )?;
+    output.flush()?;
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("time") {
+            continue;
+        }
+        let (time, measurement) = match parse_line(line) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                writeln!(errors, "line {}: {}", index + 1, err)?;
+                continue;
+            }
+        };
+        if !filter.push(time, measurement)? {
+            writeln!(errors, "line {}: time {} goes backwards", index + 1, time)?;
+            continue;
+        }
+        let estimate = filter.filter();
+        writeln!(
+            output,
+            "{},{},{},{},{}",
+            time,
+            estimate.position(),
+            estimate.velocity(),
+            estimate.uncertainty(),
+            estimate.gain()
+        )?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Time and measurement, if any, of a `time,measurement` line.
+fn parse_line(line: &str) -> Result<(f64, Option<f64>), String> {
+    let (time, measurement) = line.split_once(',').unwrap_or((line, ""));
+    let time: f64 = time
+        .trim()
+        .parse()
+        .map_err(|err| format!("time {}: {}", time.trim(), err))?;
+    let measurement = match measurement.trim() {
+        "" => None,
+        value => Some(
+            value
+                .parse()
+                .map_err(|err| format!("measurement {}: {}", value, err))?,
+        ),
+    };
+    Ok((time, measurement))
+}
+
+#[cfg(test)]
+mod tests {
+    use kalman_filter_simulator::sim::SimulateError;
+
+    use super::*;
+    use crate::testing::default_config;
+
+    /// Output and error lines of a stream of `input`, or the error that ended it.
+    fn stream(input: &str) -> Result<(Vec<String>, Vec<String>), Box<dyn Error>> {
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        filter_stream(
+            &default_config(),
+            input.as_bytes(),
+            &mut output,
+            &mut errors,
+        )?;
+        let lines = |bytes: Vec<u8>| {
+            String::from_utf8(bytes)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        };
+        Ok((lines(output), lines(errors)))
+    }
+
+    #[test]
+    fn skips_malformed_and_backward_lines() {
+        let (output, errors) =
+            stream("time,measurement\n0.1,0.3\n\n0.2,oops\n0.2,\n0.15,0.1\n0.3,0.4\n").unwrap();
+        assert_eq!(output.len(), 4);
+        assert_eq!(
+            output[0],
+            "time,estimated_position,estimated_velocity,covariance,gain"
+        );
+        let times: Vec<&str> = output[1..]
+            .iter()
+            .map(|row| row.split(',').next().unwrap())
+            .collect();
+        assert_eq!(times, ["0.1", "0.2", "0.3"]);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(
+            errors[0].starts_with("line 4: measurement oops"),
+            "{:?}",
+            errors
+        );
+        assert_eq!(errors[1], "line 6: time 0.15 goes backwards");
+    }
+
+    #[test]
+    fn a_nan_estimate_ends_the_stream() {
+        let error = stream("0.1,0.3\n0.2,NaN\n0.3,0.4\n").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<SimulateError>(),
+            Some(&SimulateError::NonFinite {
+                step: 1,
+                quantity: "estimated position"
+            })
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use rand::Rng;
+
+use kalman_filter_simulator::metrics;
+use kalman_filter_simulator::sim::{simulate_model, SimulateConfig, SimulateError};
+
+/// Parameter a convergence study varies.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum StudyParameter {
+    /// Process noise `q`
+    Q,
+    /// Measurement variance `r` the filter assumes
+    R,
+    /// Number of ticks between measurements
+    MeasurementInterval,
+}
+
+impl StudyParameter {
+    /// Scenario-file key of the parameter, used as the CSV column name.
+    fn key(self) -> &'static str {
+        match self {
+            StudyParameter::Q => "q",
+            StudyParameter::R => "r",
+            StudyParameter::MeasurementInterval => "measurement_interval",
+        }
+    }
+
+    /// `config` with the parameter set to `value`, and the value it ends up with.
+    fn apply(self, config: &SimulateConfig, value: f64) -> (SimulateConfig, f64) {
+        match self {
+            StudyParameter::Q => (
+                SimulateConfig {
+                    q: value,
+                    ..*config
+                },
+                value,
+            ),
+            StudyParameter::R => (
+                SimulateConfig {
+                    r: value,
+                    ..*config
+                },
+                value,
+            ),
+            StudyParameter::MeasurementInterval => {
+                let measurement_interval = (value.round() as usize).max(1);
+                let config = SimulateConfig {
+                    measurement_interval,
+                    ..*config
+                };
+                (config, measurement_interval as f64)
+            }
+        }
+    }
+}
+
+pub struct StudyRow {
+    pub value: f64,
+    /// `None` when the gain never settled within the run, or has no steady state
+    pub convergence_time: Option<f64>,
+}
+
+/// Simulates `config` with `parameter` set to each of `values` and measures how long the gain
+/// takes to converge to its steady state, drawing the noise of every run from `rng`.
+pub fn run_convergence_study(
+    config: &SimulateConfig,
+    parameter: StudyParameter,
+    values: &[f64],
+    rng: &mut impl Rng,
+) -> Result<Vec<StudyRow>, SimulateError> {
+    values
+        .iter()
+        .map(|&value| {
+            let (config, value) = parameter.apply(config, value);
+            let result = simulate_model(&config, rng)?;
+            Ok(StudyRow {
+                value,
+                convergence_time: metrics::steady_state_gain(&config).and_then(|gain| {
+                    metrics::convergence_time(&result, gain, metrics::CONVERGENCE_TOLERANCE)
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Writes one `<parameter>,convergence_time` row per value, leaving the time empty where the
+/// gain never converged.
+pub fn write_study_csv(
+    rows: &[StudyRow],
+    parameter: StudyParameter,
+    path: &Path,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{},convergence_time", parameter.key())?;
+    for row in rows {
+        let time = row
+            .convergence_time
+            .map(|time| time.to_string())
+            .unwrap_or_default();
+        writeln!(writer, "{},{}", row.value, time)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir};
+
+    #[test]
+    fn one_row_per_value_converging_faster_with_more_process_noise() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let rows =
+            run_convergence_study(&default_config(), StudyParameter::Q, &[0.01, 1.0], &mut rng)
+                .unwrap();
+        let values: Vec<f64> = rows.iter().map(|row| row.value).collect();
+        assert_eq!(values, [0.01, 1.0]);
+        let (slow, fast) = (
+            rows[0].convergence_time.unwrap(),
+            rows[1].convergence_time.unwrap(),
+        );
+        assert!(fast < slow, "q = 1 took {}, q = 0.01 took {}", fast, slow);
+
+        let path = scratch_dir("study").join("study.csv");
+        write_study_csv(&rows, StudyParameter::Q, &path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        let expected = format!("q,convergence_time\n0.01,{}\n1,{}\n", slow, fast);
+        assert_eq!(csv, expected);
+    }
+
+    #[test]
+    fn measurement_intervals_are_rounded_to_whole_ticks() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let rows = run_convergence_study(
+            &default_config(),
+            StudyParameter::MeasurementInterval,
+            &[0.2, 2.6],
+            &mut rng,
+        )
+        .unwrap();
+        let values: Vec<f64> = rows.iter().map(|row| row.value).collect();
+        assert_eq!(values, [1.0, 3.0]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use kalman_filter_simulator::metrics::{self, ConsistencyTest, Correlogram, WhitenessTest};
+use kalman_filter_simulator::sim::{SimulateConfig, SimulateResult};
+
+/// Largest measurement lag the innovation cross-correlation and autocorrelation look at.
+const CORRELATION_LAGS: usize = 10;
+
+/// End-of-run report printed by `--summary`.
+pub struct Summary {
+    pub steps: usize,
+    pub estimate_rmse: f64,
+    pub measurement_rmse: f64,
+    /// With `--warmup`, the warmup time and the estimate RMSE over the ticks after it
+    pub steady_state_rmse: Option<(f64, f64)>,
+    /// `None` when the filter's gain has no steady state to converge to
+    pub steady_state_gain: Option<f64>,
+    /// `None` when the gain never settled within the run, or has no steady state
+    pub convergence_time: Option<f64>,
+    /// `None` when the run had no ticks
+    pub nees_test: Option<ConsistencyTest>,
+    /// `None` when the run had no measurements
+    pub nis_test: Option<ConsistencyTest>,
+    /// `None` when the run had too few measurements
+    pub cross_correlation: Option<Correlogram>,
+    /// `None` when the run had too few measurements
+    pub whiteness_test: Option<WhitenessTest>,
+    pub time_unit: String,
+    pub outputs: Vec<PathBuf>,
+}
+
+impl Summary {
+    pub fn new(
+        result: &SimulateResult,
+        config: &SimulateConfig,
+        time_unit: &str,
+        confidence: f64,
+        warmup: f64,
+        outputs: Vec<PathBuf>,
+    ) -> Self {
+        let steady_state_gain = metrics::steady_state_gain(config);
+        Summary {
+            steps: result.ticks.len(),
+            estimate_rmse: metrics::estimate_rmse(result),
+            measurement_rmse: metrics::measurement_rmse(result),
+            steady_state_rmse: (warmup > 0.0)
+                .then(|| (warmup, metrics::estimate_rmse(&result.since(warmup)))),
+            steady_state_gain,
+            convergence_time: steady_state_gain.and_then(|gain| {
+                metrics::convergence_time(result, gain, metrics::CONVERGENCE_TOLERANCE)
+            }),
+            nees_test: metrics::nees_test(result, confidence),
+            nis_test: metrics::nis_test(result, confidence),
+            cross_correlation: metrics::innovation_cross_correlation(result, CORRELATION_LAGS),
+            whiteness_test: metrics::whiteness_test(result, CORRELATION_LAGS),
+            time_unit: time_unit.to_string(),
+            outputs,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "=== Summary ===")?;
+        writeln!(f, "steps:             {}", self.steps)?;
+        writeln!(f, "estimate RMSE:     {:.4}", self.estimate_rmse)?;
+        writeln!(f, "measurement RMSE:  {:.4}", self.measurement_rmse)?;
+        writeln!(
+            f,
+            "improvement:       {:.2}x",
+            self.measurement_rmse / self.estimate_rmse
+        )?;
+        if let Some((warmup, rmse)) = self.steady_state_rmse {
+            writeln!(
+                f,
+                "warmup RMSE:       {:.4} (after {} {})",
+                rmse, warmup, self.time_unit
+            )?;
+        }
+        if let Some(gain) = self.steady_state_gain {
+            writeln!(f, "steady-state gain: {:.4}", gain)?;
+            match self.convergence_time {
+                Some(time) => writeln!(f, "convergence time:  {:.2} {}", time, self.time_unit)?,
+                None => writeln!(f, "convergence time:  not reached")?,
+            }
+        }
+        if let Some(test) = &self.nees_test {
+            writeln!(f, "NEES chi-square:   {}", ChiSquare(test))?;
+        }
+        if let Some(test) = &self.nis_test {
+            writeln!(f, "NIS chi-square:    {}", ChiSquare(test))?;
+        }
+        if let Some(cross_correlation) = &self.cross_correlation {
+            if let Some((lag, peak)) = cross_correlation.peak() {
+                let verdict = if cross_correlation.within_bound() {
+                    "white"
+                } else {
+                    "correlated"
+                };
+                writeln!(
+                    f,
+                    "cross-correlation: {:.3} at lag {} within ±{:.3}: {}",
+                    peak, lag, cross_correlation.bound, verdict
+                )?;
+            }
+        }
+        if let Some(test) = &self.whiteness_test {
+            if let Some((lag, peak)) = test.autocorrelation.peak() {
+                writeln!(
+                    f,
+                    "autocorrelation:   {:.3} at lag {} within ±{:.3}: {}",
+                    peak,
+                    lag,
+                    test.autocorrelation.bound,
+                    if test.autocorrelation.within_bound() {
+                        "white"
+                    } else {
+                        "correlated"
+                    }
+                )?;
+            }
+            writeln!(
+                f,
+                "Ljung-Box Q:       {:.1} vs {:.1} at 95% with {} lags: {}",
+                test.q,
+                test.threshold,
+                test.autocorrelation.correlations.len(),
+                if test.passed() { "white" } else { "correlated" }
+            )?;
+        }
+        for output in &self.outputs {
+            writeln!(f, "output:            {}", output.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Sum, interval and verdict of a consistency test, as the summary and `--monte-carlo` print
+/// it.
+pub struct ChiSquare<'a>(pub &'a ConsistencyTest);
+
+impl fmt::Display for ChiSquare<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let test = self.0;
+        write!(
+            f,
+            "{:.1} in [{:.1}, {:.1}] at {}% with {} dof: {}",
+            test.squares.sum,
+            test.lower,
+            test.upper,
+            test.confidence * 100.0,
+            test.squares.dof,
+            if test.passed() { "pass" } else { "fail" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{default_config, seeded_run};
+
+    #[test]
+    fn reports_the_key_fields() {
+        let config = default_config();
+        let result = seeded_run(&config);
+        let summary = Summary::new(
+            &result,
+            &config,
+            "s",
+            0.95,
+            2.0,
+            vec![PathBuf::from("output.gif")],
+        );
+        let text = summary.to_string();
+        for field in [
+            "steps:             100",
+            &format!("estimate RMSE:     {:.4}", summary.estimate_rmse),
+            &format!("measurement RMSE:  {:.4}", summary.measurement_rmse),
+            "warmup RMSE:",
+            "steady-state gain:",
+            "convergence time:",
+            "NEES chi-square:",
+            "NIS chi-square:",
+            "output:            output.gif",
+        ] {
+            assert!(text.contains(field), "{} missing from\n{}", field, text);
+        }
+    }
+}
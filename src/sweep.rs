@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::ImageFormat;
+use rand::Rng;
+
+use kalman_filter_simulator::metrics;
+use kalman_filter_simulator::render::{rasterize, render_heatmap, Heatmap};
+use kalman_filter_simulator::sim::{simulate_model, SimulateConfig, SimulateError};
+
+/// Estimate RMSE of every pair of `q` and `r` a sweep tried.
+pub struct Sweep {
+    pub qs: Vec<f64>,
+    pub rs: Vec<f64>,
+    /// `rmses[r][q]`, indexed like `rs` and `qs`
+    pub rmses: Vec<Vec<f64>>,
+}
+
+impl Sweep {
+    fn heatmap(&self) -> Heatmap<'_> {
+        Heatmap {
+            title: "estimate RMSE",
+            column_name: "q",
+            columns: &self.qs,
+            row_name: "r",
+            rows: &self.rs,
+            values: &self.rmses,
+        }
+    }
+
+    /// `q`, `r` and estimate RMSE of the best tuned pair, if any run stayed finite.
+    pub fn best(&self) -> Option<(f64, f64, f64)> {
+        self.heatmap()
+            .minimum()
+            .map(|(r, q)| (self.qs[q], self.rs[r], self.rmses[r][q]))
+    }
+}
+
+/// Simulates `config` with the filter's `q` and `r` set to every pair of `qs` and `rs`.
+///
+/// Every run starts from a copy of `rng`, and the two only change what the filter assumes,
+/// so all of them filter the same truth and measurements and differ only in the tuning.
+pub fn run_sweep(
+    config: &SimulateConfig,
+    qs: &[f64],
+    rs: &[f64],
+    rng: &(impl Rng + Clone),
+) -> Result<Sweep, SimulateError> {
+    let rmses = rs
+        .iter()
+        .map(|&r| {
+            qs.iter()
+                .map(|&q| {
+                    let config = SimulateConfig { q, r, ..*config };
+                    let result = simulate_model(&config, &mut rng.clone())?;
+                    Ok(metrics::estimate_rmse(&result))
+                })
+                .collect()
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Sweep {
+        qs: qs.to_vec(),
+        rs: rs.to_vec(),
+        rmses,
+    })
+}
+
+/// Writes one `q,r,rmse` row per pair.
+fn write_sweep_csv(sweep: &Sweep, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "q,r,rmse")?;
+    for (r, rmses) in sweep.rs.iter().zip(&sweep.rmses) {
+        for (q, rmse) in sweep.qs.iter().zip(rmses) {
+            writeln!(writer, "{},{},{}", q, r, rmse)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Writes `sweep` to `path` by its extension: the rows of a CSV file, or the heatmap as an
+/// SVG or, for any other extension, a PNG file.
+pub fn write_sweep(sweep: &Sweep, path: &Path) -> Result<(), Box<dyn Error>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_ref().and_then(|extension| extension.to_str()) {
+        Some("csv") => write_sweep_csv(sweep, path)?,
+        Some("svg") => svg::save(path, &render_heatmap(&sweep.heatmap()))?,
+        _ => {
+            let heatmap = sweep.heatmap();
+            let (width, height) = heatmap.size();
+            rasterize(&render_heatmap(&heatmap), width, height)?
+                .save_with_format(path, ImageFormat::Png)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir};
+
+    #[test]
+    fn grid_has_a_row_per_r_and_a_column_per_q() {
+        let config = SimulateConfig {
+            total_time: 2.0,
+            ..default_config()
+        };
+        let (qs, rs) = ([0.001, 0.01, 0.1], [1.0, 4.0]);
+        let sweep = run_sweep(&config, &qs, &rs, &StdRng::seed_from_u64(1)).unwrap();
+        assert_eq!(sweep.rmses.len(), rs.len());
+        assert!(sweep.rmses.iter().all(|row| row.len() == qs.len()));
+
+        // Every pair filters the same measurements, so the pair of the config reproduces its run
+        let result = simulate_model(
+            &SimulateConfig {
+                q: 0.01,
+                r: 4.0,
+                ..config
+            },
+            &mut StdRng::seed_from_u64(1),
+        )
+        .unwrap();
+        assert_eq!(sweep.rmses[1][1], metrics::estimate_rmse(&result));
+
+        let (q, r, rmse) = sweep.best().unwrap();
+        assert!(sweep.rmses.iter().flatten().all(|&other| rmse <= other));
+        assert!(qs.contains(&q) && rs.contains(&r));
+
+        let path = scratch_dir("sweep").join("sweep.csv");
+        write_sweep(&sweep, &path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + qs.len() * rs.len());
+        assert_eq!(lines[0], "q,r,rmse");
+        assert!(lines[2].starts_with("0.01,1,"), "{}", lines[2]);
+        assert!(lines[4].starts_with("0.001,4,"), "{}", lines[4]);
+    }
+}
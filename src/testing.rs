@@ -0,0 +1,31 @@
+use std::fs;
+use std::path::PathBuf;
+
+use kalman_filter_simulator::sim::{simulate_model, SimulateConfig, SimulateResult};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::config::Scenario;
+
+/// Parameters of the built-in scenario.
+pub fn default_config() -> SimulateConfig {
+    Scenario::default().to_config()
+}
+
+/// Run of `config` with its noise drawn from a generator seeded with 1.
+pub fn seeded_run(config: &SimulateConfig) -> SimulateResult {
+    simulate_model(config, &mut StdRng::seed_from_u64(1)).unwrap()
+}
+
+/// Empty directory named after `test` in the system's temporary directory, so tests running
+/// in parallel don't share files.
+pub fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "kalman-filter-simulator-{}-{}",
+        std::process::id(),
+        test
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
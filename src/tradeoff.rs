@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rand::Rng;
+
+use kalman_filter_simulator::metrics;
+use kalman_filter_simulator::sim::{simulate_model, SimulateConfig, SimulateError};
+
+pub struct TradeoffRow {
+    pub rate: f64,
+    pub r: f64,
+    pub rmse: f64,
+}
+
+/// Compares measurement rates under a fixed measurement budget.
+///
+/// The budget is the information per unit time of the base config, which measures every
+/// `config.measurement_interval` ticks with variance `config.r`. Measuring `k` times more often
+/// therefore has to make each measurement `k` times noisier. Every configuration draws its
+/// noise from `rng`.
+pub fn run_tradeoff(
+    config: &SimulateConfig,
+    rates: &[f64],
+    rng: &mut impl Rng,
+) -> Result<Vec<TradeoffRow>, SimulateError> {
+    let base_rate = 1.0 / (config.dt * config.measurement_interval as f64);
+
+    rates
+        .iter()
+        .map(|&rate| {
+            let measurement_interval = ((1.0 / (rate * config.dt)).round() as usize).max(1);
+            // Use the rate the interval actually achieves so `r` stays on budget
+            let rate = 1.0 / (config.dt * measurement_interval as f64);
+            let r = config.r * rate / base_rate;
+
+            let config = SimulateConfig {
+                sensor_noise_stddev: r.sqrt(),
+                r,
+                measurement_interval,
+                ..*config
+            };
+            let result = simulate_model(&config, rng)?;
+
+            Ok(TradeoffRow {
+                rate,
+                r,
+                rmse: metrics::estimate_rmse(&result),
+            })
+        })
+        .collect()
+}
+
+pub fn write_tradeoff_csv(rows: &[TradeoffRow], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "rate,r,rmse")?;
+    for row in rows {
+        writeln!(writer, "{},{},{}", row.rate, row.r, row.rmse)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{default_config, scratch_dir};
+
+    #[test]
+    fn writes_a_row_per_rate() {
+        let config = default_config();
+        let rows = run_tradeoff(&config, &[10.0, 2.0], &mut StdRng::seed_from_u64(1)).unwrap();
+        assert_eq!(rows.len(), 2);
+        // Measuring 5 times less often allows 5 times less noise
+        assert!((rows[0].r / rows[1].r - 5.0).abs() < 1e-9);
+
+        let path = scratch_dir("tradeoff").join("tradeoff.csv");
+        write_tradeoff_csv(&rows, &path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "rate,r,rmse");
+    }
+}
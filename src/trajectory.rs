@@ -0,0 +1,509 @@
+use std::str::FromStr;
+
+use nalgebra::Vector2;
+use rand::{Rng, RngCore};
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+use crate::dynamics::{ConstantVelocity, Dynamics};
+
+/// Most segments a velocity schedule or waypoints a path can have, so they (and every
+/// configuration holding them) stay `Copy`.
+const MAX_SEGMENTS: usize = 8;
+
+/// Position and velocity of the truth at a tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrueState {
+    pub position: f64,
+    pub velocity: f64,
+}
+
+/// True motion, advanced one tick at a time so that it can depend on where the truth got to
+/// or on random draws.
+pub trait TrajectoryModel {
+    /// State of the truth at `time`, `dt` after the tick at which it was at `state`, for a
+    /// nominal speed of `velocity`.
+    fn step(
+        &self,
+        state: TrueState,
+        time: f64,
+        dt: f64,
+        velocity: f64,
+        rng: &mut dyn RngCore,
+    ) -> TrueState;
+}
+
+/// Shape of the true motion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Trajectory {
+    /// Moves at the nominal velocity for the whole run.
+    ConstantVelocity,
+    /// Moves at the nominal velocity until `stop_time`, then holds its position.
+    RampHold { stop_time: f64 },
+    /// Moves at the nominal velocity, except that it accelerates at `acceleration` for
+    /// `duration` from `start_time` and keeps the velocity gained.
+    Maneuver {
+        start_time: f64,
+        duration: f64,
+        acceleration: f64,
+    },
+    /// Moves at the nominal velocity until the first segment of `schedule` starts, then at
+    /// the velocity of the latest segment started.
+    Piecewise { schedule: VelocitySchedule },
+    /// Moves at the nominal velocity plus `acceleration` times the elapsed time.
+    ConstantAcceleration { acceleration: f64 },
+    /// Swings `amplitude` either side of the nominal motion, once every `period`.
+    Sinusoidal { amplitude: f64, period: f64 },
+    /// Heads in a straight line to each of `waypoints` in turn, reaching it at its time, and
+    /// holds at the last.
+    Waypoints { waypoints: Waypoints },
+    /// Moves at the nominal velocity plus a random walk driven by white-noise acceleration of
+    /// spectral density `density`, which the constant-velocity filter models exactly with
+    /// `q = density`.
+    RandomWalk { density: f64 },
+}
+
+/// Velocities that take over at increasing start times, written as a list of
+/// `[start_time, velocity]` pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(f64, f64)>", into = "Vec<(f64, f64)>")]
+pub struct VelocitySchedule {
+    segments: [(f64, f64); MAX_SEGMENTS],
+    len: usize,
+}
+
+impl VelocitySchedule {
+    /// `(start_time, velocity)` of every segment, by start time.
+    pub fn segments(&self) -> &[(f64, f64)] {
+        &self.segments[..self.len]
+    }
+}
+
+impl TryFrom<Vec<(f64, f64)>> for VelocitySchedule {
+    type Error = String;
+
+    fn try_from(segments: Vec<(f64, f64)>) -> Result<Self, String> {
+        let (segments, len) = timed_pairs(&segments, "velocity schedule", "segments")?;
+        Ok(VelocitySchedule { segments, len })
+    }
+}
+
+impl From<VelocitySchedule> for Vec<(f64, f64)> {
+    fn from(schedule: VelocitySchedule) -> Self {
+        schedule.segments().to_vec()
+    }
+}
+
+impl FromStr for VelocitySchedule {
+    type Err = String;
+
+    /// Parses comma-separated `start_time:velocity` segments, such as `3:2.5,7:0`.
+    fn from_str(value: &str) -> Result<Self, String> {
+        VelocitySchedule::try_from(parse_pairs(value, "START_TIME:VELOCITY")?)
+    }
+}
+
+/// Positions to pass through at increasing times, written as a list of `[time, position]`
+/// pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(f64, f64)>", into = "Vec<(f64, f64)>")]
+pub struct Waypoints {
+    waypoints: [(f64, f64); MAX_SEGMENTS],
+    len: usize,
+}
+
+impl Waypoints {
+    /// `(time, position)` of every waypoint, by time.
+    pub fn waypoints(&self) -> &[(f64, f64)] {
+        &self.waypoints[..self.len]
+    }
+
+    /// Position on the straight lines between the waypoints at `time`, which must not come
+    /// before the first waypoint, holding at the last.
+    fn position_at(&self, time: f64) -> f64 {
+        let waypoints = self.waypoints();
+        match waypoints.iter().position(|&(at, _)| at > time) {
+            Some(next) => {
+                let (t0, p0) = waypoints[next - 1];
+                let (t1, p1) = waypoints[next];
+                p0 + (p1 - p0) * (time - t0) / (t1 - t0)
+            }
+            None => waypoints[self.len - 1].1,
+        }
+    }
+}
+
+impl TryFrom<Vec<(f64, f64)>> for Waypoints {
+    type Error = String;
+
+    fn try_from(waypoints: Vec<(f64, f64)>) -> Result<Self, String> {
+        let (waypoints, len) = timed_pairs(&waypoints, "path", "waypoints")?;
+        Ok(Waypoints { waypoints, len })
+    }
+}
+
+impl From<Waypoints> for Vec<(f64, f64)> {
+    fn from(waypoints: Waypoints) -> Self {
+        waypoints.waypoints().to_vec()
+    }
+}
+
+impl FromStr for Waypoints {
+    type Err = String;
+
+    /// Parses comma-separated `time:position` waypoints, such as `4:10,8:-5`.
+    fn from_str(value: &str) -> Result<Self, String> {
+        Waypoints::try_from(parse_pairs(value, "TIME:POSITION")?)
+    }
+}
+
+/// 1 to `MAX_SEGMENTS` finite `(time, value)` pairs with increasing times, copied into a
+/// fixed array. `what` and `items` name them in the errors, as in "a path needs 1 to 8
+/// waypoints".
+fn timed_pairs(
+    pairs: &[(f64, f64)],
+    what: &str,
+    items: &str,
+) -> Result<([(f64, f64); MAX_SEGMENTS], usize), String> {
+    if pairs.is_empty() || pairs.len() > MAX_SEGMENTS {
+        return Err(format!(
+            "a {} needs 1 to {} {}, got {}",
+            what,
+            MAX_SEGMENTS,
+            items,
+            pairs.len()
+        ));
+    }
+    if let Some(&(time, value)) = pairs
+        .iter()
+        .find(|(time, value)| !time.is_finite() || !value.is_finite())
+    {
+        return Err(format!(
+            "{} {} must be finite, got {} at {}",
+            what, items, value, time
+        ));
+    }
+    if let Some(pair) = pairs.windows(2).find(|pair| pair[1].0 <= pair[0].0) {
+        return Err(format!(
+            "{} times must increase, got {} after {}",
+            what, pair[1].0, pair[0].0
+        ));
+    }
+    let mut array = [(0.0, 0.0); MAX_SEGMENTS];
+    array[..pairs.len()].copy_from_slice(pairs);
+    Ok((array, pairs.len()))
+}
+
+/// Parses comma-separated pairs of numbers joined by a colon, as described by `expected`.
+fn parse_pairs(value: &str, expected: &str) -> Result<Vec<(f64, f64)>, String> {
+    value
+        .split(',')
+        .map(|pair| {
+            let (time, value) = pair
+                .split_once(':')
+                .ok_or_else(|| format!("expected {}, got {}", expected, pair))?;
+            let time: f64 = time.trim().parse().map_err(|err| format!("{}", err))?;
+            let value: f64 = value.trim().parse().map_err(|err| format!("{}", err))?;
+            Ok((time, value))
+        })
+        .collect()
+}
+
+impl Trajectory {
+    /// Scalar parameters of the trajectory by name, which must all be finite.
+    pub fn parameters(&self) -> Vec<(&'static str, f64)> {
+        match *self {
+            Trajectory::ConstantVelocity
+            | Trajectory::Piecewise { .. }
+            | Trajectory::Waypoints { .. } => vec![],
+            Trajectory::RampHold { stop_time } => vec![("stop_time", stop_time)],
+            Trajectory::Maneuver {
+                start_time,
+                duration,
+                acceleration,
+            } => vec![
+                ("start_time", start_time),
+                ("duration", duration),
+                ("acceleration", acceleration),
+            ],
+            Trajectory::ConstantAcceleration { acceleration } => {
+                vec![("acceleration", acceleration)]
+            }
+            Trajectory::Sinusoidal { amplitude, period } => {
+                vec![("amplitude", amplitude), ("period", period)]
+            }
+            Trajectory::RandomWalk { density } => vec![("density", density)],
+        }
+    }
+
+    /// Whether the true motion is made of a few regimes of constant velocity or acceleration
+    /// worth marking, rather than changing continuously like a sinusoid or a random walk.
+    pub fn has_regimes(&self) -> bool {
+        !matches!(
+            self,
+            Trajectory::Sinusoidal { .. } | Trajectory::RandomWalk { .. }
+        )
+    }
+
+    /// True velocity at `time` of the trajectories that only depend on the time, for a
+    /// nominal speed of `velocity`.
+    fn velocity_at(&self, time: f64, velocity: f64) -> f64 {
+        match *self {
+            Trajectory::RampHold { stop_time } => {
+                if time < stop_time {
+                    velocity
+                } else {
+                    0.0
+                }
+            }
+            Trajectory::Maneuver {
+                start_time,
+                duration,
+                acceleration,
+            } => velocity + acceleration * (time - start_time).clamp(0.0, duration),
+            Trajectory::Piecewise { schedule } => schedule
+                .segments()
+                .iter()
+                .rev()
+                .find(|&&(start_time, _)| start_time <= time)
+                .map_or(velocity, |&(_, velocity)| velocity),
+            _ => velocity,
+        }
+    }
+}
+
+impl TrajectoryModel for Trajectory {
+    fn step(
+        &self,
+        state: TrueState,
+        time: f64,
+        dt: f64,
+        velocity: f64,
+        rng: &mut dyn RngCore,
+    ) -> TrueState {
+        match *self {
+            Trajectory::ConstantAcceleration { acceleration } => {
+                // The velocity changes linearly, so the trapezoid is the exact distance
+                let next = velocity + acceleration * time;
+                TrueState {
+                    position: state.position + (state.velocity + next) / 2.0 * dt,
+                    velocity: next,
+                }
+            }
+            Trajectory::Sinusoidal { amplitude, period } => {
+                let omega = 2.0 * std::f64::consts::PI / period;
+                TrueState {
+                    position: state.position
+                        + velocity * dt
+                        + amplitude * ((omega * time).sin() - (omega * (time - dt)).sin()),
+                    velocity: velocity + amplitude * omega * (omega * time).cos(),
+                }
+            }
+            Trajectory::Waypoints { waypoints } => {
+                let previous_time = time - dt;
+                let position = match waypoints
+                    .waypoints()
+                    .iter()
+                    .find(|&&(at, _)| at > previous_time)
+                {
+                    // Head for the next waypoint from wherever the truth is
+                    Some(&(at, target)) if time <= at => {
+                        state.position + (target - state.position) * dt / (at - previous_time)
+                    }
+                    // The truth reached a waypoint during the tick and is on the path now
+                    Some(_) => waypoints.position_at(time),
+                    None => state.position,
+                };
+                TrueState {
+                    position,
+                    velocity: (position - state.position) / dt,
+                }
+            }
+            Trajectory::RandomWalk { density } => {
+                // Without noise, Q has no Cholesky factor and the truth moves at constant velocity
+                let mut next = Vector2::new(state.position + state.velocity * dt, state.velocity);
+                if let Some(cholesky) = ConstantVelocity.process_noise(density, dt).cholesky() {
+                    next += cholesky.l()
+                        * Vector2::new(rng.sample(StandardNormal), rng.sample(StandardNormal));
+                }
+                TrueState {
+                    position: next[0],
+                    velocity: next[1],
+                }
+            }
+            _ => {
+                let velocity = self.velocity_at(time, velocity);
+                TrueState {
+                    position: state.position + velocity * dt,
+                    velocity,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::{config, run};
+    use crate::sim::SimulateConfig;
+
+    #[test]
+    fn ramp_hold_stays_put_after_the_stop() {
+        let stop_time = 4.05;
+        let result = run(
+            &SimulateConfig {
+                trajectory: Trajectory::RampHold { stop_time },
+                ..config()
+            },
+            1,
+        );
+        let held: Vec<f64> = result
+            .ticks
+            .iter()
+            .filter(|tick| tick.true_positions.0 > stop_time)
+            .map(|tick| tick.true_positions.1)
+            .collect();
+        assert!(held.len() > 1);
+        assert!(held.iter().all(|&position| position == held[0]));
+        assert!((held[0] - stop_time).abs() < 0.1);
+    }
+
+    #[test]
+    fn piecewise_slope_changes_at_the_scheduled_times() {
+        let schedule = VelocitySchedule::try_from(vec![(3.05, 2.5), (7.05, 0.0)]).unwrap();
+        let result = run(
+            &SimulateConfig {
+                trajectory: Trajectory::Piecewise { schedule },
+                ..config()
+            },
+            1,
+        );
+        for pair in result.ticks.windows(2) {
+            let (start, from) = pair[0].true_positions;
+            let (time, to) = pair[1].true_positions;
+            let expected = if time < 3.05 {
+                1.0
+            } else if time < 7.05 {
+                2.5
+            } else {
+                0.0
+            };
+            let slope = (to - from) / (time - start);
+            assert!(
+                (slope - expected).abs() < 1e-9,
+                "slope {} at {}",
+                slope,
+                time
+            );
+        }
+    }
+
+    /// `(time, true position, true velocity)` of every tick of a run along `trajectory`. The
+    /// truth starts from the initial position a `dt` before the first tick.
+    fn truth(trajectory: Trajectory, seed: u64) -> Vec<(f64, f64, f64)> {
+        run(
+            &SimulateConfig {
+                trajectory,
+                ..config()
+            },
+            seed,
+        )
+        .ticks
+        .iter()
+        .map(|tick| {
+            (
+                tick.true_positions.0,
+                tick.true_positions.1,
+                tick.true_velocity,
+            )
+        })
+        .collect()
+    }
+
+    /// Checks that the velocity of every tick is the central difference of the positions
+    /// around it, within `tolerance`.
+    fn assert_velocity_is_the_derivative(truth: &[(f64, f64, f64)], tolerance: f64) {
+        for window in truth.windows(3) {
+            let (before, (time, _, velocity), after) = (window[0], window[1], window[2]);
+            let slope = (after.1 - before.1) / (after.0 - before.0);
+            assert!(
+                (slope - velocity).abs() < tolerance,
+                "velocity {} but slope {} at {}",
+                velocity,
+                slope,
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn constant_acceleration_follows_the_parabola() {
+        let truth = truth(Trajectory::ConstantAcceleration { acceleration: 0.5 }, 1);
+        let start = truth[0].1;
+        for &(time, position, velocity) in &truth {
+            let expected = start + time + 0.25 * time * time;
+            assert!((position - expected).abs() < 1e-9, "{}", time);
+            assert!((velocity - (1.0 + 0.5 * time)).abs() < 1e-9, "{}", time);
+        }
+        assert_velocity_is_the_derivative(&truth, 1e-9);
+    }
+
+    #[test]
+    fn sinusoidal_swings_around_the_nominal_motion() {
+        let (amplitude, period) = (2.0, 4.0);
+        let omega = 2.0 * std::f64::consts::PI / period;
+        let truth = truth(Trajectory::Sinusoidal { amplitude, period }, 1);
+        let start = truth[0].1;
+        for &(time, position, velocity) in &truth {
+            let expected = start + time + amplitude * (omega * time).sin();
+            assert!((position - expected).abs() < 1e-9, "{}", time);
+            let expected = 1.0 + amplitude * omega * (omega * time).cos();
+            assert!((velocity - expected).abs() < 1e-9, "{}", time);
+        }
+        // The central difference errs by about `amplitude ω³ dt² / 6`
+        assert_velocity_is_the_derivative(&truth, 0.02);
+    }
+
+    #[test]
+    fn waypoints_are_reached_on_time_and_held() {
+        let waypoints = Waypoints::try_from(vec![(0.0, 0.0), (3.0, 6.0), (6.0, 3.0)]).unwrap();
+        let truth = truth(Trajectory::Waypoints { waypoints }, 1);
+        for &(time, position, velocity) in &truth {
+            let (expected, slope) = if time <= 3.0 + 1e-9 {
+                (2.0 * time, 2.0)
+            } else if time <= 6.0 + 1e-9 {
+                (6.0 - (time - 3.0), -1.0)
+            } else {
+                (3.0, 0.0)
+            };
+            assert!((position - expected).abs() < 1e-9, "{}: {}", time, position);
+            // Each tick's velocity is the slope of the line it moved along in that tick, which
+            // only mixes two lines around the waypoints
+            if ![0.0, 3.0, 6.0].iter().any(|at| (time - at).abs() < 0.15) {
+                assert!((velocity - slope).abs() < 1e-9, "{}: {}", time, velocity);
+            }
+        }
+    }
+
+    #[test]
+    fn random_walk_repeats_with_its_seed() {
+        let walk = Trajectory::RandomWalk { density: 0.5 };
+        let first = truth(walk, 1);
+        assert_eq!(first, truth(walk, 1));
+        assert_ne!(first, truth(walk, 2));
+        // It wanders off the nominal line, and moves by its velocity plus the noise in between
+        assert!(first
+            .iter()
+            .any(|&(time, position, _)| (position - time).abs() > 0.5));
+        for pair in first.windows(2) {
+            let ((start, from, velocity), (time, to, _)) = (pair[0], pair[1]);
+            assert!((to - from - velocity * (time - start)).abs() < 0.5);
+        }
+        assert_eq!(
+            truth(Trajectory::RandomWalk { density: 0.0 }, 1),
+            truth(Trajectory::ConstantVelocity, 1)
+        );
+    }
+}
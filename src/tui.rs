@@ -0,0 +1,199 @@
+use std::io;
+use std::time::Duration;
+
+use ratatui::backend::Backend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::Alignment;
+use ratatui::style::{Color, Style};
+use ratatui::symbols::Marker;
+use ratatui::widgets::{Axis, Block, Chart, Dataset, GraphType};
+use ratatui::Terminal;
+
+use kalman_filter_simulator::render::Palette;
+use kalman_filter_simulator::sim::SimulateResult;
+
+/// Time each tick stays on screen before the next one is drawn.
+const TICK_DURATION: Duration = Duration::from_millis(30);
+
+/// Replays `result` tick by tick as a live terminal chart until the user presses `q` or Esc.
+pub fn run_tui(result: &SimulateResult, palette: &Palette, time_unit: &str) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let outcome = replay(&mut terminal, result, palette, time_unit, quit_pressed);
+    ratatui::try_restore()?;
+    outcome
+}
+
+/// Waits up to a tick for a key press, and tells whether it was `q` or Esc.
+fn quit_pressed() -> io::Result<bool> {
+    if event::poll(TICK_DURATION)? {
+        if let Event::Key(key) = event::read()? {
+            return Ok(key.kind == KeyEventKind::Press
+                && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc));
+        }
+    }
+    Ok(false)
+}
+
+/// Draws one more tick of `result` on `terminal` every time `quit` says to carry on, until it
+/// says to stop.
+fn replay<B: Backend>(
+    terminal: &mut Terminal<B>,
+    result: &SimulateResult,
+    palette: &Palette,
+    time_unit: &str,
+    mut quit: impl FnMut() -> io::Result<bool>,
+) -> io::Result<()>
+where
+    B::Error: Send + Sync + 'static,
+{
+    let true_positions: Vec<_> = result
+        .ticks
+        .iter()
+        .map(|tick| tick.true_positions)
+        .collect();
+    let measured_positions: Vec<_> = result
+        .ticks
+        .iter()
+        .map(|tick| tick.measured_positions)
+        .collect();
+    let estimated_positions: Vec<_> = result
+        .ticks
+        .iter()
+        .map(|tick| tick.estimated_positions)
+        .collect();
+    let time_bounds = bounds(true_positions.iter().map(|&(time, _)| time));
+    let position_bounds = bounds(
+        true_positions
+            .iter()
+            .chain(estimated_positions.iter())
+            .chain(measured_positions.iter().flatten())
+            .map(|&(_, position)| position),
+    );
+
+    let color = |name: &str| name.parse().unwrap_or(Color::Reset);
+    let mut shown = 0;
+    loop {
+        if shown < result.ticks.len() {
+            shown += 1;
+        }
+        let measured: Vec<_> = measured_positions[..shown]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        let finished = shown == result.ticks.len();
+        terminal
+            .draw(|frame| {
+                let datasets = vec![
+                    Dataset::default()
+                        .name("true")
+                        .marker(Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().fg(color(palette.truth.color)))
+                        .data(&true_positions[..shown]),
+                    Dataset::default()
+                        .name("estimated")
+                        .marker(Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().fg(color(palette.estimate.color)))
+                        .data(&estimated_positions[..shown]),
+                    Dataset::default()
+                        .name("measured")
+                        .marker(Marker::Dot)
+                        .graph_type(GraphType::Scatter)
+                        .style(Style::default().fg(color(palette.measurement)))
+                        .data(&measured),
+                ];
+                let title = if finished {
+                    "Kalman Filter Simulator (q to quit)"
+                } else {
+                    "Kalman Filter Simulator"
+                };
+                let chart = Chart::new(datasets)
+                    .block(
+                        Block::bordered()
+                            .title(title)
+                            .title_alignment(Alignment::Center),
+                    )
+                    .x_axis(
+                        Axis::default()
+                            .title(format!("time [{}]", time_unit))
+                            .bounds(time_bounds)
+                            .labels(bound_labels(time_bounds)),
+                    )
+                    .y_axis(
+                        Axis::default()
+                            .title("position")
+                            .bounds(position_bounds)
+                            .labels(bound_labels(position_bounds)),
+                    );
+                frame.render_widget(chart, frame.area());
+            })
+            .map_err(io::Error::other)?;
+
+        // Keep polling once the replay is done so the final chart stays up until dismissed
+        if quit()? {
+            return Ok(());
+        }
+    }
+}
+
+/// Smallest and largest of `values`, widened when they coincide so the axis has some extent.
+fn bounds(values: impl Iterator<Item = f64>) -> [f64; 2] {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if !(min.is_finite() && max.is_finite()) {
+        return [0.0, 1.0];
+    }
+    if min == max {
+        return [min - 0.5, max + 0.5];
+    }
+    [min, max]
+}
+
+fn bound_labels([min, max]: [f64; 2]) -> Vec<String> {
+    vec![
+        format!("{:.1}", min),
+        format!("{:.1}", (min + max) / 2.0),
+        format!("{:.1}", max),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use kalman_filter_simulator::render::Theme;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+    use crate::testing::{default_config, seeded_run};
+
+    #[test]
+    fn replays_to_the_end_off_screen() {
+        let result = seeded_run(&default_config());
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let mut polls = 0;
+        replay(
+            &mut terminal,
+            &result,
+            &Theme::OkabeIto.palette(),
+            "s",
+            || {
+                polls += 1;
+                Ok(polls > result.ticks.len())
+            },
+        )
+        .unwrap();
+        assert_eq!(polls, result.ticks.len() + 1);
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("(q to quit)"));
+        assert!(screen.contains("time [s]"));
+    }
+}
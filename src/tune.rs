@@ -0,0 +1,278 @@
+use std::cell::Cell;
+
+use clap::ValueEnum;
+
+use kalman_filter_simulator::metrics;
+use kalman_filter_simulator::sim::{SimulateConfig, SimulateError, SimulateResult};
+
+/// What `--auto-tune` optimizes.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TuneObjective {
+    /// Minimize the estimate RMSE against the true position
+    Rmse,
+    /// Maximize the log-likelihood of the measurements, which needs no true position
+    Likelihood,
+}
+
+impl TuneObjective {
+    /// Value of the objective for `result`, lower being better.
+    fn cost(self, result: &SimulateResult) -> f64 {
+        match self {
+            TuneObjective::Rmse => metrics::estimate_rmse(result),
+            TuneObjective::Likelihood => -metrics::innovation_log_likelihood(result),
+        }
+    }
+
+    /// The objective as reported, from its `cost`.
+    pub fn value(self, cost: f64) -> f64 {
+        match self {
+            TuneObjective::Rmse => cost,
+            TuneObjective::Likelihood => -cost,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TuneObjective::Rmse => "estimate RMSE",
+            TuneObjective::Likelihood => "log-likelihood",
+        }
+    }
+}
+
+/// `q` and `r` an auto-tune started from and settled on, and the cost of both.
+pub struct Tuned {
+    pub start: (f64, f64, f64),
+    pub best: (f64, f64, f64),
+    /// Number of runs the search took
+    pub evaluations: usize,
+    /// Whether `best` lies on the edge of the search range, where the objective may keep
+    /// improving without bound, as the RMSE does once the filter may ignore the measurements
+    pub at_bound: bool,
+}
+
+/// Most runs a search takes before it stops, whether or not it converged.
+const MAX_EVALUATIONS: usize = 400;
+
+/// Size in decades of `q` and `r` below which the search counts as converged.
+const TOLERANCE: f64 = 1e-3;
+
+/// Range of `log10 q` and `log10 r` the search stays in.
+const SEARCH_DECADES: (f64, f64) = (-8.0, 8.0);
+
+/// Searches for the `q` and `r` with the lowest cost of `objective` with the Nelder-Mead
+/// simplex method, starting from the `q` and `r` of `config` and a step of one decade. They
+/// are searched in log space, which keeps them positive and suits their scale, between `1e-8`
+/// and `1e8`; a `q` of 0 starts from the lower end.
+///
+/// Apart from the initial covariance, the estimate only depends on the ratio of `q` to `r`,
+/// so the RMSE can't tell them apart: it searches `q` alone and keeps the `r` of `config`.
+/// The likelihood also weighs how large the innovations are against `S`, and searches both.
+///
+/// `run` runs the filter with the parameters it's given, and should filter the same
+/// measurements every time so the cost only depends on the tuning. Runs that turn non-finite
+/// cost infinitely much, but other errors end the search.
+pub fn run_auto_tune(
+    config: &SimulateConfig,
+    objective: TuneObjective,
+    run: impl Fn(&SimulateConfig) -> Result<SimulateResult, SimulateError>,
+) -> Result<Tuned, SimulateError> {
+    let parameters = |point: &[f64]| {
+        let q = 10f64.powf(point[0]);
+        let r = point.get(1).map_or(config.r, |r| 10f64.powf(*r));
+        (q, r)
+    };
+    let cost = |point: &[f64]| -> Result<f64, SimulateError> {
+        let (q, r) = parameters(point);
+        match run(&SimulateConfig { q, r, ..*config }) {
+            Ok(result) => Ok(objective.cost(&result)),
+            Err(SimulateError::NonFinite { .. }) => Ok(f64::INFINITY),
+            Err(err) => Err(err),
+        }
+    };
+
+    let mut start = vec![config.q.log10()];
+    if objective == TuneObjective::Likelihood {
+        start.push(config.r.log10());
+    }
+    let search = nelder_mead(start, cost)?;
+    let (q, r) = parameters(&search.start);
+    let (best_q, best_r) = parameters(&search.best);
+    Ok(Tuned {
+        start: (q, r, search.start_cost),
+        best: (best_q, best_r, search.best_cost),
+        evaluations: search.evaluations,
+        at_bound: search
+            .best
+            .iter()
+            .any(|&x| x <= SEARCH_DECADES.0 || x >= SEARCH_DECADES.1),
+    })
+}
+
+/// Where a `nelder_mead` search started and ended, moved into the search range, and the
+/// cost of both.
+struct Search {
+    start: Vec<f64>,
+    start_cost: f64,
+    best: Vec<f64>,
+    best_cost: f64,
+    evaluations: usize,
+}
+
+/// Nelder-Mead search for the lowest `cost` within `SEARCH_DECADES` from `start`, with a
+/// first step of one decade along each axis.
+fn nelder_mead<E>(start: Vec<f64>, cost: impl Fn(&[f64]) -> Result<f64, E>) -> Result<Search, E> {
+    let evaluations = Cell::new(0);
+    // Evaluates `point`, moved into the search range
+    let vertex = |point: Vec<f64>| -> Result<(Vec<f64>, f64), E> {
+        evaluations.set(evaluations.get() + 1);
+        let point: Vec<f64> = point
+            .into_iter()
+            .map(|x| x.clamp(SEARCH_DECADES.0, SEARCH_DECADES.1))
+            .collect();
+        let cost = cost(&point)?;
+        Ok((point, cost))
+    };
+    // Moves from `from` by `t` times the way to `to`
+    let along = |from: &[f64], to: &[f64], t: f64| -> Vec<f64> {
+        from.iter().zip(to).map(|(a, b)| a + t * (b - a)).collect()
+    };
+
+    let start = vertex(start)?;
+    let n = start.0.len();
+    let mut simplex = vec![start.clone()];
+    for axis in 0..n {
+        let mut point = start.0.clone();
+        // One decade up, or down from the top of the range
+        point[axis] += if point[axis] + 1.0 <= SEARCH_DECADES.1 {
+            1.0
+        } else {
+            -1.0
+        };
+        simplex.push(vertex(point)?);
+    }
+    loop {
+        simplex.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        let best = simplex[0].0.clone();
+        let size = simplex[1..]
+            .iter()
+            .flat_map(|(point, _)| point.iter().zip(&best).map(|(x, b)| (x - b).abs()))
+            .fold(0.0, f64::max);
+        if size < TOLERANCE || evaluations.get() >= MAX_EVALUATIONS {
+            break;
+        }
+
+        let (worst, worst_cost) = simplex[n].clone();
+        let centroid: Vec<f64> = (0..n)
+            .map(|axis| {
+                simplex[..n]
+                    .iter()
+                    .map(|(point, _)| point[axis])
+                    .sum::<f64>()
+                    / n as f64
+            })
+            .collect();
+        let reflected = vertex(along(&centroid, &worst, -1.0))?;
+        if reflected.1 < simplex[0].1 {
+            let expanded = vertex(along(&centroid, &worst, -2.0))?;
+            simplex[n] = if expanded.1 < reflected.1 {
+                expanded
+            } else {
+                reflected
+            };
+        } else if reflected.1 < simplex[n - 1].1 {
+            simplex[n] = reflected;
+        } else {
+            let t = if reflected.1 < worst_cost { -0.5 } else { 0.5 };
+            let contracted = vertex(along(&centroid, &worst, t))?;
+            if contracted.1 < worst_cost.min(reflected.1) {
+                simplex[n] = contracted;
+            } else {
+                // Shrink towards the best point
+                for corner in &mut simplex[1..] {
+                    *corner = vertex(along(&best, &corner.0, 0.5))?;
+                }
+            }
+        }
+    }
+    let (best, best_cost) = simplex.swap_remove(0);
+    Ok(Search {
+        start: start.0,
+        start_cost: start.1,
+        best,
+        best_cost,
+        evaluations: evaluations.get(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use kalman_filter_simulator::sim::SimulateTick;
+
+    use super::*;
+    use crate::testing::default_config;
+
+    /// A one-tick run whose estimate is off by `error`, so its RMSE is `|error|`.
+    fn run_with_error(error: f64) -> SimulateResult {
+        SimulateResult {
+            ticks: vec![SimulateTick {
+                estimated_positions: (0.0, error),
+                ..SimulateTick::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn finds_the_minimum_of_a_quadratic_in_log_q() {
+        let config = SimulateConfig {
+            q: 1.0,
+            ..default_config()
+        };
+        let tuned = run_auto_tune(&config, TuneObjective::Rmse, |config| {
+            Ok(run_with_error((config.q.log10() + 2.5).powi(2) + 0.5))
+        })
+        .unwrap();
+        let (q, r, cost) = tuned.best;
+        assert!((q.log10() + 2.5).abs() < 0.01, "{}", q);
+        assert_eq!(r, config.r);
+        assert!((cost - 0.5).abs() < 1e-4);
+        assert_eq!(tuned.start, (1.0, config.r, 6.75));
+        assert!(!tuned.at_bound);
+        assert!(tuned.evaluations < MAX_EVALUATIONS);
+    }
+
+    #[test]
+    fn stops_at_the_edge_of_the_search_range() {
+        // Keeps falling towards q = 0, past the lower end of the range
+        let tuned = run_auto_tune(&default_config(), TuneObjective::Rmse, |config| {
+            Ok(run_with_error(config.q.log10() + 20.0))
+        })
+        .unwrap();
+        assert_eq!(tuned.best.0, 10f64.powf(SEARCH_DECADES.0));
+        assert!(tuned.at_bound);
+
+        // Non-finite runs cost infinitely much, so the search keeps clear of them
+        let tuned = run_auto_tune(&default_config(), TuneObjective::Rmse, |config| {
+            if config.q > 1.0 {
+                return Err(SimulateError::NonFinite {
+                    step: 0,
+                    quantity: "gain",
+                });
+            }
+            Ok(run_with_error(-config.q.log10()))
+        })
+        .unwrap();
+        assert!((tuned.best.0 - 1.0).abs() < 0.01, "{}", tuned.best.0);
+        assert!(!tuned.at_bound);
+    }
+
+    #[test]
+    fn searches_two_dimensions() {
+        let search = nelder_mead(vec![0.0, 0.0], |point| {
+            Ok::<_, ()>((point[0] - 1.0).powi(2) + (point[1] + 3.0).powi(2))
+        })
+        .unwrap();
+        assert!((search.best[0] - 1.0).abs() < 0.01, "{:?}", search.best);
+        assert!((search.best[1] + 3.0).abs() < 0.01, "{:?}", search.best);
+        assert_eq!(search.start_cost, 10.0);
+    }
+}
@@ -0,0 +1,375 @@
+use nalgebra::{Matrix1, SMatrix, SVector, Vector1, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::ekf::{NonlinearModel, Tracking};
+use crate::filter::Filter;
+use crate::sim::SimulateConfig;
+
+/// Spread and weighting of the sigma points of an `UnscentedKalmanFilter`.
+///
+/// With `λ = alpha² (N + kappa) - N`, the sigma points lie `√(N + λ)` standard deviations
+/// from the mean. `beta` adds weight to the central point when forming the covariance; 2 is
+/// optimal for Gaussian distributions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnscentedParameters {
+    pub alpha: f64,
+    pub beta: f64,
+    pub kappa: f64,
+}
+
+impl Default for UnscentedParameters {
+    fn default() -> Self {
+        UnscentedParameters {
+            alpha: 1e-3,
+            beta: 2.0,
+            kappa: 0.0,
+        }
+    }
+}
+
+impl UnscentedParameters {
+    /// Whether the sigma points of an `n`-dimensional state are well defined, which needs a
+    /// positive `alpha` and `n + kappa > 0`.
+    pub fn is_valid(&self, n: usize) -> bool {
+        self.alpha.is_finite()
+            && self.alpha > 0.0
+            && self.beta.is_finite()
+            && self.kappa.is_finite()
+            && n as f64 + self.kappa > 0.0
+    }
+
+    /// `N + λ`, the squared distance of the sigma points from the mean in standard deviations.
+    fn spread(&self, n: usize) -> f64 {
+        self.alpha * self.alpha * (n as f64 + self.kappa)
+    }
+
+    /// Weights of the central sigma point in the mean and the covariance, and of every other
+    /// point in both.
+    fn weights(&self, n: usize) -> (f64, f64, f64) {
+        let spread = self.spread(n);
+        let lambda = spread - n as f64;
+        let mean = lambda / spread;
+        let covariance = mean + 1.0 - self.alpha * self.alpha + self.beta;
+        (mean, covariance, 1.0 / (2.0 * spread))
+    }
+}
+
+/// Unscented Kalman filter: passes `2N + 1` sigma points through the nonlinear `model`
+/// itself and takes the mean and covariance of where they land, so it needs no Jacobians.
+#[derive(Debug, Clone)]
+pub struct UnscentedKalmanFilter<T, const N: usize, const M: usize> {
+    model: T,
+    parameters: UnscentedParameters,
+    /// State estimate
+    pub x: SVector<f64, N>,
+    /// Covariance of the state estimate
+    pub p: SMatrix<f64, N, N>,
+    /// Spectral density of the driving white noise
+    pub q: f64,
+    /// Measurement noise covariance
+    pub r: SMatrix<f64, M, M>,
+    k: SMatrix<f64, N, M>,
+    innovation: SVector<f64, M>,
+    innovation_covariance: SMatrix<f64, M, M>,
+}
+
+impl<T: NonlinearModel<N, M>, const N: usize, const M: usize> UnscentedKalmanFilter<T, N, M> {
+    /// Starts from the estimate `x` with covariance `p`, before any update.
+    pub fn new(
+        model: T,
+        parameters: UnscentedParameters,
+        x: SVector<f64, N>,
+        p: SMatrix<f64, N, N>,
+        q: f64,
+        r: SMatrix<f64, M, M>,
+    ) -> Self {
+        UnscentedKalmanFilter {
+            model,
+            parameters,
+            x,
+            p,
+            q,
+            r,
+            k: SMatrix::zeros(),
+            innovation: SVector::zeros(),
+            innovation_covariance: SMatrix::zeros(),
+        }
+    }
+
+    /// The central sigma point `x`, followed by `x ± √(N + λ)` times each column of the
+    /// Cholesky factor of `p`. A `p` that is not positive definite has no such factor, and
+    /// every point is NaN.
+    fn sigma_points(&self) -> Vec<SVector<f64, N>> {
+        let scaled = self.p * self.parameters.spread(N);
+        let Some(cholesky) = scaled.cholesky() else {
+            return vec![SVector::repeat(f64::NAN); 2 * N + 1];
+        };
+        let l = cholesky.l();
+        let mut points = Vec::with_capacity(2 * N + 1);
+        points.push(self.x);
+        points.extend(l.column_iter().map(|column| self.x + column));
+        points.extend(l.column_iter().map(|column| self.x - column));
+        points
+    }
+
+    /// Weighted mean of the sigma points after mapping them through the model, with the mean
+    /// weights.
+    fn unscented_mean<const D: usize>(&self, points: &[SVector<f64, D>]) -> SVector<f64, D> {
+        let (central, _, other) = self.parameters.weights(N);
+        points[1..]
+            .iter()
+            .fold(points[0] * central, |mean, point| mean + point * other)
+    }
+
+    /// Sum of `(a - a_mean)(b - b_mean)ᵀ` over the sigma points, with the covariance weights.
+    fn unscented_covariance<const A: usize, const B: usize>(
+        &self,
+        a: (&[SVector<f64, A>], &SVector<f64, A>),
+        b: (&[SVector<f64, B>], &SVector<f64, B>),
+    ) -> SMatrix<f64, A, B> {
+        let (_, central, other) = self.parameters.weights(N);
+        a.0.iter()
+            .zip(b.0)
+            .enumerate()
+            .fold(SMatrix::zeros(), |sum, (i, (a_point, b_point))| {
+                let weight = if i == 0 { central } else { other };
+                sum + (a_point - a.1) * (b_point - b.1).transpose() * weight
+            })
+    }
+
+    /// Propagates the estimate a step of `dt` through the sigma points, then adds the process
+    /// noise to the covariance.
+    pub fn predict(&mut self, dt: f64) {
+        let points: Vec<_> = self
+            .sigma_points()
+            .iter()
+            .map(|point| self.model.transition(point, dt))
+            .collect();
+        let x = self.unscented_mean(&points);
+        self.p = self.unscented_covariance((&points, &x), (&points, &x))
+            + self.model.process_noise(self.q, dt);
+        self.x = x;
+    }
+
+    /// Corrects the estimate with the measurement `z`.
+    pub fn update(&mut self, z: &SVector<f64, M>) {
+        let r = self.r;
+        self.update_with_covariance(z, &r);
+    }
+
+    /// Like `update`, but assumes measurement noise covariance `r` for this measurement only.
+    ///
+    /// As in `linear::KalmanFilter`, an innovation covariance that is not positive definite
+    /// turns the estimate NaN.
+    pub fn update_with_covariance(&mut self, z: &SVector<f64, M>, r: &SMatrix<f64, M, M>) {
+        let points = self.sigma_points();
+        let measurements: Vec<_> = points
+            .iter()
+            .map(|point| self.model.measurement(point))
+            .collect();
+        let expected = self.unscented_mean(&measurements);
+        let s =
+            self.unscented_covariance((&measurements, &expected), (&measurements, &expected)) + r;
+        let cross = self.unscented_covariance((&points, &self.x), (&measurements, &expected));
+        // K = Pxz S⁻¹, solved as Kᵀ = S⁻¹ Pxzᵀ since S is symmetric
+        self.k = match s.cholesky() {
+            Some(cholesky) => cholesky.solve(&cross.transpose()).transpose(),
+            None => SMatrix::repeat(f64::NAN),
+        };
+        let innovation = z - expected;
+        self.x += self.k * innovation;
+        self.p -= self.k * s * self.k.transpose();
+        self.innovation = innovation;
+        self.innovation_covariance = s;
+    }
+
+    /// Gain `K` applied by the latest update.
+    pub fn gain(&self) -> &SMatrix<f64, N, M> {
+        &self.k
+    }
+
+    /// Innovation of the latest update, the measurement minus the mean of the sigma points'
+    /// predicted measurements.
+    pub fn innovation(&self) -> &SVector<f64, M> {
+        &self.innovation
+    }
+
+    /// Covariance of the latest innovation, the sigma points' measurement spread plus `R`.
+    pub fn innovation_covariance(&self) -> &SMatrix<f64, M, M> {
+        &self.innovation_covariance
+    }
+}
+
+impl UnscentedKalmanFilter<Tracking, 2, 1> {
+    /// Tracks the sensor of `config` from its initial guess, with unit variance on the
+    /// position and velocity like `ExtendedKalmanFilter::from_config`.
+    pub fn from_config(config: &SimulateConfig) -> Self {
+        UnscentedKalmanFilter::new(
+            Tracking {
+                sensor: config.sensor,
+            },
+            config.unscented,
+            Vector2::new(
+                config.initial_position + config.initial_position_offset,
+                config.velocity + config.initial_velocity_offset,
+            ),
+            SMatrix::identity(),
+            config.q,
+            Matrix1::new(config.r),
+        )
+    }
+}
+
+impl<T: NonlinearModel<N, 1>, const N: usize> Filter for UnscentedKalmanFilter<T, N, 1> {
+    fn predict(&mut self, dt: f64) {
+        UnscentedKalmanFilter::predict(self, dt);
+    }
+
+    fn update(&mut self, measurement: f64) {
+        UnscentedKalmanFilter::update(self, &Vector1::new(measurement));
+    }
+
+    fn update_with_variance(&mut self, measurement: f64, r: f64) {
+        self.update_with_covariance(&Vector1::new(measurement), &Matrix1::new(r));
+    }
+
+    fn position(&self) -> f64 {
+        self.x[0]
+    }
+
+    fn velocity(&self) -> f64 {
+        if N > 1 {
+            self.x[1]
+        } else {
+            0.0
+        }
+    }
+
+    fn uncertainty(&self) -> f64 {
+        self.p[(0, 0)]
+    }
+
+    fn gain(&self) -> f64 {
+        self.k[0]
+    }
+
+    fn innovation(&self) -> (f64, f64) {
+        (self.innovation[0], self.innovation_covariance[0])
+    }
+
+    fn process_noise(&self) -> f64 {
+        self.q
+    }
+
+    fn set_process_noise(&mut self, q: f64) {
+        self.q = q;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Matrix1x2;
+
+    use super::*;
+    use crate::dynamics::{ConstantVelocity, Dynamics, FilterModel};
+    use crate::linear::KalmanFilter;
+    use crate::sim::tests::{config, run};
+    use crate::sim::SimulateError;
+
+    #[test]
+    fn mean_weights_sum_to_one_for_any_spread() {
+        for alpha in [1e-3, 0.1, 1.0] {
+            let parameters = UnscentedParameters {
+                alpha,
+                ..UnscentedParameters::default()
+            };
+            let (central, _, other) = parameters.weights(2);
+            let sum = central + 4.0 * other;
+            assert!((sum - 1.0).abs() < 1e-9, "alpha {}: {}", alpha, sum);
+        }
+        assert!(UnscentedParameters::default().weights(2).0 < -1e5);
+    }
+
+    #[test]
+    fn linear_sensor_matches_the_linear_filter() {
+        // The default alpha weighs the central point near -1e6 and the others near 2.5e5, whose
+        // cancellation costs about six of the digits a well-spread alpha keeps
+        for (alpha, tolerance) in [(1e-3, 1e-7), (1.0, 1e-12)] {
+            let config = SimulateConfig {
+                model: FilterModel::Unscented,
+                unscented: UnscentedParameters {
+                    alpha,
+                    ..UnscentedParameters::default()
+                },
+                ..config()
+            };
+            let mut unscented = UnscentedKalmanFilter::from_config(&config);
+            let mut linear = KalmanFilter::new(
+                unscented.x,
+                unscented.p,
+                ConstantVelocity.transition(config.dt),
+                Matrix1x2::new(1.0, 0.0),
+                ConstantVelocity.process_noise(config.q, config.dt),
+                Matrix1::new(config.r),
+            );
+            for tick in run(&config, 1).ticks {
+                let z = Vector1::new(tick.measurement.unwrap());
+                unscented.predict(config.dt);
+                unscented.update(&z);
+                linear.predict();
+                linear.update(&z);
+                assert!(
+                    (unscented.x - linear.x).abs().max() < tolerance,
+                    "alpha {}",
+                    alpha
+                );
+                assert!(
+                    (unscented.p - linear.p).abs().max() < tolerance,
+                    "alpha {}",
+                    alpha
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected_and_a_non_positive_definite_p_turns_nan() {
+        for parameters in [
+            UnscentedParameters {
+                alpha: 0.0,
+                ..UnscentedParameters::default()
+            },
+            UnscentedParameters {
+                alpha: f64::NAN,
+                ..UnscentedParameters::default()
+            },
+            UnscentedParameters {
+                kappa: -2.0,
+                ..UnscentedParameters::default()
+            },
+        ] {
+            assert!(!parameters.is_valid(2), "{:?}", parameters);
+            let config = SimulateConfig {
+                model: FilterModel::Unscented,
+                unscented: parameters,
+                ..config()
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(SimulateError::InvalidUnscented(_))
+            ));
+        }
+        assert!(UnscentedParameters {
+            kappa: -1.0,
+            ..UnscentedParameters::default()
+        }
+        .is_valid(2));
+
+        let mut filter = UnscentedKalmanFilter::from_config(&config());
+        filter.p = SMatrix::from_diagonal(&Vector2::new(1.0, -1.0));
+        assert!(filter.sigma_points().iter().all(|point| point[0].is_nan()));
+        filter.predict(0.1);
+        assert!(filter.x[0].is_nan());
+    }
+}
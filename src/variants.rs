@@ -0,0 +1,242 @@
+use crate::filter::{Filter, State};
+use crate::metrics;
+use crate::sim::{replay, SimulateConfig, SimulateResult};
+
+/// Algebraically different forms of the known-velocity filter, for `--compare-filters`.
+///
+/// The first four compute the same estimate and differ only in how they carry the variance,
+/// which matters for round-off rather than accuracy. The alpha-beta filter trades the
+/// variance bookkeeping for fixed gains.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterVariant {
+    /// `p ← (1 - k) p`
+    Standard,
+    /// `p ← (1 - k)² p + k² r`, which keeps `p` positive despite round-off
+    Joseph,
+    /// Carries `√p`, whose smaller dynamic range loses less precision
+    SquareRoot,
+    /// Carries the information `1 / p`, where updates become additions
+    Information,
+    /// Fixed position and velocity gains from the steady state, without tracking the variance
+    AlphaBeta,
+}
+
+impl FilterVariant {
+    pub const ALL: [FilterVariant; 5] = [
+        FilterVariant::Standard,
+        FilterVariant::Joseph,
+        FilterVariant::SquareRoot,
+        FilterVariant::Information,
+        FilterVariant::AlphaBeta,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterVariant::Standard => "standard",
+            FilterVariant::Joseph => "joseph",
+            FilterVariant::SquareRoot => "square-root",
+            FilterVariant::Information => "information",
+            FilterVariant::AlphaBeta => "alpha-beta",
+        }
+    }
+}
+
+/// The known-velocity filter in one of its forms.
+pub struct VariantFilter {
+    variant: FilterVariant,
+    state: State,
+    /// Variance of the position estimate in the variant's own representation: `p`, `√p` or
+    /// `1 / p`, and the fixed posterior `p` for alpha-beta
+    variance: f64,
+    r: f64,
+    q: f64,
+    k: f64,
+    innovation: (f64, f64),
+    /// Fixed gains of the alpha-beta filter
+    alpha: f64,
+    beta: f64,
+    /// Time predicted since the latest update, which scales the alpha-beta velocity correction
+    since_update: f64,
+}
+
+impl VariantFilter {
+    /// Builds the filter from the initial guess described by `config`, starting from the same
+    /// variance as `KalmanFilter`.
+    pub fn from_config(variant: FilterVariant, config: &SimulateConfig) -> Self {
+        let (r, q) = (config.r, config.q);
+        // Benedict-Bordner pairing of the velocity gain with the position gain
+        let alpha = metrics::scalar_steady_state_gain(r, q, config.measurement_interval);
+        let beta = alpha * alpha / (2.0 - alpha);
+        let variance = match variant {
+            // The steady-state posterior variance, `k r`
+            FilterVariant::AlphaBeta => alpha * r,
+            _ => 1.0,
+        };
+        VariantFilter {
+            variant,
+            state: State {
+                x: config.initial_position + config.initial_position_offset,
+                v: config.velocity + config.initial_velocity_offset,
+            },
+            variance,
+            r,
+            q,
+            k: 0.0,
+            innovation: (0.0, 0.0),
+            alpha,
+            beta,
+            since_update: 0.0,
+        }
+    }
+}
+
+impl Filter for VariantFilter {
+    fn predict(&mut self, dt: f64) {
+        self.state.x += self.state.v * dt;
+        self.since_update += dt;
+        self.variance = match self.variant {
+            FilterVariant::Standard | FilterVariant::Joseph => self.variance + self.q,
+            FilterVariant::SquareRoot => self.variance.hypot(self.q.sqrt()),
+            FilterVariant::Information => 1.0 / (1.0 / self.variance + self.q),
+            FilterVariant::AlphaBeta => self.variance,
+        };
+    }
+
+    fn update(&mut self, measured_position: f64) {
+        self.update_with_variance(measured_position, self.r);
+    }
+
+    fn update_with_variance(&mut self, measured_position: f64, r: f64) {
+        let innovation = measured_position - self.state.x;
+        self.innovation = (innovation, self.uncertainty() + r);
+        match self.variant {
+            FilterVariant::Standard => {
+                self.k = self.variance / (self.variance + r);
+                self.variance *= 1.0 - self.k;
+            }
+            FilterVariant::Joseph => {
+                let p = self.variance;
+                self.k = p / (p + r);
+                self.variance = (1.0 - self.k).powi(2) * p + self.k * self.k * r;
+            }
+            FilterVariant::SquareRoot => {
+                let p = self.variance * self.variance;
+                self.k = p / (p + r);
+                self.variance *= (r / (p + r)).sqrt();
+            }
+            FilterVariant::Information => {
+                self.variance += 1.0 / r;
+                self.k = 1.0 / (self.variance * r);
+            }
+            FilterVariant::AlphaBeta => {
+                // The steady-state predicted variance is `k r / (1 - k)`
+                self.innovation.1 = r / (1.0 - self.alpha);
+                self.k = self.alpha;
+                if self.since_update > 0.0 {
+                    self.state.v += self.beta / self.since_update * innovation;
+                }
+            }
+        }
+        self.state.x += self.k * innovation;
+        self.since_update = 0.0;
+    }
+
+    fn position(&self) -> f64 {
+        self.state.x
+    }
+
+    fn velocity(&self) -> f64 {
+        self.state.v
+    }
+
+    fn uncertainty(&self) -> f64 {
+        match self.variant {
+            FilterVariant::Standard | FilterVariant::Joseph | FilterVariant::AlphaBeta => {
+                self.variance
+            }
+            FilterVariant::SquareRoot => self.variance * self.variance,
+            FilterVariant::Information => 1.0 / self.variance,
+        }
+    }
+
+    fn gain(&self) -> f64 {
+        self.k
+    }
+
+    fn innovation(&self) -> (f64, f64) {
+        self.innovation
+    }
+
+    fn process_noise(&self) -> f64 {
+        self.q
+    }
+
+    fn set_process_noise(&mut self, q: f64) {
+        self.q = q;
+    }
+}
+
+/// One filter form's run over the measurements of a shared result.
+pub struct VariantRun {
+    pub variant: FilterVariant,
+    /// Root mean square error of `positions` against the truth
+    pub rmse: f64,
+    /// Position estimate at every tick
+    pub positions: Vec<f64>,
+}
+
+/// Runs every filter form over the measurements recorded in `result`, simulated from
+/// `config`, and returns the runs ranked by RMSE, best first.
+pub fn compare_filters(result: &SimulateResult, config: &SimulateConfig) -> Vec<VariantRun> {
+    let mut runs: Vec<_> = FilterVariant::ALL
+        .into_iter()
+        .map(|variant| {
+            let positions = replay(result, config, VariantFilter::from_config(variant, config));
+            VariantRun {
+                variant,
+                rmse: metrics::positions_rmse(result, &positions),
+                positions,
+            }
+        })
+        .collect();
+    runs.sort_by(|a, b| a.rmse.total_cmp(&b.rmse));
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::{config, run};
+
+    #[test]
+    fn standard_and_joseph_forms_agree_on_a_benign_run() {
+        let config = config();
+        let result = run(&config, 1);
+        let runs = compare_filters(&result, &config);
+        let mut variants: Vec<_> = runs.iter().map(|run| run.variant.name()).collect();
+        variants.sort();
+        assert_eq!(
+            variants,
+            [
+                "alpha-beta",
+                "information",
+                "joseph",
+                "square-root",
+                "standard"
+            ]
+        );
+        assert!(runs.windows(2).all(|pair| pair[0].rmse <= pair[1].rmse));
+
+        let positions = |variant| {
+            let run = runs.iter().find(|run| run.variant == variant).unwrap();
+            (run.rmse, &run.positions)
+        };
+        let (standard_rmse, standard) = positions(FilterVariant::Standard);
+        let (joseph_rmse, joseph) = positions(FilterVariant::Joseph);
+        assert_eq!(standard.len(), result.ticks.len());
+        assert!((standard_rmse - joseph_rmse).abs() < 1e-9);
+        for (standard, joseph) in standard.iter().zip(joseph) {
+            assert!((standard - joseph).abs() < 1e-9);
+        }
+    }
+}
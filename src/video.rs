@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use image::Frame;
+
+/// Video codec and container `ffmpeg` encodes the animation to.
+#[derive(Debug, Clone, Copy)]
+pub enum VideoFormat {
+    /// H.264 in an MP4 container
+    Mp4,
+    /// VP9 in a WebM container
+    Webm,
+}
+
+impl VideoFormat {
+    /// `ffmpeg` arguments selecting the codec and its settings.
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            VideoFormat::Mp4 => &["-c:v", "libx264", "-movflags", "+faststart"],
+            VideoFormat::Webm => &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32"],
+        }
+    }
+}
+
+/// Encodes `animation` into a video of `fps` frames per second at `path` by piping its raw
+/// frames through `ffmpeg`, which must be on the `PATH`.
+pub fn encode_video(
+    animation: Vec<Frame>,
+    format: VideoFormat,
+    fps: u16,
+    path: &Path,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = animation
+        .first()
+        .ok_or("no frame to encode")?
+        .buffer()
+        .dimensions();
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &fps.to_string(), "-i", "-"])
+        // 4:2:0 chroma, which every player supports, needs even dimensions
+        .args([
+            "-vf",
+            "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            "-pix_fmt",
+            "yuv420p",
+        ])
+        .args(format.codec_args())
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => "video output needs ffmpeg on the PATH".into(),
+            _ => Box::<dyn Error>::from(err),
+        })?;
+
+    let mut stdin = ffmpeg.stdin.take().ok_or("could not write to ffmpeg")?;
+    let frame_count = animation.len();
+    for (i, frame) in animation.into_iter().enumerate() {
+        // A failed write means ffmpeg gave up, which its exit status explains
+        if stdin.write_all(&frame.into_buffer().into_raw()).is_err() {
+            break;
+        }
+        if show_progress && i % 10 == 9 {
+            eprintln!("{}/{} frames encoded", i + 1, frame_count);
+        }
+    }
+    // Closing stdin ends the input, so ffmpeg can finish the file
+    drop(stdin);
+
+    let status = ffmpeg.wait()?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed with {}", status).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Empty directory named after `test` in the system's temporary directory, to run the binary
+/// in without touching the working tree.
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "kalman-filter-simulator-cli-{}-{}",
+        std::process::id(),
+        test
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the simulator in `dir` with `args`, failing the test unless it exits successfully.
+fn simulate(dir: &Path, args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_kalman-filter-simulator"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+/// Names of the files in `dir`, sorted.
+fn files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn batch_writes_one_output_and_row_per_config() {
+    let dir = scratch_dir("batch");
+    fs::write(dir.join("a.toml"), "total_time = 2.0\n").unwrap();
+    fs::write(dir.join("b.toml"), "total_time = 2.0\nq = 0.1\n").unwrap();
+    let output = simulate(
+        &dir,
+        &[
+            "--config", "a.toml", "--config", "b.toml", "--png", "x.png", "--seed", "1",
+        ],
+    );
+    assert_eq!(files(&dir), ["a.png", "a.toml", "b.png", "b.toml"]);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("config "))
+        .skip(1)
+        .collect();
+    assert_eq!(rows.len(), 2, "{}", stdout);
+    assert!(rows[0].starts_with("a.toml"));
+    assert!(rows[1].starts_with("b.toml"));
+    assert_ne!(rows[0][6..], rows[1][6..]);
+}
+
+#[test]
+fn batch_keeps_the_dots_of_scenario_names() {
+    let dir = scratch_dir("batch-dotted");
+    for name in ["run.v1.toml", "run.v2.toml"] {
+        fs::write(dir.join(name), "total_time = 2.0\n").unwrap();
+    }
+    simulate(
+        &dir,
+        &[
+            "--config",
+            "run.v1.toml",
+            "--config",
+            "run.v2.toml",
+            "--csv",
+            "x.csv",
+            "--static",
+            "x.svg",
+            "--seed",
+            "1",
+        ],
+    );
+    assert_eq!(
+        files(&dir),
+        [
+            "run.v1.csv",
+            "run.v1.svg",
+            "run.v1.toml",
+            "run.v2.csv",
+            "run.v2.svg",
+            "run.v2.toml"
+        ]
+    );
+}
+
+#[test]
+fn dry_run_writes_nothing() {
+    let dir = scratch_dir("dry-run");
+    let output = simulate(
+        &dir,
+        &["--dry-run", "--csv", "run.csv", "--json", "run.json"],
+    );
+    assert!(files(&dir).is_empty());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("steady-state gain:"), "{}", stdout);
+}
+
+#[test]
+fn summary_reports_the_errors_and_gain() {
+    let dir = scratch_dir("summary");
+    let output = simulate(
+        &dir,
+        &[
+            "--seed",
+            "1",
+            "--total-time",
+            "2",
+            "--csv",
+            "run.csv",
+            "--static",
+            "run.svg",
+            "--summary",
+        ],
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let report: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "=== Summary ===")
+        .collect();
+    for label in [
+        "steps:             20",
+        "estimate RMSE:",
+        "measurement RMSE:",
+        "steady-state gain: 0.0488",
+        "convergence time:",
+    ] {
+        assert!(
+            report.iter().any(|line| line.starts_with(label)),
+            "{}",
+            stdout
+        );
+    }
+}
+
+#[test]
+fn manifest_lists_exactly_the_written_files() {
+    let dir = scratch_dir("manifest");
+    simulate(
+        &dir,
+        &[
+            "--seed",
+            "1",
+            "--csv",
+            "run.csv",
+            "--json",
+            "run.json",
+            "--png",
+            "run.png",
+            "--gnuplot",
+            "plots",
+            "--manifest",
+            "manifest.json",
+        ],
+    );
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("manifest.json")).unwrap()).unwrap();
+    let mut outputs: Vec<&str> = manifest["outputs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|output| output.as_str().unwrap())
+        .collect();
+    outputs.sort();
+    let mut written = files(&dir);
+    written.retain(|file| file != "manifest.json");
+    assert_eq!(outputs, written);
+    assert_eq!(manifest["seed"], 1);
+}
+
+#[test]
+fn smoother_overlay_is_drawn_and_beats_the_filter() {
+    let dir = scratch_dir("smoother");
+    let output = simulate(
+        &dir,
+        &["--seed", "1", "--compare-smoother", "--static", "plot.svg"],
+    );
+    assert!(fs::read_to_string(dir.join("plot.svg"))
+        .unwrap()
+        .contains("class=\"smoothed-line\""));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout
+        .lines()
+        .find(|line| line.starts_with("Filter RMSE: "))
+        .unwrap();
+    let rmses: Vec<f64> = line
+        .split([',', ':'])
+        .filter_map(|field| field.trim().parse().ok())
+        .collect();
+    assert_eq!(rmses.len(), 2, "{}", line);
+    assert!(rmses[1] < rmses[0], "{}", line);
+
+    let rejected = Command::new(env!("CARGO_BIN_EXE_kalman-filter-simulator"))
+        .current_dir(&dir)
+        .args(["--compare-smoother", "--filter-model", "constant-velocity"])
+        .args(["--static", "other.svg"])
+        .output()
+        .unwrap();
+    assert!(!rejected.status.success());
+    assert!(!dir.join("other.svg").exists());
+}
+
+#[test]
+fn every_10_exports_a_tenth_of_the_ticks() {
+    let dir = scratch_dir("every");
+    simulate(
+        &dir,
+        &[
+            "--seed",
+            "1",
+            "--total-time",
+            "100",
+            "--every",
+            "10",
+            "--csv",
+            "run.csv",
+            "--static",
+            "plot.svg",
+        ],
+    );
+    let csv = fs::read_to_string(dir.join("run.csv")).unwrap();
+    let rows: Vec<&str> = csv.lines().skip(1).collect();
+    assert_eq!(rows.len(), 100);
+    assert!(rows[0].starts_with("0,"));
+    assert!(rows[1].starts_with("1,"));
+}
+
+#[test]
+fn one_run_writes_every_requested_output() {
+    let dir = scratch_dir("outputs");
+    simulate(
+        &dir,
+        &[
+            "--seed",
+            "1",
+            "--total-time",
+            "2",
+            "--gif",
+            "a.gif",
+            "--png",
+            "b.png",
+            "--csv",
+            "c.csv",
+        ],
+    );
+    assert_eq!(files(&dir), ["a.gif", "b.png", "c.csv"]);
+    for file in ["a.gif", "b.png"] {
+        image::open(dir.join(file)).unwrap();
+    }
+    let csv = fs::read_to_string(dir.join("c.csv")).unwrap();
+    assert_eq!(csv.lines().count(), 21);
+}
+
+#[test]
+fn unseeded_runs_record_a_seed_that_repeats_them() {
+    let dir = scratch_dir("unseeded");
+    let args = ["--total-time", "2", "--csv", "run.csv"];
+    simulate(
+        &dir,
+        &[
+            &args[..],
+            &["--json", "run.json", "--manifest", "manifest.json"],
+        ]
+        .concat(),
+    );
+    let read_json = |file: &str| -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(dir.join(file)).unwrap()).unwrap()
+    };
+    let seed = read_json("manifest.json")["seed"].as_u64().unwrap();
+    assert_eq!(read_json("run.json")["seed"].as_u64(), Some(seed));
+
+    let csv = fs::read_to_string(dir.join("run.csv")).unwrap();
+    fs::remove_file(dir.join("run.csv")).unwrap();
+    simulate(&dir, &[&args[..], &["--seed", &seed.to_string()]].concat());
+    assert_eq!(fs::read_to_string(dir.join("run.csv")).unwrap(), csv);
+}